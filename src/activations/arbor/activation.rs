@@ -1,5 +1,10 @@
+use super::bundle::{BundleFormat, TreeBundle};
+use super::render::{RenderFormat, ResolvedHandles};
 use super::storage::{ArborConfig, ArborStorage};
-use super::types::{ArborEvent, Handle, NodeId, TreeId, TreeSkeleton};
+use super::types::{
+    ArborEvent, BatchOp, Handle, ListOrder, NodeId, NodeType, ResourceState, TreeId, TreeListQuery,
+    TreeSkeleton,
+};
 use crate::plexus::{HubContext, NoParent, PlexusStreamItem};
 use async_stream::stream;
 use futures::{Stream, StreamExt};
@@ -7,6 +12,10 @@ use plexus_macros::hub_methods;
 use serde_json::Value;
 use std::marker::PhantomData;
 use std::sync::{Arc, OnceLock};
+use tokio::sync::broadcast;
+
+/// Most uncached external handles resolved for search or stats in one call
+const RESOLVE_BATCH_LIMIT: usize = 64;
 
 /// Arbor activation - manages conversation trees
 ///
@@ -116,6 +125,104 @@ impl<P: HubContext> Arbor<P> {
         }
     }
 
+    /// Retrieve an archived tree with all nodes (read-only)
+    #[plexus_macros::hub_method(params(tree_id = "UUID of the archived tree to retrieve"))]
+    async fn tree_get_archived(&self, tree_id: TreeId) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.tree_get_archived(&tree_id).await {
+                Ok(tree) => yield ArborEvent::TreeData { tree },
+                Err(e) => {
+                    eprintln!("Error getting archived tree: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Retrieve a tree in batches
+    ///
+    /// Emits a `tree_header` event followed by `tree_nodes` events of at most
+    /// `batch_size` nodes each, so large trees never travel as one message.
+    /// A failure part way through ends the stream with an `error` event.
+    #[plexus_macros::hub_method(
+        streaming,
+        params(
+            tree_id = "UUID of the tree to retrieve",
+            batch_size = "Nodes per batch (default: 500)"
+        )
+    )]
+    async fn tree_get_batched(
+        &self,
+        tree_id: TreeId,
+        batch_size: Option<usize>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        let batch_size = batch_size.unwrap_or(500).max(1);
+
+        stream! {
+            let header = match storage.tree_get_header(&tree_id).await {
+                Ok(tree) => tree,
+                Err(e) => {
+                    eprintln!("Error getting tree: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                    return;
+                }
+            };
+            let node_count = match storage.tree_node_count(&tree_id).await {
+                Ok(count) => count,
+                Err(e) => {
+                    yield ArborEvent::Error { message: format!("Error counting tree nodes: {}", e.message) };
+                    return;
+                }
+            };
+            yield ArborEvent::TreeHeader { tree: header, node_count };
+
+            let mut after = None;
+            loop {
+                match storage.tree_get_nodes_batch(&tree_id, after.as_ref(), batch_size).await {
+                    Ok((nodes, next)) => {
+                        if !nodes.is_empty() {
+                            yield ArborEvent::TreeNodes { tree_id, nodes };
+                        }
+                        match next {
+                            Some(next) => after = Some(next),
+                            None => break,
+                        }
+                    }
+                    Err(e) => {
+                        yield ArborEvent::Error { message: format!("Error getting tree nodes: {}", e.message) };
+                        return;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Retrieve a node and its descendants, optionally depth-limited
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the subtree root",
+        max_depth = "Levels below the node to include (default: unlimited)"
+    ))]
+    async fn tree_get_subtree(
+        &self,
+        tree_id: TreeId,
+        node_id: NodeId,
+        max_depth: Option<usize>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.tree_get_subtree(&tree_id, &node_id, max_depth).await {
+                Ok(nodes) => yield ArborEvent::TreeSubtree { tree_id, root: node_id, max_depth, nodes },
+                Err(e) => {
+                    eprintln!("Error getting subtree: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
     /// Get lightweight tree structure without node data
     #[plexus_macros::hub_method(params(tree_id = "UUID of the tree to retrieve"))]
     async fn tree_get_skeleton(
@@ -134,15 +241,75 @@ impl<P: HubContext> Arbor<P> {
         }
     }
 
-    /// List all active trees
-    #[plexus_macros::hub_method]
-    async fn tree_list(&self) -> impl Stream<Item = ArborEvent> + Send + 'static {
+    /// List trees with a summary of each
+    ///
+    /// Summaries include the metadata `name`, node/leaf counts, depth,
+    /// timestamps and state. Results are sorted by `updated_at` and paged;
+    /// pass the returned `next_cursor` to fetch the next page.
+    #[plexus_macros::hub_method(params(
+        metadata = "Only trees whose metadata has these key/value pairs (optional)",
+        states = "States to include (default: [\"active\"])",
+        order = "Sort by updated_at: desc or asc (default: desc)",
+        cursor = "next_cursor from the previous page (optional)",
+        limit = "Page size (default: 50)"
+    ))]
+    async fn tree_list(
+        &self,
+        metadata: Option<Value>,
+        states: Option<Vec<ResourceState>>,
+        order: Option<ListOrder>,
+        cursor: Option<String>,
+        limit: Option<usize>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
         let storage = self.storage.clone();
+        let query = TreeListQuery {
+            metadata,
+            states: states.unwrap_or_default(),
+            order: order.unwrap_or_default(),
+            cursor,
+            limit: limit.unwrap_or(50),
+        };
+
         stream! {
-            match storage.tree_list(false).await {
-                Ok(tree_ids) => yield ArborEvent::TreeList { tree_ids },
+            match storage.tree_list_summaries(&query).await {
+                Ok((trees, next_cursor)) => yield ArborEvent::TreeSummaries { trees, next_cursor },
                 Err(e) => {
                     eprintln!("Error listing trees: {}", e.message);
+                    yield ArborEvent::TreeSummaries { trees: vec![], next_cursor: None };
+                }
+            }
+        }
+    }
+
+    /// Measure a tree: depth, branching, leaves, node types, text size and
+    /// approximate tokens per leaf path
+    ///
+    /// With `resolve`, up to `RESOLVE_BATCH_LIMIT` external handles without
+    /// cached content are resolved through the parent context first so they
+    /// count toward the sizes.
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        resolve = "Resolve uncached external handles before measuring (default: false)"
+    ))]
+    async fn tree_stats(
+        &self,
+        tree_id: TreeId,
+        resolve: Option<bool>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        let hub = self.hub.clone();
+
+        stream! {
+            if resolve.unwrap_or(false) {
+                if let Some(parent) = hub.get() {
+                    cache_unresolved_handles(&storage, parent, Some(tree_id), RESOLVE_BATCH_LIMIT).await;
+                }
+            }
+
+            match storage.tree_stats(&tree_id).await {
+                Ok(stats) => yield ArborEvent::TreeStats { stats },
+                Err(e) => {
+                    eprintln!("Error computing tree stats: {}", e.message);
                     yield ArborEvent::TreeList { tree_ids: vec![] };
                 }
             }
@@ -219,12 +386,36 @@ impl<P: HubContext> Arbor<P> {
         }
     }
 
+    /// Restore a tree scheduled for deletion or archived
+    ///
+    /// Moves the tree back to active and claims one reference for `owner_id`.
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree to restore",
+        owner_id = "Owner identifier to claim the restored tree for"
+    ))]
+    async fn tree_restore(
+        &self,
+        tree_id: TreeId,
+        owner_id: String,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.tree_restore(&tree_id, &owner_id).await {
+                Ok(new_count) => yield ArborEvent::TreeRestored { tree_id, owner_id, new_count },
+                Err(e) => {
+                    eprintln!("Error restoring tree: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
     /// List trees scheduled for deletion
     #[plexus_macros::hub_method]
     async fn tree_list_scheduled(&self) -> impl Stream<Item = ArborEvent> + Send + 'static {
         let storage = self.storage.clone();
         stream! {
-            match storage.tree_list(true).await {
+            match storage.tree_list_scheduled().await {
                 Ok(tree_ids) => yield ArborEvent::TreesScheduled { tree_ids },
                 Err(e) => {
                     eprintln!("Error listing scheduled trees: {}", e.message);
@@ -239,7 +430,7 @@ impl<P: HubContext> Arbor<P> {
     async fn tree_list_archived(&self) -> impl Stream<Item = ArborEvent> + Send + 'static {
         let storage = self.storage.clone();
         stream! {
-            match storage.tree_list(true).await {
+            match storage.tree_list_archived().await {
                 Ok(tree_ids) => yield ArborEvent::TreesArchived { tree_ids },
                 Err(e) => {
                     eprintln!("Error listing archived trees: {}", e.message);
@@ -249,6 +440,30 @@ impl<P: HubContext> Arbor<P> {
         }
     }
 
+    /// Run the lifecycle cleanup once (archive scheduled resources, purge archived ones)
+    #[plexus_macros::hub_method]
+    async fn cleanup_run(&self) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.cleanup_run().await {
+                Ok(report) => yield ArborEvent::CleanupReport { report },
+                Err(e) => {
+                    eprintln!("Error running cleanup: {}", e.message);
+                    yield ArborEvent::CleanupStatus { status: storage.cleanup_status() };
+                }
+            }
+        }
+    }
+
+    /// Get the cleanup configuration and the last cleanup report
+    #[plexus_macros::hub_method]
+    async fn cleanup_status(&self) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            yield ArborEvent::CleanupStatus { status: storage.cleanup_status() };
+        }
+    }
+
     /// Create a text node in a tree
     #[plexus_macros::hub_method(params(
         tree_id = "UUID of the tree",
@@ -301,218 +516,975 @@ impl<P: HubContext> Arbor<P> {
         }
     }
 
-    /// Get a node by ID
+    /// Update a node copy-on-write, keeping the old version in its lineage
     #[plexus_macros::hub_method(params(
         tree_id = "UUID of the tree",
-        node_id = "UUID of the node"
+        node_id = "UUID of the node to update",
+        content = "New text content (makes the new node a text node)",
+        handle = "New handle (makes the new node an external node)",
+        metadata = "New node metadata (default: keep the old metadata)"
     ))]
-    async fn node_get(
+    async fn node_update(
         &self,
         tree_id: TreeId,
         node_id: NodeId,
+        content: Option<String>,
+        handle: Option<Handle>,
+        metadata: Option<Value>,
     ) -> impl Stream<Item = ArborEvent> + Send + 'static {
         let storage = self.storage.clone();
         stream! {
-            match storage.node_get(&tree_id, &node_id).await {
-                Ok(node) => yield ArborEvent::NodeData { tree_id, node },
+            match storage.node_update(&tree_id, &node_id, content, handle, metadata).await {
+                Ok(new_id) => yield ArborEvent::NodeUpdated { tree_id, old_id: node_id, new_id },
                 Err(e) => {
-                    eprintln!("Error getting node: {}", e.message);
+                    eprintln!("Error updating node: {}", e.message);
                     yield ArborEvent::TreeList { tree_ids: vec![] };
                 }
             }
         }
     }
 
-    /// Get the children of a node
+    /// Get the version lineage of a node (oldest first)
     #[plexus_macros::hub_method(params(
         tree_id = "UUID of the tree",
-        node_id = "UUID of the node"
+        node_id = "UUID of any version of the node"
     ))]
-    async fn node_get_children(
+    async fn node_history(
         &self,
         tree_id: TreeId,
         node_id: NodeId,
     ) -> impl Stream<Item = ArborEvent> + Send + 'static {
         let storage = self.storage.clone();
         stream! {
-            match storage.node_get_children(&tree_id, &node_id).await {
-                Ok(children) => yield ArborEvent::NodeChildren { tree_id, node_id, children },
+            match storage.node_history(&tree_id, &node_id).await {
+                Ok(versions) => yield ArborEvent::NodeHistory { tree_id, node_id, versions },
                 Err(e) => {
-                    eprintln!("Error getting node children: {}", e.message);
+                    eprintln!("Error getting node history: {}", e.message);
                     yield ArborEvent::TreeList { tree_ids: vec![] };
                 }
             }
         }
     }
 
-    /// Get the parent of a node
+    /// Move a node and its subtree under a new parent
+    ///
+    /// Fails if the new parent is the node itself or one of its descendants.
     #[plexus_macros::hub_method(params(
         tree_id = "UUID of the tree",
-        node_id = "UUID of the node"
+        node_id = "UUID of the node to move (not the root)",
+        new_parent = "UUID of the new parent node",
+        position = "Index among the new parent's active children (optional, default: append)"
     ))]
-    async fn node_get_parent(
+    async fn node_move(
         &self,
         tree_id: TreeId,
         node_id: NodeId,
+        new_parent: NodeId,
+        position: Option<usize>,
     ) -> impl Stream<Item = ArborEvent> + Send + 'static {
         let storage = self.storage.clone();
         stream! {
-            match storage.node_get_parent(&tree_id, &node_id).await {
-                Ok(parent) => yield ArborEvent::NodeParent { tree_id, node_id, parent },
+            match storage.node_move(&tree_id, &node_id, &new_parent, position).await {
+                Ok((old_parent, position)) => yield ArborEvent::NodeMoved { tree_id, node_id, old_parent, new_parent, position },
                 Err(e) => {
-                    eprintln!("Error getting node parent: {}", e.message);
+                    eprintln!("Error moving node: {}", e.message);
                     yield ArborEvent::TreeList { tree_ids: vec![] };
                 }
             }
         }
     }
 
-    /// Get the path from root to a node
+    /// Reorder a node's children
     #[plexus_macros::hub_method(params(
         tree_id = "UUID of the tree",
-        node_id = "UUID of the node"
+        node_id = "UUID of the parent node",
+        children = "All of the node's current children, in the new order"
     ))]
-    async fn node_get_path(
+    async fn node_reorder_children(
         &self,
         tree_id: TreeId,
         node_id: NodeId,
+        children: Vec<NodeId>,
     ) -> impl Stream<Item = ArborEvent> + Send + 'static {
         let storage = self.storage.clone();
         stream! {
-            match storage.node_get_path(&tree_id, &node_id).await {
-                Ok(path) => yield ArborEvent::ContextPath { tree_id, path },
+            match storage.node_reorder_children(&tree_id, &node_id, &children).await {
+                Ok(()) => yield ArborEvent::ChildrenReordered { tree_id, node_id, children },
                 Err(e) => {
-                    eprintln!("Error getting node path: {}", e.message);
+                    eprintln!("Error reordering children: {}", e.message);
                     yield ArborEvent::TreeList { tree_ids: vec![] };
                 }
             }
         }
     }
 
-    /// List all leaf nodes in a tree
-    #[plexus_macros::hub_method(params(tree_id = "UUID of the tree"))]
-    async fn context_list_leaves(
+    /// Merge a patch into a node's metadata in place
+    ///
+    /// Uses JSON merge patch semantics: objects merge recursively and `null`
+    /// removes a key.
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the node to update",
+        patch = "Metadata patch to merge (null values remove keys)"
+    ))]
+    async fn node_update_metadata(
         &self,
         tree_id: TreeId,
+        node_id: NodeId,
+        patch: Value,
     ) -> impl Stream<Item = ArborEvent> + Send + 'static {
         let storage = self.storage.clone();
         stream! {
-            match storage.context_list_leaves(&tree_id).await {
-                Ok(leaves) => yield ArborEvent::ContextLeaves { tree_id, leaves },
+            match storage.node_update_metadata(&tree_id, &node_id, patch).await {
+                Ok(metadata) => yield ArborEvent::NodeMetadataUpdated { tree_id, node_id, metadata },
                 Err(e) => {
-                    eprintln!("Error listing leaf nodes: {}", e.message);
+                    eprintln!("Error updating node metadata: {}", e.message);
                     yield ArborEvent::TreeList { tree_ids: vec![] };
                 }
             }
         }
     }
 
-    /// Get the full path data from root to a node
+    /// Find nodes by metadata key/value, within one tree or across all trees
+    #[plexus_macros::hub_method(params(
+        filter = "JSON object of metadata keys and the values they must equal",
+        tree_id = "Restrict the query to one tree (default: all active trees)",
+        limit = "Maximum number of results (default: 100)"
+    ))]
+    async fn node_query(
+        &self,
+        filter: Value,
+        tree_id: Option<TreeId>,
+        limit: Option<usize>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.node_query(tree_id.as_ref(), &filter, limit.unwrap_or(100)).await {
+                Ok(matches) => yield ArborEvent::NodeQueryResults { filter, matches },
+                Err(e) => {
+                    eprintln!("Error querying nodes: {}", e.message);
+                    yield ArborEvent::NodeQueryResults { filter, matches: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Apply node creations, metadata updates and claims atomically
+    ///
+    /// Operations may reference nodes created earlier in the same batch with
+    /// `{"op": index}`. Either every operation is applied or none is.
     #[plexus_macros::hub_method(params(
         tree_id = "UUID of the tree",
-        node_id = "UUID of the target node"
+        ops = "Operations to apply, in order"
     ))]
-    async fn context_get_path(
+    async fn batch(
+        &self,
+        tree_id: TreeId,
+        ops: Vec<BatchOp>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.batch(&tree_id, ops).await {
+                Ok(node_ids) => yield ArborEvent::BatchApplied { tree_id, node_ids },
+                Err(e) => {
+                    eprintln!("Error applying batch: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Claim ownership of a node (increment reference count)
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the node to claim",
+        owner_id = "Owner identifier",
+        count = "Number of references to add (default: 1)"
+    ))]
+    async fn node_claim(
         &self,
         tree_id: TreeId,
         node_id: NodeId,
+        owner_id: String,
+        count: i64,
     ) -> impl Stream<Item = ArborEvent> + Send + 'static {
         let storage = self.storage.clone();
         stream! {
-            match storage.context_get_path(&tree_id, &node_id).await {
-                Ok(nodes) => yield ArborEvent::ContextPathData { tree_id, nodes },
+            match storage.node_claim(&tree_id, &node_id, &owner_id, count).await {
+                Ok(new_count) => yield ArborEvent::NodeClaimed { tree_id, node_id, owner_id, new_count },
                 Err(e) => {
-                    eprintln!("Error getting context path: {}", e.message);
+                    eprintln!("Error claiming node: {}", e.message);
                     yield ArborEvent::TreeList { tree_ids: vec![] };
                 }
             }
         }
     }
 
-    /// Get all external handles in the path to a node
+    /// Release ownership of a node (decrement reference count)
     #[plexus_macros::hub_method(params(
         tree_id = "UUID of the tree",
-        node_id = "UUID of the target node"
+        node_id = "UUID of the node to release",
+        owner_id = "Owner identifier",
+        count = "Number of references to remove (default: 1)"
     ))]
-    async fn context_get_handles(
+    async fn node_release(
         &self,
         tree_id: TreeId,
         node_id: NodeId,
+        owner_id: String,
+        count: i64,
     ) -> impl Stream<Item = ArborEvent> + Send + 'static {
         let storage = self.storage.clone();
         stream! {
-            match storage.context_get_handles(&tree_id, &node_id).await {
-                Ok(handles) => yield ArborEvent::ContextHandles { tree_id, handles },
+            match storage.node_release(&tree_id, &node_id, &owner_id, count).await {
+                Ok(new_count) => yield ArborEvent::NodeReleased { tree_id, node_id, owner_id, new_count },
                 Err(e) => {
-                    eprintln!("Error getting context handles: {}", e.message);
+                    eprintln!("Error releasing node: {}", e.message);
                     yield ArborEvent::TreeList { tree_ids: vec![] };
                 }
             }
         }
     }
 
-    /// Render tree as text visualization
+    /// Get reference information for a node
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the node"
+    ))]
+    async fn node_get_refs(
+        &self,
+        tree_id: TreeId,
+        node_id: NodeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.node_get_refs(&tree_id, &node_id).await {
+                Ok(refs) => yield ArborEvent::NodeRefs { tree_id, node_id, refs },
+                Err(e) => {
+                    eprintln!("Error getting node refs: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Restore a node scheduled for deletion or archived
     ///
-    /// If parent context is available, automatically resolves handles to show
-    /// actual content. Otherwise, shows handle references.
-    #[plexus_macros::hub_method(params(tree_id = "UUID of the tree to render"))]
-    async fn tree_render(
+    /// Moves the node back to active and claims one reference for `owner_id`.
+    /// The node's parent must already be active.
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the node to restore",
+        owner_id = "Owner identifier to claim the restored node for"
+    ))]
+    async fn node_restore(
         &self,
         tree_id: TreeId,
+        node_id: NodeId,
+        owner_id: String,
     ) -> impl Stream<Item = ArborEvent> + Send + 'static {
         let storage = self.storage.clone();
-        let hub = self.hub.clone();
+        stream! {
+            match storage.node_restore(&tree_id, &node_id, &owner_id).await {
+                Ok(new_count) => yield ArborEvent::NodeRestored { tree_id, node_id, owner_id, new_count },
+                Err(e) => {
+                    eprintln!("Error restoring node: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
 
+    /// List nodes scheduled for deletion in a tree
+    #[plexus_macros::hub_method(params(tree_id = "UUID of the tree"))]
+    async fn node_list_scheduled(
+        &self,
+        tree_id: TreeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
         stream! {
-            match storage.tree_get(&tree_id).await {
-                Ok(tree) => {
-                    // Check if we have parent context for handle resolution
-                    let render = if let Some(parent) = hub.get() {
-                        // Resolve handles through parent context
-                        tree.render_resolved(|handle| {
-                            let parent = parent.clone();
-                            let handle = handle.clone();
-                            async move {
-                                resolve_handle_to_string(&parent, &handle).await
-                            }
-                        }).await
-                    } else {
-                        // No parent context - use simple render (shows handle references)
-                        tree.render()
-                    };
-                    yield ArborEvent::TreeRender { tree_id, render };
+            match storage.node_list_scheduled(&tree_id).await {
+                Ok(node_ids) => yield ArborEvent::NodesScheduled { tree_id, node_ids },
+                Err(e) => {
+                    eprintln!("Error listing scheduled nodes: {}", e.message);
+                    yield ArborEvent::NodesScheduled { tree_id, node_ids: vec![] };
                 }
+            }
+        }
+    }
+
+    /// Delete a leaf node (schedule it for deletion)
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the node to delete (must have no active children or owner claims)"
+    ))]
+    async fn node_delete(
+        &self,
+        tree_id: TreeId,
+        node_id: NodeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.node_delete(&tree_id, &node_id).await {
+                Ok(scheduled_at) => yield ArborEvent::NodeScheduledDeletion { tree_id, node_id, scheduled_at },
                 Err(e) => {
-                    eprintln!("Error rendering tree: {}", e.message);
-                    yield ArborEvent::TreeRender { tree_id, render: format!("Error: {}", e.message) };
+                    eprintln!("Error deleting node: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
                 }
             }
         }
     }
-}
 
-/// Resolve a handle through HubContext and extract a display string
-async fn resolve_handle_to_string<P: HubContext>(parent: &P, handle: &Handle) -> String {
-    match parent.resolve_handle(handle).await {
-        Ok(mut stream) => {
-            // Collect the first data item from the stream
-            while let Some(item) = stream.next().await {
-                match item {
-                    PlexusStreamItem::Data { content, .. } => {
-                        // Try to extract a meaningful display string from the resolved content
-                        return extract_display_content(&content);
-                    }
+    /// Delete a node and all of its descendants (schedule them for deletion)
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the subtree root to prune (no node in it may be claimed)"
+    ))]
+    async fn node_prune_subtree(
+        &self,
+        tree_id: TreeId,
+        node_id: NodeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.node_prune_subtree(&tree_id, &node_id).await {
+                Ok(node_ids) => yield ArborEvent::NodesScheduled { tree_id, node_ids },
+                Err(e) => {
+                    eprintln!("Error pruning subtree: {}", e.message);
+                    yield ArborEvent::NodesScheduled { tree_id, node_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Copy a subtree under a new parent, possibly in another tree
+    #[plexus_macros::hub_method(params(
+        src_tree = "UUID of the source tree",
+        src_node = "UUID of the subtree root to copy",
+        dst_tree = "UUID of the destination tree",
+        dst_parent = "UUID of the node to attach the copy under"
+    ))]
+    async fn node_copy_subtree(
+        &self,
+        src_tree: TreeId,
+        src_node: NodeId,
+        dst_tree: TreeId,
+        dst_parent: NodeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.node_copy_subtree(&src_tree, &src_node, &dst_tree, &dst_parent).await {
+                Ok(node_id) => yield ArborEvent::SubtreeCopied { tree_id: dst_tree, node_id, parent: dst_parent },
+                Err(e) => {
+                    eprintln!("Error copying subtree: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Clone a tree, or the path from its root to one node, into a new tree
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree to clone",
+        from_node = "Only clone the path from the root to this node (default: whole tree)",
+        owner_id = "Owner identifier for the new tree"
+    ))]
+    async fn tree_clone(
+        &self,
+        tree_id: TreeId,
+        from_node: Option<NodeId>,
+        owner_id: String,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.tree_clone(&tree_id, from_node, &owner_id).await {
+                Ok((new_tree, head)) => yield ArborEvent::TreeCloned { tree_id: new_tree, source_tree_id: tree_id, head },
+                Err(e) => {
+                    eprintln!("Error cloning tree: {}", e.message);
+                    yield ArborEvent::TreeCreated { tree_id: TreeId::nil() };
+                }
+            }
+        }
+    }
+
+    /// Get a node by ID
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the node"
+    ))]
+    async fn node_get(
+        &self,
+        tree_id: TreeId,
+        node_id: NodeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.node_get(&tree_id, &node_id).await {
+                Ok(node) => yield ArborEvent::NodeData { tree_id, node },
+                Err(e) => {
+                    eprintln!("Error getting node: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Get the children of a node
+    ///
+    /// Passing `cursor` or `limit` returns one page of children in child
+    /// order; pass the returned `next_cursor` to fetch the next page.
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the node",
+        cursor = "Last child of the previous page (optional)",
+        limit = "Page size (default: 100 when paging)"
+    ))]
+    async fn node_get_children(
+        &self,
+        tree_id: TreeId,
+        node_id: NodeId,
+        cursor: Option<NodeId>,
+        limit: Option<usize>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            let result = if cursor.is_some() || limit.is_some() {
+                storage
+                    .node_get_children_page(&tree_id, &node_id, cursor.as_ref(), limit.unwrap_or(100))
+                    .await
+            } else {
+                storage.node_get_children(&tree_id, &node_id).await.map(|c| (c, None))
+            };
+
+            match result {
+                Ok((children, next_cursor)) => yield ArborEvent::NodeChildren { tree_id, node_id, children, next_cursor },
+                Err(e) => {
+                    eprintln!("Error getting node children: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Get the parent of a node
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the node"
+    ))]
+    async fn node_get_parent(
+        &self,
+        tree_id: TreeId,
+        node_id: NodeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.node_get_parent(&tree_id, &node_id).await {
+                Ok(parent) => yield ArborEvent::NodeParent { tree_id, node_id, parent },
+                Err(e) => {
+                    eprintln!("Error getting node parent: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Get the path from root to a node
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the node"
+    ))]
+    async fn node_get_path(
+        &self,
+        tree_id: TreeId,
+        node_id: NodeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.node_get_path(&tree_id, &node_id).await {
+                Ok(path) => yield ArborEvent::ContextPath { tree_id, path },
+                Err(e) => {
+                    eprintln!("Error getting node path: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// List all leaf nodes in a tree
+    #[plexus_macros::hub_method(params(tree_id = "UUID of the tree"))]
+    async fn context_list_leaves(
+        &self,
+        tree_id: TreeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.context_list_leaves(&tree_id).await {
+                Ok(leaves) => yield ArborEvent::ContextLeaves { tree_id, leaves },
+                Err(e) => {
+                    eprintln!("Error listing leaf nodes: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Get the full path data from root to a node
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the target node"
+    ))]
+    async fn context_get_path(
+        &self,
+        tree_id: TreeId,
+        node_id: NodeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.context_get_path(&tree_id, &node_id).await {
+                Ok(nodes) => yield ArborEvent::ContextPathData { tree_id, nodes },
+                Err(e) => {
+                    eprintln!("Error getting context path: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Find the lowest common ancestor of two nodes
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        a = "UUID of the first node",
+        b = "UUID of the second node"
+    ))]
+    async fn node_common_ancestor(
+        &self,
+        tree_id: TreeId,
+        a: NodeId,
+        b: NodeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.node_common_ancestor(&tree_id, &a, &b).await {
+                Ok(ancestor) => yield ArborEvent::CommonAncestor { tree_id, a, b, ancestor },
+                Err(e) => {
+                    eprintln!("Error finding common ancestor: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Compare two branches: their common ancestor and the divergent node sequences
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        a = "UUID of the tip of the first branch",
+        b = "UUID of the tip of the second branch"
+    ))]
+    async fn branch_diff(
+        &self,
+        tree_id: TreeId,
+        a: NodeId,
+        b: NodeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.branch_diff(&tree_id, &a, &b).await {
+                Ok((ancestor, left, right)) => yield ArborEvent::BranchDiff { tree_id, ancestor, left, right },
+                Err(e) => {
+                    eprintln!("Error diffing branches: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Get all external handles in the path to a node
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree",
+        node_id = "UUID of the target node"
+    ))]
+    async fn context_get_handles(
+        &self,
+        tree_id: TreeId,
+        node_id: NodeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            match storage.context_get_handles(&tree_id, &node_id).await {
+                Ok(handles) => yield ArborEvent::ContextHandles { tree_id, handles },
+                Err(e) => {
+                    eprintln!("Error getting context handles: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Full-text search across trees, ranked best first
+    ///
+    /// With `include_resolved` and a `tree_id`, up to `RESOLVE_BATCH_LIMIT`
+    /// external handles of that tree that have not been indexed yet are
+    /// resolved through the parent context and cached before searching.
+    /// Searches across all trees only use content already indexed, e.g. by
+    /// `index_handles`.
+    #[plexus_macros::hub_method(params(
+        query = "Search terms (all terms must match)",
+        tree_id = "Restrict the search to one tree (default: all active trees)",
+        include_resolved = "Also search resolved external-handle content (default: false)",
+        limit = "Maximum number of results (default: 20)"
+    ))]
+    async fn search(
+        &self,
+        query: String,
+        tree_id: Option<TreeId>,
+        include_resolved: Option<bool>,
+        limit: Option<usize>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        let hub = self.hub.clone();
+        let include_resolved = include_resolved.unwrap_or(false);
+
+        stream! {
+            if let (true, Some(tree_id), Some(parent)) = (include_resolved, tree_id, hub.get()) {
+                cache_unresolved_handles(&storage, parent, Some(tree_id), RESOLVE_BATCH_LIMIT).await;
+            }
+
+            match storage.search(&query, tree_id, include_resolved, limit.unwrap_or(20)).await {
+                Ok(hits) => yield ArborEvent::SearchResults { query, hits },
+                Err(e) => {
+                    eprintln!("Error searching trees: {}", e.message);
+                    yield ArborEvent::SearchResults { query, hits: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Resolve and cache external handles for search
+    ///
+    /// Indexes at most `limit` uncached handles per call, oldest first;
+    /// handles that fail are recorded and skipped for a while. Call again
+    /// until `remaining` is 0 to index everything.
+    #[plexus_macros::hub_method(params(
+        tree_id = "Only index this tree (default: all active trees)",
+        limit = "Maximum number of handles to resolve (default: 64)"
+    ))]
+    async fn index_handles(
+        &self,
+        tree_id: Option<TreeId>,
+        limit: Option<usize>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        let hub = self.hub.clone();
+        let limit = limit.unwrap_or(RESOLVE_BATCH_LIMIT);
+
+        stream! {
+            let (resolved, failed) = match hub.get() {
+                Some(parent) => cache_unresolved_handles(&storage, parent, tree_id, limit).await,
+                None => (0, 0),
+            };
+            let remaining = match storage.search_unresolved_count(tree_id).await {
+                Ok(remaining) => remaining,
+                Err(e) => {
+                    eprintln!("Error listing unresolved handles: {}", e.message);
+                    0
+                }
+            };
+            yield ArborEvent::HandlesIndexed { tree_id, resolved, failed, remaining };
+        }
+    }
+
+    /// Watch a tree for changes
+    ///
+    /// Keeps the subscription open and emits an event for every write to the
+    /// tree, whichever activation made it. Ends once the tree is purged.
+    #[plexus_macros::hub_method(
+        streaming,
+        params(tree_id = "UUID of the tree to watch")
+    )]
+    async fn tree_watch(
+        &self,
+        tree_id: TreeId,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        // Subscribe before the stream is polled so no write is missed
+        let mut events = self.storage.subscribe();
+        stream! {
+            loop {
+                match events.recv().await {
+                    Ok(event) if event.tree_id() == Some(tree_id) => {
+                        let purged = matches!(event, ArborEvent::TreeDeleted { .. });
+                        yield event;
+                        if purged {
+                            break;
+                        }
+                    }
+                    Ok(_) => continue,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        eprintln!("Tree watch for {} skipped {} events", tree_id, skipped);
+                        // Tell the watcher to refetch, since it missed changes
+                        yield ArborEvent::TreeUpdated { tree_id };
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                }
+            }
+        }
+    }
+
+    /// Export a tree as a portable JSON or JSONL bundle
+    ///
+    /// With `include_resolved`, the content behind every external handle is
+    /// resolved through the parent context and embedded in the bundle.
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree to export",
+        format = "Bundle format: json or jsonl (default: json)",
+        include_resolved = "Embed resolved content for external handles (default: false)"
+    ))]
+    async fn tree_export(
+        &self,
+        tree_id: TreeId,
+        format: Option<BundleFormat>,
+        include_resolved: Option<bool>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        let hub = self.hub.clone();
+        let format = format.unwrap_or_default();
+
+        stream! {
+            let mut bundle = match storage.tree_export(&tree_id).await {
+                Ok(bundle) => bundle,
+                Err(e) => {
+                    eprintln!("Error exporting tree: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                    return;
+                }
+            };
+
+            if include_resolved.unwrap_or(false) {
+                if let Some(parent) = hub.get() {
+                    for node in bundle.nodes.iter_mut() {
+                        if let NodeType::External { handle } = &node.data {
+                            node.resolved = resolve_handle_to_value(parent, handle).await.ok();
+                        }
+                    }
+                }
+            }
+
+            match bundle.serialize(format) {
+                Ok(bundle) => yield ArborEvent::TreeExported { tree_id, format, bundle },
+                Err(e) => {
+                    eprintln!("Error serializing bundle: {}", e.message);
+                    yield ArborEvent::TreeList { tree_ids: vec![] };
+                }
+            }
+        }
+    }
+
+    /// Import a JSON or JSONL bundle as a new tree
+    #[plexus_macros::hub_method(params(
+        bundle = "Serialized bundle (JSON or JSONL, detected automatically)",
+        owner_id = "Owner identifier for the new tree",
+        inline_resolved = "Turn external nodes with resolved content into text nodes (default: false)"
+    ))]
+    async fn tree_import(
+        &self,
+        bundle: String,
+        owner_id: String,
+        inline_resolved: Option<bool>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        stream! {
+            let bundle = match TreeBundle::parse(&bundle) {
+                Ok(bundle) => bundle,
+                Err(e) => {
+                    eprintln!("Error parsing bundle: {}", e.message);
+                    yield ArborEvent::TreeCreated { tree_id: TreeId::nil() };
+                    return;
+                }
+            };
+
+            match storage.tree_import(&bundle, &owner_id, inline_resolved.unwrap_or(false)).await {
+                Ok(tree_id) => yield ArborEvent::TreeImported {
+                    tree_id,
+                    source_tree_id: bundle.header.tree_id,
+                    node_count: bundle.nodes.len(),
+                },
+                Err(e) => {
+                    eprintln!("Error importing tree: {}", e.message);
+                    yield ArborEvent::TreeCreated { tree_id: TreeId::nil() };
+                }
+            }
+        }
+    }
+
+    /// Render tree as text visualization
+    ///
+    /// If parent context is available, automatically resolves handles to show
+    /// actual content. Otherwise, shows handle references. The `markdown`
+    /// format renders only the path from the root to `node_id`.
+    #[plexus_macros::hub_method(params(
+        tree_id = "UUID of the tree to render",
+        format = "Output format: text, dot, mermaid, json or markdown (default: text)",
+        node_id = "End of the path to render (required for markdown)"
+    ))]
+    async fn tree_render(
+        &self,
+        tree_id: TreeId,
+        format: Option<RenderFormat>,
+        node_id: Option<NodeId>,
+    ) -> impl Stream<Item = ArborEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        let hub = self.hub.clone();
+        let format = format.unwrap_or_default();
+
+        stream! {
+            let tree = match storage.tree_get(&tree_id).await {
+                Ok(tree) => tree,
+                Err(e) => {
+                    eprintln!("Error rendering tree: {}", e.message);
+                    yield ArborEvent::TreeRender { tree_id, render: format!("Error: {}", e.message) };
+                    return;
+                }
+            };
+
+            if format == RenderFormat::Text {
+                // Check if we have parent context for handle resolution
+                let render = if let Some(parent) = hub.get() {
+                    // Resolve handles through parent context
+                    tree.render_resolved(|handle| {
+                        let parent = parent.clone();
+                        let handle = handle.clone();
+                        async move {
+                            resolve_handle_to_string(&parent, &handle).await
+                        }
+                    }).await
+                } else {
+                    // No parent context - use simple render (shows handle references)
+                    tree.render()
+                };
+                yield ArborEvent::TreeRender { tree_id, render };
+                return;
+            }
+
+            // Markdown only needs the nodes on its path resolved
+            let wanted: Vec<NodeId> = match (format, node_id) {
+                (RenderFormat::Markdown, Some(node_id)) => {
+                    let mut path = Vec::new();
+                    let mut current = Some(node_id);
+                    while let Some(id) = current {
+                        path.push(id);
+                        current = tree.nodes.get(&id).and_then(|n| n.parent);
+                    }
+                    path
+                }
+                _ => tree.nodes.keys().copied().collect(),
+            };
+
+            let mut resolved = ResolvedHandles::new();
+            if let Some(parent) = hub.get() {
+                for id in wanted {
+                    if let Some(NodeType::External { handle }) = tree.nodes.get(&id).map(|n| &n.data) {
+                        if let Ok(data) = resolve_handle_to_value(parent, handle).await {
+                            resolved.insert(id, data);
+                        }
+                    }
+                }
+            }
+
+            let render = match format {
+                RenderFormat::Text => unreachable!("handled above"),
+                RenderFormat::Dot => Ok(tree.render_dot(&resolved)),
+                RenderFormat::Mermaid => Ok(tree.render_mermaid(&resolved)),
+                RenderFormat::Json => Ok(serde_json::to_string_pretty(&tree.render_json(&resolved))
+                    .unwrap_or_default()),
+                RenderFormat::Markdown => match node_id {
+                    Some(node_id) => tree.render_markdown(&node_id, &resolved),
+                    None => Err("markdown format requires node_id".into()),
+                },
+            };
+
+            match render {
+                Ok(render) => yield ArborEvent::TreeRender { tree_id, render },
+                Err(e) => {
+                    eprintln!("Error rendering tree: {}", e.message);
+                    yield ArborEvent::TreeRender { tree_id, render: format!("Error: {}", e.message) };
+                }
+            }
+        }
+    }
+}
+
+/// Resolve up to `limit` external handles that have no cached content and
+/// cache them
+///
+/// Failures are recorded so they are not retried on every call. Returns the
+/// number of handles resolved and failed.
+async fn cache_unresolved_handles<P: HubContext>(
+    storage: &ArborStorage,
+    parent: &P,
+    tree_id: Option<TreeId>,
+    limit: usize,
+) -> (usize, usize) {
+    let unresolved = match storage.search_unresolved_handles(tree_id, limit).await {
+        Ok(unresolved) => unresolved,
+        Err(e) => {
+            eprintln!("Error listing unresolved handles: {}", e.message);
+            return (0, 0);
+        }
+    };
+
+    let (mut resolved, mut failed) = (0, 0);
+    for (node_id, handle) in unresolved {
+        let cached = match resolve_handle_to_value(parent, &handle).await {
+            Ok(content) => {
+                let text = content
+                    .get("content")
+                    .and_then(|c| c.as_str())
+                    .map(str::to_string)
+                    .unwrap_or_else(|| content.to_string());
+                resolved += 1;
+                storage.node_set_resolved(&node_id, &text).await
+            }
+            Err(placeholder) => {
+                failed += 1;
+                storage.node_set_resolve_failed(&node_id, &placeholder).await
+            }
+        };
+        if let Err(e) = cached {
+            eprintln!("Error caching resolved content: {}", e.message);
+        }
+    }
+    (resolved, failed)
+}
+
+/// Resolve a handle through HubContext and extract a display string
+async fn resolve_handle_to_string<P: HubContext>(parent: &P, handle: &Handle) -> String {
+    match resolve_handle_to_value(parent, handle).await {
+        // Try to extract a meaningful display string from the resolved content
+        Ok(content) => extract_display_content(&content),
+        Err(placeholder) => placeholder,
+    }
+}
+
+/// Resolve a handle through HubContext to its first data item
+///
+/// On failure, returns a bracketed placeholder describing why.
+async fn resolve_handle_to_value<P: HubContext>(parent: &P, handle: &Handle) -> Result<Value, String> {
+    match parent.resolve_handle(handle).await {
+        Ok(mut stream) => {
+            // Collect the first data item from the stream
+            while let Some(item) = stream.next().await {
+                match item {
+                    PlexusStreamItem::Data { content, .. } => return Ok(content),
                     PlexusStreamItem::Error { message, .. } => {
-                        return format!("[error: {}]", message);
+                        return Err(format!("[error: {}]", message));
                     }
                     PlexusStreamItem::Done { .. } => break,
                     _ => continue,
                 }
             }
-            format!("[empty: {}]", handle)
+            Err(format!("[empty: {}]", handle))
         }
         Err(e) => {
-            format!("[unresolved: {} - {}]", handle.method, e)
+            Err(format!("[unresolved: {} - {}]", handle.method, e))
         }
     }
 }
@@ -0,0 +1,269 @@
+//! Pluggable Arbor storage backends
+//!
+//! [`ArborBackend`] is the tree/node/reference surface other activations
+//! (Cone, ClaudeCode) build on. [`ArborStorage`] implements it on SQLite;
+//! [`MemoryArborBackend`](super::MemoryArborBackend) keeps everything in
+//! process memory for tests and ephemeral hubs. Embedding applications can
+//! supply their own implementation.
+//!
+//! The Arbor activation itself still requires `ArborStorage`, since search,
+//! history, bundles and cleanup are SQLite-specific.
+
+use super::storage::ArborStorage;
+use super::types::{ArborError, Handle, Node, NodeId, ResourceRefs, Tree, TreeId};
+use async_trait::async_trait;
+use serde_json::Value;
+
+/// Tree, node and reference-counting operations backing Arbor
+///
+/// Implementations must follow the SQLite semantics: a new tree holds one
+/// reference for its creator and an empty text root node, releasing the last
+/// reference schedules a resource for deletion, claiming revives a scheduled
+/// resource, and archived resources are read-only.
+#[async_trait]
+pub trait ArborBackend: Send + Sync {
+    // ========================================================================
+    // Trees
+    // ========================================================================
+
+    /// Create a new tree with a root node, owned once by `owner_id`
+    async fn tree_create(&self, metadata: Option<Value>, owner_id: &str) -> Result<TreeId, ArborError>;
+
+    /// Get a tree with all its nodes (not archived)
+    async fn tree_get(&self, tree_id: &TreeId) -> Result<Tree, ArborError>;
+
+    /// Get a tree with all its nodes, including archived trees
+    async fn tree_get_archived(&self, tree_id: &TreeId) -> Result<Tree, ArborError>;
+
+    /// List active trees, optionally including those scheduled for deletion
+    async fn tree_list(&self, include_scheduled: bool) -> Result<Vec<TreeId>, ArborError>;
+
+    /// List trees scheduled for deletion
+    async fn tree_list_scheduled(&self) -> Result<Vec<TreeId>, ArborError>;
+
+    /// List archived trees
+    async fn tree_list_archived(&self) -> Result<Vec<TreeId>, ArborError>;
+
+    /// Replace tree-level metadata
+    async fn tree_update_metadata(&self, tree_id: &TreeId, metadata: Value) -> Result<(), ArborError>;
+
+    /// Add `count` references for `owner_id`, returning the new total
+    async fn tree_claim(&self, tree_id: &TreeId, owner_id: &str, count: i64) -> Result<i64, ArborError>;
+
+    /// Drop `count` references for `owner_id`, returning the new total
+    async fn tree_release(&self, tree_id: &TreeId, owner_id: &str, count: i64) -> Result<i64, ArborError>;
+
+    // ========================================================================
+    // Nodes
+    // ========================================================================
+
+    /// Create a text node
+    async fn node_create_text(
+        &self,
+        tree_id: &TreeId,
+        parent: Option<NodeId>,
+        content: String,
+        metadata: Option<Value>,
+    ) -> Result<NodeId, ArborError>;
+
+    /// Create an external node
+    async fn node_create_external(
+        &self,
+        tree_id: &TreeId,
+        parent: Option<NodeId>,
+        handle: Handle,
+        metadata: Option<Value>,
+    ) -> Result<NodeId, ArborError>;
+
+    /// Create an external node that is already scheduled for deletion
+    async fn node_create_external_ephemeral(
+        &self,
+        tree_id: &TreeId,
+        parent: Option<NodeId>,
+        handle: Handle,
+        metadata: Option<Value>,
+    ) -> Result<NodeId, ArborError>;
+
+    /// Get a node
+    async fn node_get(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Node, ArborError>;
+
+    /// Get a node's active children
+    async fn node_get_children(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<NodeId>, ArborError>;
+
+    /// Get a node's parent (None for the root)
+    async fn node_get_parent(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Option<NodeId>, ArborError>;
+
+    /// Get the node IDs from the root to a node
+    async fn node_get_path(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<NodeId>, ArborError>;
+
+    /// Follow copy-on-write update lineage to the newest version of a node
+    /// (the node itself when it was never updated)
+    async fn node_current_version(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<NodeId, ArborError>;
+
+    /// Add `count` references for `owner_id`, returning the new total
+    async fn node_claim(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        owner_id: &str,
+        count: i64,
+    ) -> Result<i64, ArborError>;
+
+    /// Drop `count` references for `owner_id`, returning the new total
+    async fn node_release(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        owner_id: &str,
+        count: i64,
+    ) -> Result<i64, ArborError>;
+
+    /// Get a node's reference owners
+    async fn node_get_refs(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<ResourceRefs, ArborError>;
+
+    /// Merge a JSON merge patch into an active node's metadata in place,
+    /// returning the new metadata
+    async fn node_update_metadata(&self, tree_id: &TreeId, node_id: &NodeId, patch: Value) -> Result<Value, ArborError>;
+
+    // ========================================================================
+    // Context
+    // ========================================================================
+
+    /// List active nodes without active children
+    async fn context_list_leaves(&self, tree_id: &TreeId) -> Result<Vec<NodeId>, ArborError>;
+
+    /// Get the nodes from the root to a node
+    async fn context_get_path(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<Node>, ArborError>;
+
+    /// Get the external handles on the path from the root to a node
+    async fn context_get_handles(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<Handle>, ArborError>;
+}
+
+#[async_trait]
+impl ArborBackend for ArborStorage {
+    async fn tree_create(&self, metadata: Option<Value>, owner_id: &str) -> Result<TreeId, ArborError> {
+        ArborStorage::tree_create(self, metadata, owner_id).await
+    }
+
+    async fn tree_get(&self, tree_id: &TreeId) -> Result<Tree, ArborError> {
+        ArborStorage::tree_get(self, tree_id).await
+    }
+
+    async fn tree_get_archived(&self, tree_id: &TreeId) -> Result<Tree, ArborError> {
+        ArborStorage::tree_get_archived(self, tree_id).await
+    }
+
+    async fn tree_list(&self, include_scheduled: bool) -> Result<Vec<TreeId>, ArborError> {
+        ArborStorage::tree_list(self, include_scheduled).await
+    }
+
+    async fn tree_list_scheduled(&self) -> Result<Vec<TreeId>, ArborError> {
+        ArborStorage::tree_list_scheduled(self).await
+    }
+
+    async fn tree_list_archived(&self) -> Result<Vec<TreeId>, ArborError> {
+        ArborStorage::tree_list_archived(self).await
+    }
+
+    async fn tree_update_metadata(&self, tree_id: &TreeId, metadata: Value) -> Result<(), ArborError> {
+        ArborStorage::tree_update_metadata(self, tree_id, metadata).await
+    }
+
+    async fn tree_claim(&self, tree_id: &TreeId, owner_id: &str, count: i64) -> Result<i64, ArborError> {
+        ArborStorage::tree_claim(self, tree_id, owner_id, count).await
+    }
+
+    async fn tree_release(&self, tree_id: &TreeId, owner_id: &str, count: i64) -> Result<i64, ArborError> {
+        ArborStorage::tree_release(self, tree_id, owner_id, count).await
+    }
+
+    async fn node_create_text(
+        &self,
+        tree_id: &TreeId,
+        parent: Option<NodeId>,
+        content: String,
+        metadata: Option<Value>,
+    ) -> Result<NodeId, ArborError> {
+        ArborStorage::node_create_text(self, tree_id, parent, content, metadata).await
+    }
+
+    async fn node_create_external(
+        &self,
+        tree_id: &TreeId,
+        parent: Option<NodeId>,
+        handle: Handle,
+        metadata: Option<Value>,
+    ) -> Result<NodeId, ArborError> {
+        ArborStorage::node_create_external(self, tree_id, parent, handle, metadata).await
+    }
+
+    async fn node_create_external_ephemeral(
+        &self,
+        tree_id: &TreeId,
+        parent: Option<NodeId>,
+        handle: Handle,
+        metadata: Option<Value>,
+    ) -> Result<NodeId, ArborError> {
+        ArborStorage::node_create_external_ephemeral(self, tree_id, parent, handle, metadata).await
+    }
+
+    async fn node_get(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Node, ArborError> {
+        ArborStorage::node_get(self, tree_id, node_id).await
+    }
+
+    async fn node_get_children(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<NodeId>, ArborError> {
+        ArborStorage::node_get_children(self, tree_id, node_id).await
+    }
+
+    async fn node_get_parent(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Option<NodeId>, ArborError> {
+        ArborStorage::node_get_parent(self, tree_id, node_id).await
+    }
+
+    async fn node_get_path(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<NodeId>, ArborError> {
+        ArborStorage::node_get_path(self, tree_id, node_id).await
+    }
+
+    async fn node_current_version(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<NodeId, ArborError> {
+        ArborStorage::node_current_version(self, tree_id, node_id).await
+    }
+
+    async fn node_claim(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        owner_id: &str,
+        count: i64,
+    ) -> Result<i64, ArborError> {
+        ArborStorage::node_claim(self, tree_id, node_id, owner_id, count).await
+    }
+
+    async fn node_release(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        owner_id: &str,
+        count: i64,
+    ) -> Result<i64, ArborError> {
+        ArborStorage::node_release(self, tree_id, node_id, owner_id, count).await
+    }
+
+    async fn node_get_refs(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<ResourceRefs, ArborError> {
+        ArborStorage::node_get_refs(self, tree_id, node_id).await
+    }
+
+    async fn node_update_metadata(&self, tree_id: &TreeId, node_id: &NodeId, patch: Value) -> Result<Value, ArborError> {
+        ArborStorage::node_update_metadata(self, tree_id, node_id, patch).await
+    }
+
+    async fn context_list_leaves(&self, tree_id: &TreeId) -> Result<Vec<NodeId>, ArborError> {
+        ArborStorage::context_list_leaves(self, tree_id).await
+    }
+
+    async fn context_get_path(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<Node>, ArborError> {
+        ArborStorage::context_get_path(self, tree_id, node_id).await
+    }
+
+    async fn context_get_handles(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<Handle>, ArborError> {
+        ArborStorage::context_get_handles(self, tree_id, node_id).await
+    }
+}
@@ -0,0 +1,196 @@
+//! Portable tree bundles for `tree_export` / `tree_import`
+//!
+//! A bundle is a self-contained snapshot of one tree: the tree header, every
+//! active node with its child order and metadata, and optionally the resolved
+//! content behind each external handle. Bundles serialize either as a single
+//! JSON document or as JSONL (one header line followed by one line per node).
+
+use super::types::{ArborError, NodeId, NodeType, ResourceState, Tree, TreeId};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Current bundle format version
+pub const BUNDLE_VERSION: u32 = 1;
+
+/// Serialization format of a bundle
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BundleFormat {
+    /// A single JSON document
+    #[default]
+    Json,
+    /// One JSON record per line: header first, then nodes
+    Jsonl,
+}
+
+/// Tree-level part of a bundle
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct BundleHeader {
+    /// Bundle format version
+    pub version: u32,
+    /// ID of the tree in the exporting database
+    pub tree_id: TreeId,
+    /// Root node ID
+    pub root: NodeId,
+    /// Tree-level metadata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    /// Creation timestamp (Unix seconds)
+    pub created_at: i64,
+    /// Last modified timestamp (Unix seconds)
+    pub updated_at: i64,
+    /// Export timestamp (Unix seconds)
+    pub exported_at: i64,
+}
+
+/// A node in a bundle
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct BundleNode {
+    /// ID of the node in the exporting database
+    pub id: NodeId,
+    /// Parent node (None for root)
+    pub parent: Option<NodeId>,
+    /// Child nodes (in order)
+    pub children: Vec<NodeId>,
+    /// Node data (handle or built-in)
+    pub data: NodeType,
+    /// Node metadata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Value>,
+    /// Creation timestamp (Unix seconds)
+    pub created_at: i64,
+    /// Resolved content behind an external handle, if it was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub resolved: Option<Value>,
+}
+
+/// A self-contained tree snapshot
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct TreeBundle {
+    pub header: BundleHeader,
+    /// Nodes in breadth-first order from the root
+    pub nodes: Vec<BundleNode>,
+}
+
+/// One line of a JSONL bundle
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum BundleRecord {
+    Header(BundleHeader),
+    Node(BundleNode),
+}
+
+impl TreeBundle {
+    /// Build a bundle from a tree, keeping only active nodes reachable from the root
+    pub fn from_tree(tree: &Tree, exported_at: i64) -> Self {
+        let is_active = |id: &NodeId| {
+            tree.nodes
+                .get(id)
+                .map(|n| n.state.as_ref().map_or(true, |s| *s == ResourceState::Active))
+                .unwrap_or(false)
+        };
+
+        let mut nodes = Vec::new();
+        let mut queue = std::collections::VecDeque::from([tree.root]);
+
+        while let Some(id) = queue.pop_front() {
+            let Some(node) = tree.nodes.get(&id) else {
+                continue;
+            };
+
+            let children: Vec<NodeId> = node.children.iter().copied().filter(|c| is_active(c)).collect();
+            queue.extend(children.iter().copied());
+
+            nodes.push(BundleNode {
+                id: node.id,
+                parent: node.parent,
+                children,
+                data: node.data.clone(),
+                metadata: node.metadata.clone(),
+                created_at: node.created_at,
+                resolved: None,
+            });
+        }
+
+        TreeBundle {
+            header: BundleHeader {
+                version: BUNDLE_VERSION,
+                tree_id: tree.id,
+                root: tree.root,
+                metadata: tree.metadata.clone(),
+                created_at: tree.created_at,
+                updated_at: tree.updated_at,
+                exported_at,
+            },
+            nodes,
+        }
+    }
+
+    /// Serialize the bundle in the given format
+    pub fn serialize(&self, format: BundleFormat) -> Result<String, ArborError> {
+        match format {
+            BundleFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|e| format!("Failed to serialize bundle: {}", e).into()),
+            BundleFormat::Jsonl => {
+                let mut lines = Vec::with_capacity(self.nodes.len() + 1);
+                lines.push(to_jsonl_line(&BundleRecord::Header(self.header.clone()))?);
+                for node in &self.nodes {
+                    lines.push(to_jsonl_line(&BundleRecord::Node(node.clone()))?);
+                }
+                Ok(lines.join("\n"))
+            }
+        }
+    }
+
+    /// Parse a bundle, detecting JSON vs JSONL from the content
+    pub fn parse(input: &str) -> Result<Self, ArborError> {
+        let bundle = match serde_json::from_str::<TreeBundle>(input) {
+            Ok(bundle) => bundle,
+            Err(_) => Self::parse_jsonl(input)?,
+        };
+
+        if bundle.header.version > BUNDLE_VERSION {
+            return Err(format!(
+                "Bundle version {} is newer than supported version {}",
+                bundle.header.version, BUNDLE_VERSION
+            )
+            .into());
+        }
+
+        Ok(bundle)
+    }
+
+    fn parse_jsonl(input: &str) -> Result<Self, ArborError> {
+        let mut header = None;
+        let mut nodes = Vec::new();
+
+        for (i, line) in input.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let record: BundleRecord = serde_json::from_str(line)
+                .map_err(|e| format!("Invalid bundle line {}: {}", i + 1, e))?;
+            match record {
+                BundleRecord::Header(h) if header.is_none() => header = Some(h),
+                BundleRecord::Header(_) => {
+                    return Err(format!("Duplicate bundle header on line {}", i + 1).into())
+                }
+                BundleRecord::Node(node) => nodes.push(node),
+            }
+        }
+
+        let header = header.ok_or("Bundle has no header")?;
+        Ok(TreeBundle { header, nodes })
+    }
+
+    /// Index nodes by their exported ID
+    pub fn nodes_by_id(&self) -> HashMap<NodeId, &BundleNode> {
+        self.nodes.iter().map(|n| (n.id, n)).collect()
+    }
+}
+
+fn to_jsonl_line(record: &BundleRecord) -> Result<String, ArborError> {
+    serde_json::to_string(record).map_err(|e| format!("Failed to serialize bundle: {}", e).into())
+}
@@ -0,0 +1,539 @@
+//! In-memory Arbor backend
+//!
+//! Keeps trees and nodes in a map behind a mutex. Nothing is persisted and no
+//! change-feed events are published, which makes it suited to tests and
+//! ephemeral hubs that don't want a database file.
+
+use super::backend::ArborBackend;
+use super::types::{ArborError, Handle, Node, NodeId, NodeType, ResourceRefs, ResourceState, Tree, TreeId};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A tree and the bookkeeping `Tree` itself doesn't carry
+struct TreeRecord {
+    /// The tree with all its nodes; `refs` holds the tree's owners
+    tree: Tree,
+    /// Node ref counts, including the implicit reference from the tree
+    node_ref_counts: HashMap<NodeId, i64>,
+}
+
+/// Arbor backend that keeps everything in process memory
+#[derive(Default)]
+pub struct MemoryArborBackend {
+    trees: Mutex<HashMap<TreeId, TreeRecord>>,
+}
+
+impl MemoryArborBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn trees(&self) -> MutexGuard<'_, HashMap<TreeId, TreeRecord>> {
+        self.trees.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Insert a node under `parent` in `tree_id`
+    fn insert_node(
+        &self,
+        tree_id: &TreeId,
+        parent: Option<NodeId>,
+        data: NodeType,
+        metadata: Option<Value>,
+        ephemeral: bool,
+    ) -> Result<NodeId, ArborError> {
+        let node_id = NodeId::new();
+        let now = current_timestamp();
+
+        let mut trees = self.trees();
+        let record = trees
+            .get_mut(tree_id)
+            .ok_or_else(|| format!("Tree not found: {}", tree_id))?;
+
+        if let Some(parent_id) = parent {
+            record
+                .tree
+                .nodes
+                .get_mut(&parent_id)
+                .ok_or_else(|| format!("Parent node not found: {}", parent_id))?
+                .children
+                .push(node_id);
+        }
+
+        let (state, scheduled_deletion_at, ref_count) = if ephemeral {
+            (ResourceState::ScheduledDelete, Some(now), 0)
+        } else {
+            (ResourceState::Active, None, 1)
+        };
+
+        record.tree.nodes.insert(
+            node_id,
+            Node {
+                id: node_id,
+                parent,
+                children: Vec::new(),
+                data,
+                state: Some(state),
+                refs: Some(empty_refs()),
+                scheduled_deletion_at,
+                archived_at: None,
+                created_at: now,
+                metadata,
+            },
+        );
+        record.node_ref_counts.insert(node_id, ref_count);
+
+        Ok(node_id)
+    }
+
+    fn list_trees(&self, states: &[ResourceState]) -> Vec<TreeId> {
+        self.trees()
+            .values()
+            .filter(|r| r.tree.state.as_ref().map_or(false, |s| states.contains(s)))
+            .map(|r| r.tree.id)
+            .collect()
+    }
+}
+
+#[async_trait]
+impl ArborBackend for MemoryArborBackend {
+    async fn tree_create(&self, metadata: Option<Value>, owner_id: &str) -> Result<TreeId, ArborError> {
+        let tree_id = TreeId::new();
+        let root_id = NodeId::new();
+        let now = current_timestamp();
+
+        let root = Node {
+            id: root_id,
+            parent: None,
+            children: Vec::new(),
+            data: NodeType::Text { content: String::new() },
+            state: Some(ResourceState::Active),
+            refs: Some(empty_refs()),
+            scheduled_deletion_at: None,
+            archived_at: None,
+            created_at: now,
+            metadata: None,
+        };
+
+        let tree = Tree {
+            id: tree_id,
+            root: root_id,
+            nodes: HashMap::from([(root_id, root)]),
+            state: Some(ResourceState::Active),
+            refs: Some(ResourceRefs {
+                ref_count: 1,
+                owners: HashMap::from([(owner_id.to_string(), 1)]),
+            }),
+            scheduled_deletion_at: None,
+            archived_at: None,
+            created_at: now,
+            updated_at: now,
+            metadata,
+        };
+
+        self.trees().insert(
+            tree_id,
+            TreeRecord {
+                tree,
+                node_ref_counts: HashMap::from([(root_id, 1)]),
+            },
+        );
+        Ok(tree_id)
+    }
+
+    async fn tree_get(&self, tree_id: &TreeId) -> Result<Tree, ArborError> {
+        let tree = self.tree_get_archived(tree_id).await?;
+        if tree.state == Some(ResourceState::Archived) {
+            return Err("Tree is archived, use tree_get_archived()".into());
+        }
+        Ok(tree)
+    }
+
+    async fn tree_get_archived(&self, tree_id: &TreeId) -> Result<Tree, ArborError> {
+        self.trees()
+            .get(tree_id)
+            .map(|r| r.tree.clone())
+            .ok_or_else(|| format!("Tree not found: {}", tree_id).into())
+    }
+
+    async fn tree_list(&self, include_scheduled: bool) -> Result<Vec<TreeId>, ArborError> {
+        if include_scheduled {
+            Ok(self.list_trees(&[ResourceState::Active, ResourceState::ScheduledDelete]))
+        } else {
+            Ok(self.list_trees(&[ResourceState::Active]))
+        }
+    }
+
+    async fn tree_list_scheduled(&self) -> Result<Vec<TreeId>, ArborError> {
+        Ok(self.list_trees(&[ResourceState::ScheduledDelete]))
+    }
+
+    async fn tree_list_archived(&self) -> Result<Vec<TreeId>, ArborError> {
+        Ok(self.list_trees(&[ResourceState::Archived]))
+    }
+
+    async fn tree_update_metadata(&self, tree_id: &TreeId, metadata: Value) -> Result<(), ArborError> {
+        let mut trees = self.trees();
+        let tree = &mut trees
+            .get_mut(tree_id)
+            .ok_or_else(|| format!("Tree not found: {}", tree_id))?
+            .tree;
+
+        if tree.state == Some(ResourceState::Active) {
+            tree.metadata = Some(metadata);
+            tree.updated_at = current_timestamp();
+        }
+        Ok(())
+    }
+
+    async fn tree_claim(&self, tree_id: &TreeId, owner_id: &str, count: i64) -> Result<i64, ArborError> {
+        let now = current_timestamp();
+        let mut trees = self.trees();
+        let tree = &mut trees
+            .get_mut(tree_id)
+            .ok_or_else(|| format!("Tree not found: {}", tree_id))?
+            .tree;
+
+        if tree.state == Some(ResourceState::Archived) {
+            return Err("Cannot claim archived tree".into());
+        }
+
+        tree.state = Some(ResourceState::Active);
+        tree.scheduled_deletion_at = None;
+        tree.updated_at = now;
+
+        let refs = tree.refs.get_or_insert_with(empty_refs);
+        *refs.owners.entry(owner_id.to_string()).or_insert(0) += count;
+        refs.ref_count += count;
+        Ok(refs.ref_count)
+    }
+
+    async fn tree_release(&self, tree_id: &TreeId, owner_id: &str, count: i64) -> Result<i64, ArborError> {
+        let now = current_timestamp();
+        let mut trees = self.trees();
+        let tree = &mut trees
+            .get_mut(tree_id)
+            .ok_or_else(|| format!("No reference found for owner {}", owner_id))?
+            .tree;
+
+        let refs = tree.refs.get_or_insert_with(empty_refs);
+        release_ref(refs, owner_id, count)?;
+        refs.ref_count -= count;
+        let ref_count = refs.ref_count;
+
+        tree.updated_at = now;
+        if ref_count == 0 {
+            tree.state = Some(ResourceState::ScheduledDelete);
+            tree.scheduled_deletion_at = Some(now);
+        }
+        Ok(ref_count)
+    }
+
+    async fn node_create_text(
+        &self,
+        tree_id: &TreeId,
+        parent: Option<NodeId>,
+        content: String,
+        metadata: Option<Value>,
+    ) -> Result<NodeId, ArborError> {
+        self.insert_node(tree_id, parent, NodeType::Text { content }, metadata, false)
+    }
+
+    async fn node_create_external(
+        &self,
+        tree_id: &TreeId,
+        parent: Option<NodeId>,
+        handle: Handle,
+        metadata: Option<Value>,
+    ) -> Result<NodeId, ArborError> {
+        self.insert_node(tree_id, parent, NodeType::External { handle }, metadata, false)
+    }
+
+    async fn node_create_external_ephemeral(
+        &self,
+        tree_id: &TreeId,
+        parent: Option<NodeId>,
+        handle: Handle,
+        metadata: Option<Value>,
+    ) -> Result<NodeId, ArborError> {
+        self.insert_node(tree_id, parent, NodeType::External { handle }, metadata, true)
+    }
+
+    async fn node_get(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Node, ArborError> {
+        self.trees()
+            .get(tree_id)
+            .and_then(|r| r.tree.nodes.get(node_id))
+            .cloned()
+            .ok_or_else(|| format!("Node not found: {}", node_id).into())
+    }
+
+    async fn node_get_children(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<NodeId>, ArborError> {
+        let node = self.node_get(tree_id, node_id).await?;
+        let trees = self.trees();
+        let Some(record) = trees.get(tree_id) else {
+            return Ok(Vec::new());
+        };
+
+        Ok(node
+            .children
+            .into_iter()
+            .filter(|child| {
+                record
+                    .tree
+                    .nodes
+                    .get(child)
+                    .map_or(false, |c| c.state == Some(ResourceState::Active))
+            })
+            .collect())
+    }
+
+    async fn node_get_parent(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Option<NodeId>, ArborError> {
+        Ok(self.node_get(tree_id, node_id).await?.parent)
+    }
+
+    async fn node_get_path(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<NodeId>, ArborError> {
+        Ok(self
+            .context_get_path(tree_id, node_id)
+            .await?
+            .into_iter()
+            .map(|node| node.id)
+            .collect())
+    }
+
+    async fn node_current_version(&self, _tree_id: &TreeId, node_id: &NodeId) -> Result<NodeId, ArborError> {
+        // No copy-on-write updates in memory, so every node is its newest version
+        Ok(*node_id)
+    }
+
+    async fn node_claim(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        owner_id: &str,
+        count: i64,
+    ) -> Result<i64, ArborError> {
+        let now = current_timestamp();
+        let mut trees = self.trees();
+        let record = trees
+            .get_mut(tree_id)
+            .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+        // Nodes can only be pinned (or revived) on a fully active branch
+        let mut ancestor = record.tree.nodes.get(node_id).and_then(|node| node.parent);
+        while let Some(id) = ancestor {
+            let parent = record
+                .tree
+                .nodes
+                .get(&id)
+                .ok_or_else(|| format!("Node not found: {}", id))?;
+            if matches!(&parent.state, Some(state) if *state != ResourceState::Active) {
+                return Err(format!("Ancestor node {} is not active, restore it first", id).into());
+            }
+            ancestor = parent.parent;
+        }
+
+        let node = record
+            .tree
+            .nodes
+            .get_mut(node_id)
+            .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+        if node.state == Some(ResourceState::Archived) {
+            return Err("Cannot claim archived node".into());
+        }
+
+        node.state = Some(ResourceState::Active);
+        node.scheduled_deletion_at = None;
+
+        let refs = node.refs.get_or_insert_with(empty_refs);
+        *refs.owners.entry(owner_id.to_string()).or_insert(0) += count;
+        refs.ref_count += count;
+
+        let ref_count = record.node_ref_counts.entry(*node_id).or_insert(0);
+        *ref_count += count;
+        let new_count = *ref_count;
+
+        record.tree.updated_at = now;
+        Ok(new_count)
+    }
+
+    async fn node_release(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        owner_id: &str,
+        count: i64,
+    ) -> Result<i64, ArborError> {
+        let now = current_timestamp();
+        let mut trees = self.trees();
+        let record = trees
+            .get_mut(tree_id)
+            .ok_or_else(|| format!("Node not found: {}", node_id))?;
+        let node = record
+            .tree
+            .nodes
+            .get_mut(node_id)
+            .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+        let refs = node.refs.get_or_insert_with(empty_refs);
+        release_ref(refs, owner_id, count)?;
+        refs.ref_count -= count;
+
+        let ref_count = record.node_ref_counts.entry(*node_id).or_insert(0);
+        *ref_count -= count;
+        let new_count = *ref_count;
+
+        if new_count == 0 {
+            node.state = Some(ResourceState::ScheduledDelete);
+            node.scheduled_deletion_at = Some(now);
+        }
+
+        record.tree.updated_at = now;
+        Ok(new_count)
+    }
+
+    async fn node_get_refs(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<ResourceRefs, ArborError> {
+        Ok(self
+            .node_get(tree_id, node_id)
+            .await?
+            .refs
+            .unwrap_or_else(empty_refs))
+    }
+
+    async fn node_update_metadata(&self, tree_id: &TreeId, node_id: &NodeId, patch: Value) -> Result<Value, ArborError> {
+        let mut trees = self.trees();
+        let tree = &mut trees
+            .get_mut(tree_id)
+            .ok_or_else(|| format!("Tree not found: {}", tree_id))?
+            .tree;
+        let node = tree
+            .nodes
+            .get_mut(node_id)
+            .filter(|node| node.state == Some(ResourceState::Active))
+            .ok_or_else(|| format!("Active node not found: {}", node_id))?;
+
+        let metadata = node.metadata.get_or_insert_with(|| Value::Object(Default::default()));
+        merge_patch(metadata, &patch);
+        let metadata = metadata.clone();
+        tree.updated_at = current_timestamp();
+        Ok(metadata)
+    }
+
+    async fn context_list_leaves(&self, tree_id: &TreeId) -> Result<Vec<NodeId>, ArborError> {
+        let trees = self.trees();
+        let Some(record) = trees.get(tree_id) else {
+            return Ok(Vec::new());
+        };
+
+        let nodes = &record.tree.nodes;
+        let is_active = |node: &Node| node.state == Some(ResourceState::Active);
+
+        Ok(nodes
+            .values()
+            .filter(|node| is_active(node))
+            .filter(|node| {
+                !node
+                    .children
+                    .iter()
+                    .any(|child| nodes.get(child).map_or(false, is_active))
+            })
+            .map(|node| node.id)
+            .collect())
+    }
+
+    async fn context_get_path(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<Node>, ArborError> {
+        let trees = self.trees();
+        let nodes = &trees
+            .get(tree_id)
+            .ok_or_else(|| format!("Node not found: {}", node_id))?
+            .tree
+            .nodes;
+
+        let mut path = Vec::new();
+        let mut current_id = Some(*node_id);
+
+        // Walk up the tree to root
+        while let Some(id) = current_id {
+            let node = nodes
+                .get(&id)
+                .ok_or_else(|| format!("Node not found: {}", id))?;
+            current_id = node.parent;
+            path.push(node.clone());
+        }
+
+        // Reverse to get root-to-node path
+        path.reverse();
+        Ok(path)
+    }
+
+    async fn context_get_handles(&self, tree_id: &TreeId, node_id: &NodeId) -> Result<Vec<Handle>, ArborError> {
+        let path_nodes = self.context_get_path(tree_id, node_id).await?;
+
+        Ok(path_nodes
+            .into_iter()
+            .filter_map(|node| match node.data {
+                NodeType::External { handle } => Some(handle),
+                NodeType::Text { .. } => None,
+            })
+            .collect())
+    }
+}
+
+fn empty_refs() -> ResourceRefs {
+    ResourceRefs {
+        ref_count: 0,
+        owners: HashMap::new(),
+    }
+}
+
+/// Drop `count` of `owner_id`'s references, removing the owner at zero
+fn release_ref(refs: &mut ResourceRefs, owner_id: &str, count: i64) -> Result<(), ArborError> {
+    let current_count = *refs
+        .owners
+        .get(owner_id)
+        .ok_or_else(|| format!("No reference found for owner {}", owner_id))?;
+
+    if current_count < count {
+        return Err(format!(
+            "Cannot release {} references, owner only has {}",
+            count, current_count
+        )
+        .into());
+    }
+
+    if current_count == count {
+        refs.owners.remove(owner_id);
+    } else {
+        refs.owners.insert(owner_id.to_string(), current_count - count);
+    }
+    Ok(())
+}
+
+/// Apply a JSON merge patch (RFC 7396), as SQLite's `json_patch` does
+fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Default::default());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+fn current_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
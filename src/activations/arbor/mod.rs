@@ -1,14 +1,28 @@
 mod methods;
 mod activation;
+mod backend;
+mod bundle;
+mod memory;
+mod path_cache;
+mod render;
 mod storage;
 mod types;
 
+#[cfg(test)]
+mod tests;
+
 pub use activation::{Arbor, ArborMethod};
+pub use backend::ArborBackend;
+pub use bundle::{BundleFormat, BundleHeader, BundleNode, TreeBundle, BUNDLE_VERSION};
+pub use memory::MemoryArborBackend;
+pub use render::{RenderFormat, ResolvedHandles};
 // Keep methods module for any helper types if needed
 pub use storage::{ArborConfig, ArborStorage};
 pub use types::{
-    ArborError, ArborEvent, Node, NodeId, NodeType, ResourceRefs, ResourceState, Tree,
-    TreeId, TreeSkeleton,
+    estimate_tokens, ArborError, ArborEvent, BatchNodeRef, BatchOp, CleanupReport, CleanupStatus,
+    LeafPathStats, ListOrder, Node, NodeId, NodeQueryMatch, NodeType, NodeTypeCounts, ResourceRefs,
+    ResourceState, SearchHit, SearchSource, Tree, TreeId, TreeListQuery, TreeSkeleton, TreeStats,
+    TreeSummary,
 };
 
 // Re-export Handle from crate::types for consistency
@@ -0,0 +1,143 @@
+//! LRU cache of root-to-node paths
+//!
+//! Cone asks for the path to its head on every chat turn, so hot paths are
+//! kept in memory keyed by tree and node. A node's path only changes when
+//! parent links change (moves, copy-on-write updates) or nodes are purged;
+//! those operations invalidate the whole tree's entries.
+
+use super::types::{NodeId, TreeId};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+type PathKey = (TreeId, NodeId);
+
+/// Shared, thread-safe path cache; clones share the same entries
+#[derive(Clone)]
+pub(crate) struct PathCache {
+    inner: Arc<Mutex<Lru>>,
+}
+
+struct Lru {
+    /// Maximum number of cached paths (0 disables caching)
+    capacity: usize,
+    /// Monotonic use counter; higher is more recently used
+    tick: u64,
+    entries: HashMap<PathKey, (u64, Vec<NodeId>)>,
+    /// Use counter -> key, oldest first
+    recency: BTreeMap<u64, PathKey>,
+}
+
+impl PathCache {
+    pub(crate) fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Lru {
+                capacity,
+                tick: 0,
+                entries: HashMap::new(),
+                recency: BTreeMap::new(),
+            })),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Lru> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Get a cached path, marking it as recently used
+    pub(crate) fn get(&self, tree_id: &TreeId, node_id: &NodeId) -> Option<Vec<NodeId>> {
+        let mut lru = self.lock();
+        let key = (*tree_id, *node_id);
+        lru.tick += 1;
+        let tick = lru.tick;
+
+        let (used, path) = lru.entries.get_mut(&key)?;
+        let old = std::mem::replace(used, tick);
+        let path = path.clone();
+
+        lru.recency.remove(&old);
+        lru.recency.insert(tick, key);
+        Some(path)
+    }
+
+    /// Cache a path, evicting the least recently used one when full
+    pub(crate) fn insert(&self, tree_id: &TreeId, node_id: &NodeId, path: Vec<NodeId>) {
+        let mut lru = self.lock();
+        if lru.capacity == 0 {
+            return;
+        }
+
+        let key = (*tree_id, *node_id);
+        lru.tick += 1;
+        let tick = lru.tick;
+
+        if let Some((old, _)) = lru.entries.insert(key, (tick, path)) {
+            lru.recency.remove(&old);
+        }
+        lru.recency.insert(tick, key);
+
+        while lru.entries.len() > lru.capacity {
+            let Some((_, oldest)) = lru.recency.pop_first() else {
+                break;
+            };
+            lru.entries.remove(&oldest);
+        }
+    }
+
+    /// Drop every cached path in a tree
+    pub(crate) fn invalidate_tree(&self, tree_id: &TreeId) {
+        let mut lru = self.lock();
+        let Lru { entries, recency, .. } = &mut *lru;
+        entries.retain(|(tree, _), (used, _)| {
+            let keep = tree != tree_id;
+            if !keep {
+                recency.remove(&*used);
+            }
+            keep
+        });
+    }
+
+    /// Number of cached paths
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.lock().entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let cache = PathCache::new(2);
+        let tree = TreeId::new();
+        let (a, b, c) = (NodeId::new(), NodeId::new(), NodeId::new());
+
+        cache.insert(&tree, &a, vec![a]);
+        cache.insert(&tree, &b, vec![a, b]);
+        // Touch a so b becomes the eviction candidate
+        assert_eq!(cache.get(&tree, &a), Some(vec![a]));
+        cache.insert(&tree, &c, vec![a, c]);
+
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.get(&tree, &b), None);
+        assert_eq!(cache.get(&tree, &c), Some(vec![a, c]));
+    }
+
+    #[test]
+    fn test_invalidate_tree_and_disabled_cache() {
+        let cache = PathCache::new(8);
+        let (tree, other) = (TreeId::new(), TreeId::new());
+        let node = NodeId::new();
+
+        cache.insert(&tree, &node, vec![node]);
+        cache.insert(&other, &node, vec![node]);
+        cache.invalidate_tree(&tree);
+        assert_eq!(cache.get(&tree, &node), None);
+        assert_eq!(cache.get(&other, &node), Some(vec![node]));
+
+        let disabled = PathCache::new(0);
+        disabled.insert(&tree, &node, vec![node]);
+        assert_eq!(disabled.len(), 0);
+    }
+}
@@ -0,0 +1,250 @@
+//! Alternative `tree_render` formats
+//!
+//! `Tree::render` produces the compact box-drawing view. The formats here are
+//! meant for pasting into docs and PRs: Graphviz DOT and Mermaid diagrams of
+//! the whole tree, a nested JSON document, and a Markdown transcript of one
+//! root-to-node path with full, untruncated content.
+
+use super::types::{ArborError, Node, NodeId, NodeType, Tree};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+
+/// Maximum characters of content shown in a diagram label
+const LABEL_LIMIT: usize = 80;
+
+/// Output format of `tree_render`
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RenderFormat {
+    /// Box-drawing text view (content truncated to one line)
+    #[default]
+    Text,
+    /// Graphviz DOT digraph
+    Dot,
+    /// Mermaid flowchart
+    Mermaid,
+    /// Nested JSON document rooted at the tree root
+    Json,
+    /// Markdown transcript of the path from the root to one node
+    Markdown,
+}
+
+/// Resolved handle data keyed by node, as returned by the handle's plugin
+pub type ResolvedHandles = HashMap<NodeId, Value>;
+
+/// What a node says and who said it, for display
+struct NodeText {
+    /// Role (and name) of the speaker, when known
+    speaker: Option<String>,
+    /// Full content
+    content: String,
+}
+
+impl Tree {
+    /// Render the tree as a Graphviz DOT digraph
+    pub fn render_dot(&self, resolved: &ResolvedHandles) -> String {
+        let mut output = format!("digraph \"{}\" {{\n    node [shape=box];\n", self.id);
+
+        for node in self.nodes_breadth_first() {
+            output.push_str(&format!(
+                "    \"{}\" [label=\"{}\"];\n",
+                node.id,
+                escape_dot(&label(&node_text(node, resolved)))
+            ));
+            for child in &node.children {
+                output.push_str(&format!("    \"{}\" -> \"{}\";\n", node.id, child));
+            }
+        }
+
+        output.push_str("}\n");
+        output
+    }
+
+    /// Render the tree as a Mermaid flowchart
+    pub fn render_mermaid(&self, resolved: &ResolvedHandles) -> String {
+        let nodes = self.nodes_breadth_first();
+        // Mermaid IDs must be simple identifiers, so number nodes instead of
+        // using their UUIDs
+        let ids: HashMap<NodeId, String> = nodes
+            .iter()
+            .enumerate()
+            .map(|(i, node)| (node.id, format!("n{}", i)))
+            .collect();
+
+        let mut output = String::from("graph TD\n");
+        for node in &nodes {
+            output.push_str(&format!(
+                "    {}[\"{}\"]\n",
+                ids[&node.id],
+                escape_mermaid(&label(&node_text(node, resolved)))
+            ));
+        }
+        for node in &nodes {
+            for child in &node.children {
+                if let Some(child_id) = ids.get(child) {
+                    output.push_str(&format!("    {} --> {}\n", ids[&node.id], child_id));
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Render the tree as a nested JSON document with full content
+    pub fn render_json(&self, resolved: &ResolvedHandles) -> Value {
+        json!({
+            "tree_id": self.id,
+            "metadata": self.metadata,
+            "root": self.node_json(&self.root, resolved),
+        })
+    }
+
+    fn node_json(&self, node_id: &NodeId, resolved: &ResolvedHandles) -> Value {
+        let Some(node) = self.nodes.get(node_id) else {
+            return Value::Null;
+        };
+
+        let mut value = json!({
+            "id": node.id,
+            "state": node.state,
+            "created_at": node.created_at,
+        });
+        match &node.data {
+            NodeType::Text { content } => {
+                value["type"] = json!("text");
+                value["content"] = json!(content);
+            }
+            NodeType::External { handle } => {
+                value["type"] = json!("external");
+                value["handle"] = json!(handle.to_string());
+                if let Some(data) = resolved.get(node_id) {
+                    value["resolved"] = data.clone();
+                }
+            }
+        }
+        if let Some(metadata) = &node.metadata {
+            value["metadata"] = metadata.clone();
+        }
+        value["children"] = node
+            .children
+            .iter()
+            .map(|child| self.node_json(child, resolved))
+            .collect();
+
+        value
+    }
+
+    /// Render the path from the root to `node_id` as a Markdown transcript
+    ///
+    /// Each node becomes a bold speaker line followed by its full content.
+    /// An empty root node is skipped.
+    pub fn render_markdown(&self, node_id: &NodeId, resolved: &ResolvedHandles) -> Result<String, ArborError> {
+        let mut path = Vec::new();
+        let mut current = Some(*node_id);
+        while let Some(id) = current {
+            let node = self
+                .nodes
+                .get(&id)
+                .ok_or_else(|| format!("Node not found: {}", id))?;
+            path.push(node);
+            current = node.parent;
+        }
+        path.reverse();
+
+        let sections: Vec<String> = path
+            .into_iter()
+            .filter(|node| {
+                node.parent.is_some()
+                    || !matches!(&node.data, NodeType::Text { content } if content.is_empty())
+            })
+            .map(|node| {
+                let text = node_text(node, resolved);
+                let speaker = text.speaker.unwrap_or_else(|| match &node.data {
+                    NodeType::Text { .. } => "text".to_string(),
+                    NodeType::External { handle } => handle.method.clone(),
+                });
+                format!("**{}**\n\n{}\n", speaker, text.content.trim_end())
+            })
+            .collect();
+
+        Ok(sections.join("\n---\n\n"))
+    }
+
+    /// Nodes reachable from the root, in breadth-first order
+    fn nodes_breadth_first(&self) -> Vec<&Node> {
+        let mut nodes = Vec::with_capacity(self.nodes.len());
+        let mut queue = VecDeque::from([self.root]);
+        while let Some(id) = queue.pop_front() {
+            if let Some(node) = self.nodes.get(&id) {
+                queue.extend(node.children.iter().copied());
+                nodes.push(node);
+            }
+        }
+        nodes
+    }
+}
+
+fn node_text(node: &Node, resolved: &ResolvedHandles) -> NodeText {
+    let role = node
+        .metadata
+        .as_ref()
+        .and_then(|m| m.get("role"))
+        .and_then(|r| r.as_str())
+        .map(str::to_string);
+
+    match &node.data {
+        NodeType::Text { content } => NodeText {
+            speaker: role,
+            content: content.clone(),
+        },
+        NodeType::External { handle } => match resolved.get(&node.id) {
+            // Message-shaped data: { "role": ..., "name": ..., "content": ... }
+            Some(data) if data.get("content").and_then(|c| c.as_str()).is_some() => {
+                let speaker = data.get("role").and_then(|r| r.as_str()).map(|r| {
+                    match data.get("name").and_then(|n| n.as_str()) {
+                        Some(name) => format!("{}:{}", r, name),
+                        None => r.to_string(),
+                    }
+                });
+                NodeText {
+                    speaker: speaker.or(role),
+                    content: data["content"].as_str().unwrap_or_default().to_string(),
+                }
+            }
+            Some(data) => NodeText {
+                speaker: role,
+                content: serde_json::to_string_pretty(data).unwrap_or_else(|_| data.to_string()),
+            },
+            None => NodeText {
+                speaker: role,
+                content: format!("[{}]", handle),
+            },
+        },
+    }
+}
+
+/// Single-line diagram label, truncated to `LABEL_LIMIT` characters
+fn label(text: &NodeText) -> String {
+    let line = match &text.speaker {
+        Some(speaker) => format!("[{}] {}", speaker, text.content),
+        None => text.content.clone(),
+    };
+    let line = line.split_whitespace().collect::<Vec<_>>().join(" ");
+
+    if line.chars().count() > LABEL_LIMIT {
+        let truncated: String = line.chars().take(LABEL_LIMIT - 1).collect();
+        format!("{}…", truncated)
+    } else {
+        line
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_mermaid(s: &str) -> String {
+    s.replace('"', "#quot;")
+}
@@ -1,12 +1,283 @@
+use super::bundle::TreeBundle;
+use super::path_cache::PathCache;
 use super::types::{
-    ArborError, ArborId, Node, NodeId, NodeType, ResourceRefs, ResourceState, Tree, TreeId, Handle,
+    ArborError, ArborEvent, ArborId, BatchNodeRef, BatchOp, CleanupReport, CleanupStatus, Node, NodeId,
+    ListOrder, NodeQueryMatch, NodeType, ResourceRefs, ResourceState, SearchHit, SearchSource, Tree, TreeId,
+    TreeListQuery, TreeSummary, Handle, LeafPathStats, NodeTypeCounts, TreeStats, estimate_tokens,
 };
 use serde_json::Value;
-use sqlx::{sqlite::{SqliteConnectOptions, SqlitePool}, ConnectOptions, Row};
+use crate::migrations::{self, Migration};
+use sqlx::{
+    sqlite::{SqliteConnectOptions, SqliteConnection, SqlitePool, SqliteRow},
+    ConnectOptions, Row,
+};
 use uuid::Uuid;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Buffered change-feed events per subscriber before it starts lagging
+const EVENT_CHANNEL_CAPACITY: usize = 1024;
+
+/// Seconds before a handle that failed to resolve is tried again for search
+const RESOLVE_RETRY_WINDOW: i64 = 3600;
+
+/// Columns selected for building a `Node` with `nodes_from_rows`
+const NODE_COLUMNS: &str = "id, tree_id, parent_id, ref_count, state, scheduled_deletion_at, archived_at,
+     node_type, content, handle_plugin_id, handle_version, handle_method,
+     handle_meta, created_at, metadata";
+
+/// Arbor schema migrations, applied in order by `run_migrations`
+///
+/// Never edit a released migration; append a new one instead.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "trees, nodes, refs and child order",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS trees (
+                id TEXT PRIMARY KEY,
+                root_node_id TEXT NOT NULL,
+                ref_count INTEGER NOT NULL DEFAULT 1,
+                state TEXT NOT NULL DEFAULT 'active',
+                scheduled_deletion_at INTEGER,
+                archived_at INTEGER,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                metadata TEXT
+            );
+
+            CREATE TABLE IF NOT EXISTS tree_refs (
+                tree_id TEXT NOT NULL,
+                owner_id TEXT NOT NULL,
+                count INTEGER NOT NULL DEFAULT 1,
+                claimed_at INTEGER NOT NULL,
+                PRIMARY KEY (tree_id, owner_id),
+                FOREIGN KEY (tree_id) REFERENCES trees(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS nodes (
+                id TEXT PRIMARY KEY,
+                tree_id TEXT NOT NULL,
+                parent_id TEXT,
+                ref_count INTEGER NOT NULL DEFAULT 1,
+                state TEXT NOT NULL DEFAULT 'active',
+                scheduled_deletion_at INTEGER,
+                archived_at INTEGER,
+                node_type TEXT NOT NULL,
+                content TEXT,
+                handle_plugin_id TEXT,
+                handle_version TEXT,
+                handle_method TEXT,
+                handle_meta TEXT,
+                created_at INTEGER NOT NULL,
+                metadata TEXT,
+                FOREIGN KEY (tree_id) REFERENCES trees(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS node_refs (
+                node_id TEXT NOT NULL,
+                owner_id TEXT NOT NULL,
+                count INTEGER NOT NULL DEFAULT 1,
+                claimed_at INTEGER NOT NULL,
+                PRIMARY KEY (node_id, owner_id),
+                FOREIGN KEY (node_id) REFERENCES nodes(id) ON DELETE CASCADE
+            );
+
+            CREATE TABLE IF NOT EXISTS node_children (
+                parent_id TEXT NOT NULL,
+                child_id TEXT NOT NULL,
+                position INTEGER NOT NULL,
+                PRIMARY KEY (parent_id, child_id),
+                FOREIGN KEY (parent_id) REFERENCES nodes(id) ON DELETE CASCADE,
+                FOREIGN KEY (child_id) REFERENCES nodes(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_trees_state ON trees(state);
+            CREATE INDEX IF NOT EXISTS idx_trees_scheduled ON trees(scheduled_deletion_at) WHERE state = 'scheduled_delete';
+            CREATE INDEX IF NOT EXISTS idx_trees_archived ON trees(archived_at) WHERE state = 'archived';
+            CREATE INDEX IF NOT EXISTS idx_nodes_tree ON nodes(tree_id);
+            CREATE INDEX IF NOT EXISTS idx_nodes_parent ON nodes(parent_id);
+            CREATE INDEX IF NOT EXISTS idx_nodes_state ON nodes(state);
+            CREATE INDEX IF NOT EXISTS idx_nodes_scheduled ON nodes(scheduled_deletion_at) WHERE state = 'scheduled_delete';
+            CREATE INDEX IF NOT EXISTS idx_node_children_parent ON node_children(parent_id);
+            CREATE INDEX IF NOT EXISTS idx_node_children_child ON node_children(child_id);
+        "#,
+    },
+    Migration {
+        version: 2,
+        description: "node version lineage",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS node_versions (
+                old_node_id TEXT PRIMARY KEY,
+                new_node_id TEXT NOT NULL,
+                tree_id TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (tree_id) REFERENCES trees(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_node_versions_new ON node_versions(new_node_id);
+        "#,
+    },
+    Migration {
+        version: 3,
+        description: "full-text search index",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS node_resolved (
+                node_id TEXT PRIMARY KEY,
+                content TEXT NOT NULL,
+                resolved_at INTEGER NOT NULL,
+                FOREIGN KEY (node_id) REFERENCES nodes(id) ON DELETE CASCADE
+            );
+
+            -- Full-text search over node content/metadata, tree metadata and
+            -- resolved external content (external-content FTS5 tables kept in
+            -- sync by triggers)
+            CREATE VIRTUAL TABLE IF NOT EXISTS node_search USING fts5(
+                content, metadata, content='nodes', content_rowid='rowid'
+            );
+            CREATE VIRTUAL TABLE IF NOT EXISTS tree_search USING fts5(
+                metadata, content='trees', content_rowid='rowid'
+            );
+            CREATE VIRTUAL TABLE IF NOT EXISTS resolved_search USING fts5(
+                content, content='node_resolved', content_rowid='rowid'
+            );
+
+            CREATE TRIGGER IF NOT EXISTS node_search_insert AFTER INSERT ON nodes BEGIN
+                INSERT INTO node_search(rowid, content, metadata) VALUES (new.rowid, new.content, new.metadata);
+            END;
+            CREATE TRIGGER IF NOT EXISTS node_search_delete AFTER DELETE ON nodes BEGIN
+                INSERT INTO node_search(node_search, rowid, content, metadata) VALUES ('delete', old.rowid, old.content, old.metadata);
+            END;
+            CREATE TRIGGER IF NOT EXISTS node_search_update AFTER UPDATE OF content, metadata ON nodes BEGIN
+                INSERT INTO node_search(node_search, rowid, content, metadata) VALUES ('delete', old.rowid, old.content, old.metadata);
+                INSERT INTO node_search(rowid, content, metadata) VALUES (new.rowid, new.content, new.metadata);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS tree_search_insert AFTER INSERT ON trees BEGIN
+                INSERT INTO tree_search(rowid, metadata) VALUES (new.rowid, new.metadata);
+            END;
+            CREATE TRIGGER IF NOT EXISTS tree_search_delete AFTER DELETE ON trees BEGIN
+                INSERT INTO tree_search(tree_search, rowid, metadata) VALUES ('delete', old.rowid, old.metadata);
+            END;
+            CREATE TRIGGER IF NOT EXISTS tree_search_update AFTER UPDATE OF metadata ON trees BEGIN
+                INSERT INTO tree_search(tree_search, rowid, metadata) VALUES ('delete', old.rowid, old.metadata);
+                INSERT INTO tree_search(rowid, metadata) VALUES (new.rowid, new.metadata);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS resolved_search_insert AFTER INSERT ON node_resolved BEGIN
+                INSERT INTO resolved_search(rowid, content) VALUES (new.rowid, new.content);
+            END;
+            CREATE TRIGGER IF NOT EXISTS resolved_search_delete AFTER DELETE ON node_resolved BEGIN
+                INSERT INTO resolved_search(resolved_search, rowid, content) VALUES ('delete', old.rowid, old.content);
+            END;
+            CREATE TRIGGER IF NOT EXISTS resolved_search_update AFTER UPDATE OF content ON node_resolved BEGIN
+                INSERT INTO resolved_search(resolved_search, rowid, content) VALUES ('delete', old.rowid, old.content);
+                INSERT INTO resolved_search(rowid, content) VALUES (new.rowid, new.content);
+            END;
+
+            -- Index rows that existed before the search tables
+            INSERT INTO node_search(node_search) VALUES ('rebuild');
+            INSERT INTO tree_search(tree_search) VALUES ('rebuild');
+        "#,
+    },
+    Migration {
+        version: 4,
+        description: "failed handle resolutions",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS node_resolve_failures (
+                node_id TEXT PRIMARY KEY,
+                error TEXT NOT NULL,
+                failed_at INTEGER NOT NULL,
+                FOREIGN KEY (node_id) REFERENCES nodes(id) ON DELETE CASCADE
+            );
+        "#,
+    },
+    Migration {
+        version: 5,
+        description: "stable full-text search keys",
+        sql: r#"
+            -- The v3 search tables indexed the implicit rowids of tables with
+            -- TEXT primary keys, which VACUUM may renumber. Rebuild them keyed
+            -- on search_keys, whose INTEGER PRIMARY KEY is stable.
+            DROP TRIGGER IF EXISTS node_search_insert;
+            DROP TRIGGER IF EXISTS node_search_delete;
+            DROP TRIGGER IF EXISTS node_search_update;
+            DROP TRIGGER IF EXISTS tree_search_insert;
+            DROP TRIGGER IF EXISTS tree_search_delete;
+            DROP TRIGGER IF EXISTS tree_search_update;
+            DROP TRIGGER IF EXISTS resolved_search_insert;
+            DROP TRIGGER IF EXISTS resolved_search_delete;
+            DROP TRIGGER IF EXISTS resolved_search_update;
+            DROP TABLE IF EXISTS node_search;
+            DROP TABLE IF EXISTS tree_search;
+            DROP TABLE IF EXISTS resolved_search;
+
+            CREATE TABLE IF NOT EXISTS search_keys (
+                id INTEGER PRIMARY KEY,
+                source TEXT NOT NULL,
+                key TEXT NOT NULL,
+                UNIQUE (source, key)
+            );
+
+            CREATE VIRTUAL TABLE IF NOT EXISTS node_search USING fts5(content, metadata);
+            CREATE VIRTUAL TABLE IF NOT EXISTS tree_search USING fts5(metadata);
+            CREATE VIRTUAL TABLE IF NOT EXISTS resolved_search USING fts5(content);
+
+            CREATE TRIGGER IF NOT EXISTS node_search_insert AFTER INSERT ON nodes BEGIN
+                INSERT OR IGNORE INTO search_keys(source, key) VALUES ('node', new.id);
+                INSERT INTO node_search(rowid, content, metadata)
+                SELECT id, new.content, new.metadata FROM search_keys WHERE source = 'node' AND key = new.id;
+            END;
+            CREATE TRIGGER IF NOT EXISTS node_search_delete AFTER DELETE ON nodes BEGIN
+                DELETE FROM node_search WHERE rowid = (SELECT id FROM search_keys WHERE source = 'node' AND key = old.id);
+                DELETE FROM search_keys WHERE source = 'node' AND key = old.id;
+            END;
+            CREATE TRIGGER IF NOT EXISTS node_search_update AFTER UPDATE OF content, metadata ON nodes BEGIN
+                UPDATE node_search SET content = new.content, metadata = new.metadata WHERE rowid = (SELECT id FROM search_keys WHERE source = 'node' AND key = new.id);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS tree_search_insert AFTER INSERT ON trees BEGIN
+                INSERT OR IGNORE INTO search_keys(source, key) VALUES ('tree', new.id);
+                INSERT INTO tree_search(rowid, metadata)
+                SELECT id, new.metadata FROM search_keys WHERE source = 'tree' AND key = new.id;
+            END;
+            CREATE TRIGGER IF NOT EXISTS tree_search_delete AFTER DELETE ON trees BEGIN
+                DELETE FROM tree_search WHERE rowid = (SELECT id FROM search_keys WHERE source = 'tree' AND key = old.id);
+                DELETE FROM search_keys WHERE source = 'tree' AND key = old.id;
+            END;
+            CREATE TRIGGER IF NOT EXISTS tree_search_update AFTER UPDATE OF metadata ON trees BEGIN
+                UPDATE tree_search SET metadata = new.metadata WHERE rowid = (SELECT id FROM search_keys WHERE source = 'tree' AND key = new.id);
+            END;
+
+            CREATE TRIGGER IF NOT EXISTS resolved_search_insert AFTER INSERT ON node_resolved BEGIN
+                INSERT OR IGNORE INTO search_keys(source, key) VALUES ('resolved', new.node_id);
+                INSERT INTO resolved_search(rowid, content)
+                SELECT id, new.content FROM search_keys WHERE source = 'resolved' AND key = new.node_id;
+            END;
+            CREATE TRIGGER IF NOT EXISTS resolved_search_delete AFTER DELETE ON node_resolved BEGIN
+                DELETE FROM resolved_search WHERE rowid = (SELECT id FROM search_keys WHERE source = 'resolved' AND key = old.node_id);
+                DELETE FROM search_keys WHERE source = 'resolved' AND key = old.node_id;
+            END;
+            CREATE TRIGGER IF NOT EXISTS resolved_search_update AFTER UPDATE OF content ON node_resolved BEGIN
+                UPDATE resolved_search SET content = new.content WHERE rowid = (SELECT id FROM search_keys WHERE source = 'resolved' AND key = new.node_id);
+            END;
+
+            INSERT OR IGNORE INTO search_keys(source, key) SELECT 'node', id FROM nodes;
+            INSERT INTO node_search(rowid, content, metadata)
+            SELECT k.id, s.content, s.metadata FROM nodes s JOIN search_keys k ON k.source = 'node' AND k.key = s.id;
+            INSERT OR IGNORE INTO search_keys(source, key) SELECT 'tree', id FROM trees;
+            INSERT INTO tree_search(rowid, metadata)
+            SELECT k.id, s.metadata FROM trees s JOIN search_keys k ON k.source = 'tree' AND k.key = s.id;
+            INSERT OR IGNORE INTO search_keys(source, key) SELECT 'resolved', node_id FROM node_resolved;
+            INSERT INTO resolved_search(rowid, content)
+            SELECT k.id, s.content FROM node_resolved s JOIN search_keys k ON k.source = 'resolved' AND k.key = s.node_id;
+        "#,
+    },
+];
 
 /// Configuration for Arbor storage
 #[derive(Debug, Clone)]
@@ -25,6 +296,9 @@ pub struct ArborConfig {
 
     /// Cleanup task interval (seconds)
     pub cleanup_interval: i64, // Default: 1 hour = 3600
+
+    /// Root-to-node paths kept in the LRU path cache (0 disables it)
+    pub path_cache_capacity: usize, // Default: 1024
 }
 
 impl Default for ArborConfig {
@@ -35,6 +309,7 @@ impl Default for ArborConfig {
             db_path: PathBuf::from("arbor.db"),
             auto_cleanup: true,
             cleanup_interval: 3600,             // 1 hour
+            path_cache_capacity: 1024,
         }
     }
 }
@@ -65,12 +340,30 @@ impl Default for ArborConfig {
 /// See: `docs/architecture/*_arbor-usage-pattern.md`
 pub struct ArborStorage {
     pool: SqlitePool,
-    #[allow(dead_code)]
     config: ArborConfig,
+    /// Report of the most recent cleanup run (manual or background)
+    last_cleanup: Arc<Mutex<Option<CleanupReport>>>,
+    /// Background cleanup worker, aborted when the storage is dropped
+    cleanup_task: Option<JoinHandle<()>>,
+    /// In-process change feed; every write publishes here after it commits
+    events: broadcast::Sender<ArborEvent>,
+    /// Hot root-to-node paths, invalidated per tree when parent links change
+    paths: PathCache,
+}
+
+impl Drop for ArborStorage {
+    fn drop(&mut self) {
+        if let Some(task) = self.cleanup_task.take() {
+            task.abort();
+        }
+    }
 }
 
 impl ArborStorage {
     /// Create a new storage instance and run migrations
+    ///
+    /// When `config.auto_cleanup` is set, this also spawns the background
+    /// lifecycle worker.
     pub async fn new(config: ArborConfig) -> Result<Self, ArborError> {
         let db_url = format!("sqlite:{}?mode=rwc", config.db_path.display());
         let mut connect_options: SqliteConnectOptions = db_url.parse()
@@ -80,89 +373,46 @@ impl ArborStorage {
             .await
             .map_err(|e| format!("Failed to connect to database: {}", e))?;
 
-        let storage = Self { pool, config };
+        let paths = PathCache::new(config.path_cache_capacity);
+        let mut storage = Self {
+            pool,
+            config,
+            last_cleanup: Arc::new(Mutex::new(None)),
+            cleanup_task: None,
+            events: broadcast::channel(EVENT_CHANNEL_CAPACITY).0,
+            paths,
+        };
         storage.run_migrations().await?;
 
+        if storage.config.auto_cleanup {
+            storage.cleanup_task = Some(tokio::spawn(cleanup_worker(
+                storage.pool.clone(),
+                storage.config.clone(),
+                storage.last_cleanup.clone(),
+                storage.events.clone(),
+                storage.paths.clone(),
+            )));
+        }
+
         Ok(storage)
     }
 
-    /// Run database migrations
-    async fn run_migrations(&self) -> Result<(), ArborError> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS trees (
-                id TEXT PRIMARY KEY,
-                root_node_id TEXT NOT NULL,
-                ref_count INTEGER NOT NULL DEFAULT 1,
-                state TEXT NOT NULL DEFAULT 'active',
-                scheduled_deletion_at INTEGER,
-                archived_at INTEGER,
-                created_at INTEGER NOT NULL,
-                updated_at INTEGER NOT NULL,
-                metadata TEXT
-            );
-
-            CREATE TABLE IF NOT EXISTS tree_refs (
-                tree_id TEXT NOT NULL,
-                owner_id TEXT NOT NULL,
-                count INTEGER NOT NULL DEFAULT 1,
-                claimed_at INTEGER NOT NULL,
-                PRIMARY KEY (tree_id, owner_id),
-                FOREIGN KEY (tree_id) REFERENCES trees(id) ON DELETE CASCADE
-            );
-
-            CREATE TABLE IF NOT EXISTS nodes (
-                id TEXT PRIMARY KEY,
-                tree_id TEXT NOT NULL,
-                parent_id TEXT,
-                ref_count INTEGER NOT NULL DEFAULT 1,
-                state TEXT NOT NULL DEFAULT 'active',
-                scheduled_deletion_at INTEGER,
-                archived_at INTEGER,
-                node_type TEXT NOT NULL,
-                content TEXT,
-                handle_plugin_id TEXT,
-                handle_version TEXT,
-                handle_method TEXT,
-                handle_meta TEXT,
-                created_at INTEGER NOT NULL,
-                metadata TEXT,
-                FOREIGN KEY (tree_id) REFERENCES trees(id) ON DELETE CASCADE
-            );
-
-            CREATE TABLE IF NOT EXISTS node_refs (
-                node_id TEXT NOT NULL,
-                owner_id TEXT NOT NULL,
-                count INTEGER NOT NULL DEFAULT 1,
-                claimed_at INTEGER NOT NULL,
-                PRIMARY KEY (node_id, owner_id),
-                FOREIGN KEY (node_id) REFERENCES nodes(id) ON DELETE CASCADE
-            );
-
-            CREATE TABLE IF NOT EXISTS node_children (
-                parent_id TEXT NOT NULL,
-                child_id TEXT NOT NULL,
-                position INTEGER NOT NULL,
-                PRIMARY KEY (parent_id, child_id),
-                FOREIGN KEY (parent_id) REFERENCES nodes(id) ON DELETE CASCADE,
-                FOREIGN KEY (child_id) REFERENCES nodes(id) ON DELETE CASCADE
-            );
+    /// Subscribe to the change feed
+    ///
+    /// Receives an `ArborEvent` for every committed write, from any activation
+    /// sharing this storage. Slow receivers may observe `RecvError::Lagged`.
+    pub fn subscribe(&self) -> broadcast::Receiver<ArborEvent> {
+        self.events.subscribe()
+    }
 
-            CREATE INDEX IF NOT EXISTS idx_trees_state ON trees(state);
-            CREATE INDEX IF NOT EXISTS idx_trees_scheduled ON trees(scheduled_deletion_at) WHERE state = 'scheduled_delete';
-            CREATE INDEX IF NOT EXISTS idx_trees_archived ON trees(archived_at) WHERE state = 'archived';
-            CREATE INDEX IF NOT EXISTS idx_nodes_tree ON nodes(tree_id);
-            CREATE INDEX IF NOT EXISTS idx_nodes_parent ON nodes(parent_id);
-            CREATE INDEX IF NOT EXISTS idx_nodes_state ON nodes(state);
-            CREATE INDEX IF NOT EXISTS idx_nodes_scheduled ON nodes(scheduled_deletion_at) WHERE state = 'scheduled_delete';
-            CREATE INDEX IF NOT EXISTS idx_node_children_parent ON node_children(parent_id);
-            CREATE INDEX IF NOT EXISTS idx_node_children_child ON node_children(child_id);
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to run migrations: {}", e))?;
+    /// Publish an event to the change feed (no-op without subscribers)
+    fn publish(&self, event: ArborEvent) {
+        let _ = self.events.send(event);
+    }
 
+    /// Run database migrations
+    async fn run_migrations(&self) -> Result<(), ArborError> {
+        migrations::run_migrations(&self.pool, "arbor", MIGRATIONS).await?;
         Ok(())
     }
 
@@ -222,6 +472,7 @@ impl ArborStorage {
 
         tx.commit().await.map_err(|e| e.to_string())?;
 
+        self.publish(ArborEvent::TreeCreated { tree_id });
         Ok(tree_id)
     }
 
@@ -235,11 +486,30 @@ impl ArborStorage {
         self.tree_get_internal(tree_id, true).await
     }
 
+    /// Get a tree's header (metadata, state and refs) without loading its nodes
+    ///
+    /// The returned tree has an empty `nodes` map; fetch nodes in pages with
+    /// `tree_get_nodes_batch` or by branch with `tree_get_subtree`.
+    pub async fn tree_get_header(&self, tree_id: &TreeId) -> Result<Tree, ArborError> {
+        self.tree_header_internal(tree_id, false).await
+    }
+
     /// Internal tree getter
     async fn tree_get_internal(
         &self,
         tree_id: &TreeId,
         allow_archived: bool,
+    ) -> Result<Tree, ArborError> {
+        let mut tree = self.tree_header_internal(tree_id, allow_archived).await?;
+        tree.nodes = self.get_nodes_for_tree(tree_id).await?;
+        Ok(tree)
+    }
+
+    /// Internal tree header getter
+    async fn tree_header_internal(
+        &self,
+        tree_id: &TreeId,
+        allow_archived: bool,
     ) -> Result<Tree, ArborError> {
         // Get tree metadata
         let tree_row = sqlx::query(
@@ -263,9 +533,6 @@ impl ArborStorage {
         let root_node_id = ArborId::parse_str(&root_node_id)
             .map_err(|e| format!("Invalid root node ID: {}", e))?;
 
-        // Get all nodes for this tree
-        let nodes = self.get_nodes_for_tree(tree_id).await?;
-
         // Get reference information
         let refs = self.get_tree_refs(tree_id).await?;
 
@@ -275,7 +542,7 @@ impl ArborStorage {
         Ok(Tree {
             id: *tree_id,
             root: root_node_id,
-            nodes,
+            nodes: HashMap::new(),
             state: Some(state),
             refs: Some(refs),
             scheduled_deletion_at: tree_row.get("scheduled_deletion_at"),
@@ -288,130 +555,91 @@ impl ArborStorage {
 
     /// Get all nodes for a tree
     async fn get_nodes_for_tree(&self, tree_id: &TreeId) -> Result<HashMap<NodeId, Node>, ArborError> {
-        let rows = sqlx::query(
-            "SELECT id, tree_id, parent_id, ref_count, state, scheduled_deletion_at, archived_at,
-                    node_type, content, handle_plugin_id, handle_version, handle_method,
-                    handle_meta, created_at, metadata
-             FROM nodes WHERE tree_id = ?",
-        )
-        .bind(tree_id.to_string())
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to fetch nodes: {}", e))?;
-
-        let mut nodes = HashMap::new();
+        let rows = sqlx::query(&format!("SELECT {} FROM nodes WHERE tree_id = ?", NODE_COLUMNS))
+            .bind(tree_id.to_string())
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to fetch nodes: {}", e))?;
 
-        for row in rows {
-            let node_id_str: String = row.get("id");
-            let node_id = ArborId::parse_str(&node_id_str)
-                .map_err(|e| format!("Invalid node ID: {}", e))?;
+        let nodes = self.nodes_from_rows(&rows).await?;
+        Ok(nodes.into_iter().map(|node| (node.id, node)).collect())
+    }
 
-            let parent_id_str: Option<String> = row.get("parent_id");
-            let parent_id = parent_id_str
-                .map(|s| ArborId::parse_str(&s).map_err(|e| format!("Invalid parent ID: {}", e)))
-                .transpose()?;
+    /// Build a node from a row selected with `NODE_COLUMNS`, loading its
+    /// children and references
+    async fn node_from_row(&self, row: &SqliteRow) -> Result<Node, ArborError> {
+        let mut nodes = self.nodes_from_rows(std::slice::from_ref(row)).await?;
+        Ok(nodes.remove(0))
+    }
 
-            // Get children for this node
-            let children = self.get_node_children_internal(&node_id).await?;
+    /// Build nodes from rows selected with `NODE_COLUMNS`, in row order
+    ///
+    /// Children and references for all rows are loaded with one query each
+    /// instead of two queries per node.
+    async fn nodes_from_rows(&self, rows: &[SqliteRow]) -> Result<Vec<Node>, ArborError> {
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
 
-            let node_type_str: String = row.get("node_type");
-            let data = match node_type_str.as_str() {
-                "text" => {
-                    let content: String = row.get("content");
-                    NodeType::Text { content }
-                }
-                "external" => {
-                    let plugin_id_str: String = row.get("handle_plugin_id");
-                    let plugin_id = Uuid::parse_str(&plugin_id_str)
-                        .map_err(|e| format!("Invalid handle plugin_id: {}", e))?;
-                    let version: String = row.get("handle_version");
-                    let method: String = row.get("handle_method");
-                    let meta_json: Option<String> = row.get("handle_meta");
-                    let meta: Vec<String> = meta_json
-                        .and_then(|s| serde_json::from_str(&s).ok())
-                        .unwrap_or_default();
-
-                    NodeType::External {
-                        handle: Handle::new(plugin_id, version, method)
-                            .with_meta(meta),
-                    }
-                }
-                _ => return Err(format!("Unknown node type: {}", node_type_str).into()),
-            };
+        let ids: Vec<String> = rows.iter().map(|row| row.get("id")).collect();
+        let ids_json = serde_json::to_string(&ids)
+            .map_err(|e| format!("Failed to serialize node IDs: {}", e))?;
 
-            let state_str: String = row.get("state");
-            let state = ResourceState::from_str(&state_str).unwrap_or(ResourceState::Active);
-
-            let refs = self.get_node_refs(&node_id).await?;
-
-            let metadata_json: Option<String> = row.get("metadata");
-            let metadata = metadata_json.and_then(|s| serde_json::from_str(&s).ok());
-
-            let node = Node {
-                id: node_id,
-                parent: parent_id,
-                children,
-                data,
-                state: Some(state),
-                refs: Some(refs),
-                scheduled_deletion_at: row.get("scheduled_deletion_at"),
-                archived_at: row.get("archived_at"),
-                created_at: row.get("created_at"),
-                metadata,
-            };
+        let child_rows = sqlx::query(
+            "SELECT parent_id, child_id FROM node_children
+             WHERE parent_id IN (SELECT value FROM json_each(?))
+             ORDER BY parent_id, position",
+        )
+        .bind(&ids_json)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to fetch node children: {}", e))?;
 
-            nodes.insert(node_id, node);
+        let mut children: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+        for row in &child_rows {
+            children
+                .entry(parse_id(row, "parent_id")?)
+                .or_default()
+                .push(parse_id(row, "child_id")?);
         }
 
-        Ok(nodes)
-    }
-
-    /// Get children for a node (ordered by position)
-    async fn get_node_children_internal(&self, node_id: &NodeId) -> Result<Vec<NodeId>, ArborError> {
-        let rows = sqlx::query(
-            "SELECT child_id FROM node_children WHERE parent_id = ? ORDER BY position",
+        let ref_rows = sqlx::query(
+            "SELECT node_id, owner_id, count FROM node_refs
+             WHERE node_id IN (SELECT value FROM json_each(?))",
         )
-        .bind(node_id.to_string())
+        .bind(&ids_json)
         .fetch_all(&self.pool)
         .await
-        .map_err(|e| format!("Failed to fetch node children: {}", e))?;
+        .map_err(|e| format!("Failed to fetch node refs: {}", e))?;
 
-        let children: Result<Vec<NodeId>, ArborError> = rows
-            .iter()
+        let mut refs: HashMap<NodeId, ResourceRefs> = HashMap::new();
+        for row in &ref_rows {
+            let count: i64 = row.get("count");
+            let node_refs = refs.entry(parse_id(row, "node_id")?).or_insert_with(|| ResourceRefs {
+                ref_count: 0,
+                owners: HashMap::new(),
+            });
+            node_refs.owners.insert(row.get("owner_id"), count);
+            node_refs.ref_count += count;
+        }
+
+        rows.iter()
             .map(|row| {
-                let child_id_str: String = row.get("child_id");
-                ArborId::parse_str(&child_id_str)
-                    .map_err(|e| e.into())
+                let node_id = parse_id(row, "id")?;
+                let node_children = children.remove(&node_id).unwrap_or_default();
+                let node_refs = refs.remove(&node_id).unwrap_or_else(|| ResourceRefs {
+                    ref_count: 0,
+                    owners: HashMap::new(),
+                });
+                build_node(row, node_children, node_refs)
             })
-            .collect();
-
-        children
+            .collect()
     }
 
     /// Add a child to a parent in the node_children table
     async fn add_child_to_parent(&self, parent_id: &NodeId, child_id: &NodeId) -> Result<(), ArborError> {
-        // Get next position for this parent
-        let row = sqlx::query(
-            "SELECT COALESCE(MAX(position), -1) + 1 as next_pos FROM node_children WHERE parent_id = ?",
-        )
-        .bind(parent_id.to_string())
-        .fetch_one(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to get next position: {}", e))?;
-
-        let next_pos: i64 = row.get("next_pos");
-
-        sqlx::query(
-            "INSERT INTO node_children (parent_id, child_id, position) VALUES (?, ?, ?)",
-        )
-        .bind(parent_id.to_string())
-        .bind(child_id.to_string())
-        .bind(next_pos)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to add child to parent: {}", e))?;
-
-        Ok(())
+        let mut conn = self.pool.acquire().await.map_err(|e| e.to_string())?;
+        append_child(&mut conn, parent_id, child_id).await
     }
 
     /// Get reference information for a tree
@@ -533,26 +761,245 @@ impl ArborStorage {
         tree_ids
     }
 
-    /// Update tree metadata
-    pub async fn tree_update_metadata(
+    /// List trees with a summary of each, filtered, sorted by updated_at and paged
+    ///
+    /// Returns the page and the cursor for the next one (None on the last page).
+    pub async fn tree_list_summaries(
         &self,
-        tree_id: &TreeId,
-        metadata: Value,
-    ) -> Result<(), ArborError> {
-        let now = current_timestamp();
-        let metadata_json = serde_json::to_string(&metadata)
-            .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+        query: &TreeListQuery,
+    ) -> Result<(Vec<TreeSummary>, Option<String>), ArborError> {
+        let states = if query.states.is_empty() {
+            vec![ResourceState::Active]
+        } else {
+            query.states.clone()
+        };
 
-        sqlx::query(
-            "UPDATE trees SET metadata = ?, updated_at = ? WHERE id = ? AND state = 'active'",
-        )
-        .bind(metadata_json)
-        .bind(now)
-        .bind(tree_id.to_string())
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to update tree metadata: {}", e))?;
+        let mut sql = format!(
+            "SELECT id, updated_at FROM trees WHERE state IN ({})",
+            vec!["?"; states.len()].join(", ")
+        );
+        let mut binds: Vec<String> = states.iter().map(|s| s.as_str().to_string()).collect();
+
+        if let Some(filter) = &query.metadata {
+            let conditions = filter
+                .as_object()
+                .ok_or("Metadata filter must be a JSON object")?;
+            binds.extend(push_metadata_conditions(&mut sql, conditions)?);
+        }
+
+        let (cmp, order) = match query.order {
+            ListOrder::Desc => ("<", "DESC"),
+            ListOrder::Asc => (">", "ASC"),
+        };
+        let cursor = query.cursor.as_deref().map(parse_tree_cursor).transpose()?;
+        if cursor.is_some() {
+            sql.push_str(&format!(" AND (updated_at {cmp} ? OR (updated_at = ? AND id {cmp} ?))"));
+        }
+        sql.push_str(&format!(" ORDER BY updated_at {order}, id {order} LIMIT ?"));
+
+        let mut db_query = sqlx::query(&sql);
+        for bind in binds {
+            db_query = db_query.bind(bind);
+        }
+        if let Some((updated_at, id)) = &cursor {
+            db_query = db_query.bind(*updated_at).bind(*updated_at).bind(id.to_string());
+        }
+
+        // Fetch one extra row to learn whether another page follows
+        let rows = db_query
+            .bind(query.limit as i64 + 1)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to list trees: {}", e))?;
+
+        let has_more = rows.len() > query.limit;
+        let mut trees = Vec::with_capacity(rows.len().min(query.limit));
+        for row in rows.iter().take(query.limit) {
+            let tree_id = parse_id(row, "id")?;
+            trees.push(self.tree_summary(&tree_id).await?);
+        }
+
+        let next_cursor = if has_more {
+            trees.last().map(|t| format!("{}:{}", t.updated_at, t.id))
+        } else {
+            None
+        };
+
+        Ok((trees, next_cursor))
+    }
+
+    /// Summarize one tree: header fields plus active node, leaf and depth counts
+    pub async fn tree_summary(&self, tree_id: &TreeId) -> Result<TreeSummary, ArborError> {
+        let tree = self.tree_header_internal(tree_id, true).await?;
+
+        let counts = sqlx::query(
+            "SELECT COUNT(*) AS node_count,
+                    COALESCE(SUM(NOT EXISTS (
+                        SELECT 1 FROM node_children c JOIN nodes k ON k.id = c.child_id
+                        WHERE c.parent_id = n.id AND k.state = 'active'
+                    )), 0) AS leaf_count
+             FROM nodes n WHERE n.tree_id = ? AND n.state = 'active'",
+        )
+        .bind(tree_id.to_string())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to count nodes: {}", e))?;
+
+        let depth = sqlx::query(
+            "WITH RECURSIVE walk(node_id, depth) AS (
+                 SELECT root_node_id, 0 FROM trees WHERE id = ?
+                 UNION ALL
+                 SELECT c.child_id, walk.depth + 1
+                 FROM node_children c
+                 JOIN walk ON c.parent_id = walk.node_id
+                 JOIN nodes k ON k.id = c.child_id AND k.state = 'active'
+             )
+             SELECT COALESCE(MAX(depth), 0) AS depth FROM walk",
+        )
+        .bind(tree_id.to_string())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to measure tree depth: {}", e))?
+        .get::<i64, _>("depth");
+
+        let name = tree
+            .metadata
+            .as_ref()
+            .and_then(|m| m.get("name"))
+            .and_then(|n| n.as_str())
+            .map(str::to_string);
+
+        Ok(TreeSummary {
+            id: tree.id,
+            name,
+            state: tree.state.unwrap_or(ResourceState::Active),
+            node_count: counts.get::<i64, _>("node_count") as usize,
+            leaf_count: counts.get::<i64, _>("leaf_count") as usize,
+            depth: depth as usize,
+            created_at: tree.created_at,
+            updated_at: tree.updated_at,
+            metadata: tree.metadata,
+        })
+    }
+
+    /// Measure the shape and size of a tree's active nodes
+    ///
+    /// External nodes count with their cached resolved content (see
+    /// `node_set_resolved`); uncached ones are reported as unresolved.
+    pub async fn tree_stats(&self, tree_id: &TreeId) -> Result<TreeStats, ArborError> {
+        self.tree_header_internal(tree_id, true).await?;
+
+        let rows = sqlx::query(
+            "WITH RECURSIVE sized(id, node_type, bytes, unresolved) AS (
+                 SELECT n.id, n.node_type,
+                        CASE WHEN n.node_type = 'text'
+                             THEN COALESCE(length(CAST(n.content AS BLOB)), 0)
+                             ELSE COALESCE(length(CAST(r.content AS BLOB)), 0) END,
+                        n.node_type = 'external' AND r.node_id IS NULL
+                 FROM nodes n
+                 LEFT JOIN node_resolved r ON r.node_id = n.id
+                 WHERE n.tree_id = ? AND n.state = 'active'
+             ),
+             walk(node_id, node_type, bytes, unresolved, depth, path_bytes, path_unresolved) AS (
+                 SELECT s.id, s.node_type, s.bytes, s.unresolved, 0, s.bytes, s.unresolved
+                 FROM trees t JOIN sized s ON s.id = t.root_node_id
+                 WHERE t.id = ?
+                 UNION ALL
+                 SELECT s.id, s.node_type, s.bytes, s.unresolved, walk.depth + 1,
+                        walk.path_bytes + s.bytes, walk.path_unresolved + s.unresolved
+                 FROM walk
+                 JOIN node_children c ON c.parent_id = walk.node_id
+                 JOIN sized s ON s.id = c.child_id
+             )
+             SELECT walk.*,
+                    (SELECT COUNT(*) FROM node_children c JOIN sized s ON s.id = c.child_id
+                     WHERE c.parent_id = walk.node_id) AS child_count
+             FROM walk",
+        )
+        .bind(tree_id.to_string())
+        .bind(tree_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to compute tree stats: {}", e))?;
+
+        let mut stats = TreeStats {
+            tree_id: *tree_id,
+            node_count: rows.len(),
+            node_types: NodeTypeCounts::default(),
+            leaf_count: 0,
+            depth: 0,
+            branching_factor: 0.0,
+            max_children: 0,
+            text_bytes: 0,
+            resolved_bytes: 0,
+            unresolved_count: 0,
+            leaf_paths: Vec::new(),
+        };
+        let (mut parents, mut child_edges) = (0usize, 0usize);
+
+        for row in &rows {
+            let bytes = row.get::<i64, _>("bytes") as usize;
+            let depth = row.get::<i64, _>("depth") as usize;
+            let child_count = row.get::<i64, _>("child_count") as usize;
+
+            if row.get::<String, _>("node_type") == "text" {
+                stats.node_types.text += 1;
+                stats.text_bytes += bytes;
+            } else {
+                stats.node_types.external += 1;
+                stats.resolved_bytes += bytes;
+                if row.get::<i64, _>("unresolved") != 0 {
+                    stats.unresolved_count += 1;
+                }
+            }
+
+            stats.depth = stats.depth.max(depth);
+            stats.max_children = stats.max_children.max(child_count);
+            if child_count > 0 {
+                parents += 1;
+                child_edges += child_count;
+            } else {
+                let path_bytes = row.get::<i64, _>("path_bytes") as usize;
+                stats.leaf_paths.push(LeafPathStats {
+                    leaf_id: parse_id(row, "node_id")?,
+                    depth,
+                    bytes: path_bytes,
+                    tokens: estimate_tokens(path_bytes),
+                    unresolved: row.get::<i64, _>("path_unresolved") as usize,
+                });
+            }
+        }
+
+        stats.leaf_count = stats.leaf_paths.len();
+        if parents > 0 {
+            stats.branching_factor = child_edges as f64 / parents as f64;
+        }
+        stats.leaf_paths.sort_by(|a, b| b.bytes.cmp(&a.bytes).then(b.depth.cmp(&a.depth)));
+
+        Ok(stats)
+    }
+
+    /// Update tree metadata
+    pub async fn tree_update_metadata(
+        &self,
+        tree_id: &TreeId,
+        metadata: Value,
+    ) -> Result<(), ArborError> {
+        let now = current_timestamp();
+        let metadata_json = serde_json::to_string(&metadata)
+            .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+
+        sqlx::query(
+            "UPDATE trees SET metadata = ?, updated_at = ? WHERE id = ? AND state = 'active'",
+        )
+        .bind(metadata_json)
+        .bind(now)
+        .bind(tree_id.to_string())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to update tree metadata: {}", e))?;
 
+        self.publish(ArborEvent::TreeUpdated { tree_id: *tree_id });
         Ok(())
     }
 
@@ -632,6 +1079,12 @@ impl ArborStorage {
         let new_count: i64 = new_count_row.get("ref_count");
 
         tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.publish(ArborEvent::TreeClaimed {
+            tree_id: *tree_id,
+            owner_id: owner_id.to_string(),
+            new_count,
+        });
         Ok(new_count)
     }
 
@@ -720,9 +1173,77 @@ impl ArborStorage {
         }
 
         tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.publish(ArborEvent::TreeReleased {
+            tree_id: *tree_id,
+            owner_id: owner_id.to_string(),
+            new_count: ref_count,
+        });
+        if ref_count == 0 {
+            self.publish(ArborEvent::TreeScheduledDeletion { tree_id: *tree_id, scheduled_at: now });
+        }
         Ok(ref_count)
     }
 
+    /// Restore a tree scheduled for deletion or archived
+    ///
+    /// Moves the tree back to `active` and claims one reference for
+    /// `owner_id`. Unlike `tree_claim`, this also works on archived trees, as
+    /// long as they have not been purged yet. Returns the new reference count.
+    pub async fn tree_restore(&self, tree_id: &TreeId, owner_id: &str) -> Result<i64, ArborError> {
+        let now = current_timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        let tree_row = sqlx::query("SELECT state FROM trees WHERE id = ?")
+            .bind(tree_id.to_string())
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to fetch tree: {}", e))?
+            .ok_or_else(|| format!("Tree not found: {}", tree_id))?;
+
+        let state_str: String = tree_row.get("state");
+        if ResourceState::from_str(&state_str) == Some(ResourceState::Active) {
+            return Err(format!("Tree is already active: {}", tree_id).into());
+        }
+
+        sqlx::query(
+            "INSERT INTO tree_refs (tree_id, owner_id, count, claimed_at)
+             VALUES (?, ?, 1, ?)
+             ON CONFLICT(tree_id, owner_id) DO UPDATE SET
+                count = count + 1,
+                claimed_at = excluded.claimed_at",
+        )
+        .bind(tree_id.to_string())
+        .bind(owner_id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to claim tree: {}", e))?;
+
+        let new_count: i64 = sqlx::query(
+            "UPDATE trees
+             SET state = 'active', scheduled_deletion_at = NULL, archived_at = NULL,
+                 ref_count = ref_count + 1, updated_at = ?
+             WHERE id = ?
+             RETURNING ref_count",
+        )
+        .bind(now)
+        .bind(tree_id.to_string())
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to restore tree: {}", e))?
+        .get("ref_count");
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.publish(ArborEvent::TreeRestored {
+            tree_id: *tree_id,
+            owner_id: owner_id.to_string(),
+            new_count,
+        });
+        Ok(new_count)
+    }
+
     /// Create a text node in a tree
     pub async fn node_create_text(
         &self,
@@ -755,6 +1276,7 @@ impl ArborStorage {
             self.add_child_to_parent(&parent_id, &node_id).await?;
         }
 
+        self.publish(ArborEvent::NodeCreated { tree_id: *tree_id, node_id, parent });
         Ok(node_id)
     }
 
@@ -794,6 +1316,7 @@ impl ArborStorage {
             self.add_child_to_parent(&parent_id, &node_id).await?;
         }
 
+        self.publish(ArborEvent::NodeCreated { tree_id: *tree_id, node_id, parent });
         Ok(node_id)
     }
 
@@ -834,6 +1357,7 @@ impl ArborStorage {
             self.add_child_to_parent(&parent_id, &node_id).await?;
         }
 
+        self.publish(ArborEvent::NodeCreated { tree_id: *tree_id, node_id, parent });
         Ok(node_id)
     }
 
@@ -843,11 +1367,18 @@ impl ArborStorage {
         tree_id: &TreeId,
         node_id: &NodeId,
     ) -> Result<Node, ArborError> {
-        let nodes = self.get_nodes_for_tree(tree_id).await?;
-        nodes
-            .get(node_id)
-            .cloned()
-            .ok_or_else(|| format!("Node not found: {}", node_id).into())
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM nodes WHERE tree_id = ? AND id = ?",
+            NODE_COLUMNS
+        ))
+        .bind(tree_id.to_string())
+        .bind(node_id.to_string())
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to fetch node: {}", e))?
+        .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+        self.node_from_row(&row).await
     }
 
     /// Get children of a node
@@ -877,6 +1408,140 @@ impl ArborStorage {
         children
     }
 
+    /// Get one page of a node's active children, in child order
+    ///
+    /// `cursor` is the last child of the previous page. Returns the page and
+    /// the cursor for the next one (None when there are no more children).
+    pub async fn node_get_children_page(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        cursor: Option<&NodeId>,
+        limit: usize,
+    ) -> Result<(Vec<NodeId>, Option<NodeId>), ArborError> {
+        let after = match cursor {
+            Some(cursor) => sqlx::query(
+                "SELECT position FROM node_children WHERE parent_id = ? AND child_id = ?",
+            )
+            .bind(node_id.to_string())
+            .bind(cursor.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to fetch cursor: {}", e))?
+            .ok_or_else(|| format!("Invalid cursor: {} is not a child of {}", cursor, node_id))?
+            .get::<i64, _>("position"),
+            None => -1,
+        };
+
+        // Fetch one extra row to learn whether another page follows
+        let rows = sqlx::query(
+            "SELECT c.child_id FROM node_children c
+             JOIN nodes n ON n.id = c.child_id
+             WHERE c.parent_id = ? AND n.tree_id = ? AND n.state = 'active' AND c.position > ?
+             ORDER BY c.position
+             LIMIT ?",
+        )
+        .bind(node_id.to_string())
+        .bind(tree_id.to_string())
+        .bind(after)
+        .bind(limit as i64 + 1)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to fetch children: {}", e))?;
+
+        let mut children = rows
+            .iter()
+            .map(|row| parse_id(row, "child_id"))
+            .collect::<Result<Vec<NodeId>, ArborError>>()?;
+
+        let next_cursor = if children.len() > limit {
+            children.truncate(limit);
+            children.last().copied()
+        } else {
+            None
+        };
+
+        Ok((children, next_cursor))
+    }
+
+    /// Get a node and its descendants down to `max_depth` levels below it
+    ///
+    /// Nodes are returned breadth-first, starting with `node_id` itself
+    /// (depth 0). Nodes on the last level keep their `children` lists so the
+    /// caller can continue from there. `None` loads the whole subtree.
+    pub async fn tree_get_subtree(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        max_depth: Option<usize>,
+    ) -> Result<Vec<Node>, ArborError> {
+        let rows = sqlx::query(&format!(
+            "WITH RECURSIVE subtree(node_id, depth) AS (
+                 SELECT id, 0 FROM nodes WHERE tree_id = ? AND id = ?
+                 UNION ALL
+                 SELECT c.child_id, subtree.depth + 1
+                 FROM node_children c JOIN subtree ON c.parent_id = subtree.node_id
+                 WHERE ? IS NULL OR subtree.depth < ?
+             )
+             SELECT {} FROM subtree JOIN nodes ON nodes.id = subtree.node_id
+             ORDER BY subtree.depth",
+            NODE_COLUMNS
+        ))
+        .bind(tree_id.to_string())
+        .bind(node_id.to_string())
+        .bind(max_depth.map(|d| d as i64))
+        .bind(max_depth.map(|d| d as i64))
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to fetch subtree: {}", e))?;
+
+        if rows.is_empty() {
+            return Err(format!("Node not found: {}", node_id).into());
+        }
+
+        self.nodes_from_rows(&rows).await
+    }
+
+    /// Get one batch of a tree's nodes, ordered by node ID
+    ///
+    /// `after` is the last node of the previous batch. Returns the batch and
+    /// the cursor for the next one (None once every node has been returned).
+    pub async fn tree_get_nodes_batch(
+        &self,
+        tree_id: &TreeId,
+        after: Option<&NodeId>,
+        limit: usize,
+    ) -> Result<(Vec<Node>, Option<NodeId>), ArborError> {
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM nodes WHERE tree_id = ? AND id > ? ORDER BY id LIMIT ?",
+            NODE_COLUMNS
+        ))
+        .bind(tree_id.to_string())
+        .bind(after.map(|id| id.to_string()).unwrap_or_default())
+        .bind(limit as i64 + 1)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to fetch nodes: {}", e))?;
+
+        let has_more = rows.len() > limit;
+        let nodes = self.nodes_from_rows(&rows[..rows.len().min(limit)]).await?;
+
+        let next = if has_more { nodes.last().map(|n| n.id) } else { None };
+        Ok((nodes, next))
+    }
+
+    /// Count all nodes stored for a tree, in any state
+    pub async fn tree_node_count(&self, tree_id: &TreeId) -> Result<usize, ArborError> {
+        let count: i64 = sqlx::query("SELECT COUNT(*) AS count FROM nodes WHERE tree_id = ?")
+            .bind(tree_id.to_string())
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to count nodes: {}", e))?
+            .get("count");
+
+        Ok(count as usize)
+    }
+
     /// Get parent of a node
     pub async fn node_get_parent(
         &self,
@@ -904,73 +1569,162 @@ impl ArborStorage {
     }
 
     /// Get path from root to a node (list of node IDs)
+    ///
+    /// Walks the parent links in a single recursive query and caches the
+    /// result, so repeated lookups of hot paths (such as a cone's head) cost
+    /// no queries at all.
     pub async fn node_get_path(
         &self,
         tree_id: &TreeId,
         node_id: &NodeId,
     ) -> Result<Vec<NodeId>, ArborError> {
-        let mut path = Vec::new();
-        let mut current_id = Some(*node_id);
+        if let Some(path) = self.paths.get(tree_id, node_id) {
+            return Ok(path);
+        }
+
+        let rows = sqlx::query(
+            "WITH RECURSIVE ancestors(id, parent_id, depth) AS (
+                 SELECT id, parent_id, 0 FROM nodes WHERE tree_id = ? AND id = ?
+                 UNION ALL
+                 SELECT n.id, n.parent_id, a.depth + 1
+                 FROM nodes n JOIN ancestors a ON n.id = a.parent_id
+             )
+             SELECT id FROM ancestors ORDER BY depth DESC",
+        )
+        .bind(tree_id.to_string())
+        .bind(node_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to fetch path: {}", e))?;
 
-        // Walk up the tree to root
-        while let Some(id) = current_id {
-            path.push(id);
-            current_id = self.node_get_parent(tree_id, &id).await?;
+        if rows.is_empty() {
+            return Err(format!("Node not found: {}", node_id).into());
         }
 
-        // Reverse to get root-to-node path
-        path.reverse();
+        let path = rows
+            .iter()
+            .map(|row| parse_id(row, "id"))
+            .collect::<Result<Vec<NodeId>, ArborError>>()?;
+
+        self.paths.insert(tree_id, node_id, path.clone());
         Ok(path)
     }
 
     /// List all leaf nodes in a tree
+    ///
+    /// Leaves are active nodes reachable from the root over active nodes that
+    /// have no active children.
     pub async fn context_list_leaves(
         &self,
         tree_id: &TreeId,
     ) -> Result<Vec<NodeId>, ArborError> {
         let rows = sqlx::query(
-            "SELECT id FROM nodes
-             WHERE tree_id = ? AND state = 'active'
-             AND id NOT IN (SELECT DISTINCT parent_id FROM nodes WHERE parent_id IS NOT NULL AND tree_id = ?)",
+            "WITH RECURSIVE reachable(id) AS (
+                 SELECT n.id FROM trees t JOIN nodes n ON n.id = t.root_node_id
+                 WHERE t.id = ? AND n.state = 'active'
+                 UNION ALL
+                 SELECT n.id FROM nodes n JOIN reachable r ON n.parent_id = r.id
+                 WHERE n.state = 'active'
+             )
+             SELECT id FROM reachable
+             WHERE NOT EXISTS (
+                 SELECT 1 FROM nodes c WHERE c.parent_id = reachable.id AND c.state = 'active'
+             )",
         )
         .bind(tree_id.to_string())
-        .bind(tree_id.to_string())
         .fetch_all(&self.pool)
         .await
         .map_err(|e| format!("Failed to fetch leaf nodes: {}", e))?;
 
-        let leaves: Result<Vec<NodeId>, ArborError> = rows
-            .iter()
-            .map(|row| {
-                let id_str: String = row.get("id");
-                ArborId::parse_str(&id_str)
-                    .map_err(|e| format!("Invalid node ID: {}", e).into())
-            })
-            .collect();
-
-        leaves
+        rows.iter().map(|row| parse_id(row, "id")).collect()
     }
 
     /// Get the full path data from root to a node (all node data)
+    ///
+    /// Loads only the nodes on the path, not the whole tree.
     pub async fn context_get_path(
         &self,
         tree_id: &TreeId,
         node_id: &NodeId,
     ) -> Result<Vec<Node>, ArborError> {
         let path_ids = self.node_get_path(tree_id, node_id).await?;
-        let nodes = self.get_nodes_for_tree(tree_id).await?;
+        let ids: Vec<String> = path_ids.iter().map(|id| id.to_string()).collect();
+        let ids_json = serde_json::to_string(&ids)
+            .map_err(|e| format!("Failed to serialize node IDs: {}", e))?;
+
+        let rows = sqlx::query(&format!(
+            "SELECT {} FROM nodes WHERE tree_id = ? AND id IN (SELECT value FROM json_each(?))",
+            NODE_COLUMNS
+        ))
+        .bind(tree_id.to_string())
+        .bind(&ids_json)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to fetch path nodes: {}", e))?;
+
+        let mut nodes: HashMap<NodeId, Node> = self
+            .nodes_from_rows(&rows)
+            .await?
+            .into_iter()
+            .map(|node| (node.id, node))
+            .collect();
 
-        let path_nodes: Result<Vec<Node>, ArborError> = path_ids
+        path_ids
             .iter()
             .map(|id| {
                 nodes
-                    .get(id)
-                    .cloned()
+                    .remove(id)
                     .ok_or_else(|| format!("Node not found in path: {}", id).into())
             })
-            .collect();
+            .collect()
+    }
+
+    /// Find the lowest common ancestor of two nodes
+    ///
+    /// A node counts as its own ancestor, so if `a` is on the path to `b`
+    /// the result is `a`.
+    pub async fn node_common_ancestor(
+        &self,
+        tree_id: &TreeId,
+        a: &NodeId,
+        b: &NodeId,
+    ) -> Result<NodeId, ArborError> {
+        let path_a = self.node_get_path(tree_id, a).await?;
+        let path_b = self.node_get_path(tree_id, b).await?;
+
+        path_a
+            .iter()
+            .zip(path_b.iter())
+            .take_while(|(x, y)| x == y)
+            .last()
+            .map(|(x, _)| *x)
+            .ok_or_else(|| format!("Nodes {} and {} share no ancestor", a, b).into())
+    }
+
+    /// Compare two branches
+    ///
+    /// Returns the lowest common ancestor and the node sequences leading from
+    /// just below it down to `a` and to `b` respectively.
+    pub async fn branch_diff(
+        &self,
+        tree_id: &TreeId,
+        a: &NodeId,
+        b: &NodeId,
+    ) -> Result<(NodeId, Vec<Node>, Vec<Node>), ArborError> {
+        let mut path_a = self.context_get_path(tree_id, a).await?;
+        let mut path_b = self.context_get_path(tree_id, b).await?;
 
-        path_nodes
+        let shared = path_a
+            .iter()
+            .zip(path_b.iter())
+            .take_while(|(x, y)| x.id == y.id)
+            .count();
+        if shared == 0 {
+            return Err(format!("Nodes {} and {} share no ancestor", a, b).into());
+        }
+
+        let ancestor = path_a[shared - 1].id;
+        Ok((ancestor, path_a.split_off(shared), path_b.split_off(shared)))
     }
 
     /// Get all external handles in the path to a node
@@ -992,25 +1746,2155 @@ impl ArborStorage {
         Ok(handles)
     }
 
-    /// Cleanup task: Archive trees scheduled for deletion (after 7 days)
-    pub async fn cleanup_scheduled_trees(&self) -> Result<usize, ArborError> {
-        let now = current_timestamp();
-        let seven_days_ago = now - (7 * 24 * 60 * 60);
+    // ========================================================================
+    // Search
+    // ========================================================================
 
-        let result = sqlx::query(
-            "UPDATE trees
-             SET state = 'archived', archived_at = ?, updated_at = ?
-             WHERE state = 'scheduled_delete' AND scheduled_deletion_at < ?",
+    /// Full-text search across trees
+    ///
+    /// Matches text node content, node metadata and tree metadata, plus cached
+    /// resolved external content when `include_resolved` is set (see
+    /// `node_set_resolved`). Every whitespace-separated term must match.
+    /// Tree metadata matches are reported on the tree's root node. Results are
+    /// ranked best first by BM25, normalised per source so that node, tree
+    /// and resolved matches can be merged.
+    pub async fn search(
+        &self,
+        query: &str,
+        tree_id: Option<TreeId>,
+        include_resolved: bool,
+        limit: usize,
+    ) -> Result<Vec<SearchHit>, ArborError> {
+        let fts_query = fts_phrase_query(query);
+        if fts_query.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut sources = vec![
+            (
+                SearchSource::Node,
+                "SELECT n.id AS node_id, n.tree_id AS tree_id, bm25(node_search) AS score,
+                        snippet(node_search, -1, '[', ']', '...', 12) AS snippet
+                 FROM node_search
+                 JOIN search_keys k ON k.id = node_search.rowid AND k.source = 'node'
+                 JOIN nodes n ON n.id = k.key
+                 JOIN trees t ON t.id = n.tree_id
+                 WHERE node_search MATCH ? AND n.state = 'active' AND t.state = 'active'
+                 AND (? IS NULL OR n.tree_id = ?)
+                 ORDER BY score LIMIT ?",
+            ),
+            (
+                SearchSource::Tree,
+                "SELECT t.root_node_id AS node_id, t.id AS tree_id, bm25(tree_search) AS score,
+                        snippet(tree_search, -1, '[', ']', '...', 12) AS snippet
+                 FROM tree_search
+                 JOIN search_keys k ON k.id = tree_search.rowid AND k.source = 'tree'
+                 JOIN trees t ON t.id = k.key
+                 WHERE tree_search MATCH ? AND t.state = 'active'
+                 AND (? IS NULL OR t.id = ?)
+                 ORDER BY score LIMIT ?",
+            ),
+        ];
+        if include_resolved {
+            sources.push((
+                SearchSource::Resolved,
+                "SELECT n.id AS node_id, n.tree_id AS tree_id, bm25(resolved_search) AS score,
+                        snippet(resolved_search, -1, '[', ']', '...', 12) AS snippet
+                 FROM resolved_search
+                 JOIN search_keys k ON k.id = resolved_search.rowid AND k.source = 'resolved'
+                 JOIN nodes n ON n.id = k.key
+                 JOIN trees t ON t.id = n.tree_id
+                 WHERE resolved_search MATCH ? AND n.state = 'active' AND t.state = 'active'
+                 AND (? IS NULL OR n.tree_id = ?)
+                 ORDER BY score LIMIT ?",
+            ));
+        }
+
+        let tree_filter = tree_id.map(|t| t.to_string());
+        let mut hits = Vec::new();
+
+        for (source, sql) in sources {
+            let rows = sqlx::query(sql)
+                .bind(&fts_query)
+                .bind(&tree_filter)
+                .bind(&tree_filter)
+                .bind(limit as i64)
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to search: {}", e))?;
+
+            // BM25 scores are negative, lower-is-better, and only comparable
+            // within one table: scale each source so its best match is 1.0
+            let best: Option<f64> = rows.first().map(|row| row.get("score"));
+            for row in &rows {
+                let score: f64 = row.get("score");
+                hits.push(SearchHit {
+                    tree_id: parse_id(row, "tree_id")?,
+                    node_id: parse_id(row, "node_id")?,
+                    path: vec![],
+                    source,
+                    snippet: row.get("snippet"),
+                    score: match best {
+                        Some(best) if best < 0.0 => score / best,
+                        _ => 1.0,
+                    },
+                });
+            }
+        }
+
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+        hits.truncate(limit);
+
+        for hit in hits.iter_mut() {
+            hit.path = self.node_get_path(&hit.tree_id, &hit.node_id).await?;
+        }
+
+        Ok(hits)
+    }
+
+    /// List active external nodes whose resolved content is not cached yet
+    ///
+    /// Oldest first, at most `limit`. Nodes whose last resolution failed are
+    /// skipped until `RESOLVE_RETRY_WINDOW` has passed.
+    pub async fn search_unresolved_handles(
+        &self,
+        tree_id: Option<TreeId>,
+        limit: usize,
+    ) -> Result<Vec<(NodeId, Handle)>, ArborError> {
+        let tree_filter = tree_id.map(|t| t.to_string());
+        let rows = sqlx::query(
+            "SELECT n.id, n.handle_plugin_id, n.handle_version, n.handle_method, n.handle_meta
+             FROM nodes n
+             LEFT JOIN node_resolved r ON r.node_id = n.id
+             LEFT JOIN node_resolve_failures f ON f.node_id = n.id
+             WHERE n.node_type = 'external' AND n.state = 'active' AND r.node_id IS NULL
+             AND (f.node_id IS NULL OR f.failed_at <= ?)
+             AND (? IS NULL OR n.tree_id = ?)
+             ORDER BY n.created_at, n.id
+             LIMIT ?",
         )
-        .bind(now)
-        .bind(now)
-        .bind(seven_days_ago)
+        .bind(current_timestamp() - RESOLVE_RETRY_WINDOW)
+        .bind(&tree_filter)
+        .bind(&tree_filter)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to fetch unresolved handles: {}", e))?;
+
+        let mut handles = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let plugin_id_str: String = row.get("handle_plugin_id");
+            let plugin_id = Uuid::parse_str(&plugin_id_str)
+                .map_err(|e| format!("Invalid handle plugin_id: {}", e))?;
+            let meta_json: Option<String> = row.get("handle_meta");
+            let meta: Vec<String> = meta_json
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default();
+            let handle = Handle::new(plugin_id, row.get::<String, _>("handle_version"), row.get::<String, _>("handle_method"))
+                .with_meta(meta);
+            handles.push((parse_id(row, "id")?, handle));
+        }
+
+        Ok(handles)
+    }
+
+    /// Count the nodes `search_unresolved_handles` would currently return
+    pub async fn search_unresolved_count(&self, tree_id: Option<TreeId>) -> Result<usize, ArborError> {
+        let tree_filter = tree_id.map(|t| t.to_string());
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS remaining
+             FROM nodes n
+             LEFT JOIN node_resolved r ON r.node_id = n.id
+             LEFT JOIN node_resolve_failures f ON f.node_id = n.id
+             WHERE n.node_type = 'external' AND n.state = 'active' AND r.node_id IS NULL
+             AND (f.node_id IS NULL OR f.failed_at <= ?)
+             AND (? IS NULL OR n.tree_id = ?)",
+        )
+        .bind(current_timestamp() - RESOLVE_RETRY_WINDOW)
+        .bind(&tree_filter)
+        .bind(&tree_filter)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to count unresolved handles: {}", e))?;
+
+        Ok(row.get::<i64, _>("remaining") as usize)
+    }
+
+    /// Cache the resolved content of an external node for search
+    pub async fn node_set_resolved(&self, node_id: &NodeId, content: &str) -> Result<(), ArborError> {
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        sqlx::query(
+            "INSERT INTO node_resolved (node_id, content, resolved_at) VALUES (?, ?, ?)
+             ON CONFLICT(node_id) DO UPDATE SET content = excluded.content, resolved_at = excluded.resolved_at",
+        )
+        .bind(node_id.to_string())
+        .bind(content)
+        .bind(current_timestamp())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to cache resolved content: {}", e))?;
+
+        sqlx::query("DELETE FROM node_resolve_failures WHERE node_id = ?")
+            .bind(node_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to clear resolve failure: {}", e))?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        Ok(())
+    }
+
+    /// Record that an external node failed to resolve, so search indexing
+    /// backs off instead of retrying it on every call
+    pub async fn node_set_resolve_failed(&self, node_id: &NodeId, error: &str) -> Result<(), ArborError> {
+        sqlx::query(
+            "INSERT INTO node_resolve_failures (node_id, error, failed_at) VALUES (?, ?, ?)
+             ON CONFLICT(node_id) DO UPDATE SET error = excluded.error, failed_at = excluded.failed_at",
+        )
+        .bind(node_id.to_string())
+        .bind(error)
+        .bind(current_timestamp())
         .execute(&self.pool)
         .await
-        .map_err(|e| format!("Failed to archive trees: {}", e))?;
+        .map_err(|e| format!("Failed to record resolve failure: {}", e))?;
+
+        Ok(())
+    }
+
+    // ========================================================================
+    // Export / Import
+    // ========================================================================
 
-        Ok(result.rows_affected() as usize)
+    /// Export a tree as a portable bundle
+    ///
+    /// Only active nodes reachable from the root are included. External
+    /// handles are exported unresolved; callers with hub access fill in
+    /// `BundleNode::resolved` themselves.
+    pub async fn tree_export(&self, tree_id: &TreeId) -> Result<TreeBundle, ArborError> {
+        let tree = self.tree_get(tree_id).await?;
+        Ok(TreeBundle::from_tree(&tree, current_timestamp()))
     }
+
+    /// Import a bundle as a new tree owned by `owner_id`
+    ///
+    /// Every tree and node gets a fresh ID, so the same bundle can be imported
+    /// repeatedly. With `inline_resolved`, external nodes that carry resolved
+    /// content become text nodes holding that content, and the original handle
+    /// is kept in the node metadata under `imported_handle`.
+    pub async fn tree_import(
+        &self,
+        bundle: &TreeBundle,
+        owner_id: &str,
+        inline_resolved: bool,
+    ) -> Result<TreeId, ArborError> {
+        let by_id = bundle.nodes_by_id();
+        if !by_id.contains_key(&bundle.header.root) {
+            return Err("Bundle does not contain its root node".into());
+        }
+
+        let tree_id = TreeId::new();
+        let now = current_timestamp();
+        let id_map: HashMap<NodeId, NodeId> =
+            bundle.nodes.iter().map(|n| (n.id, NodeId::new())).collect();
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        let metadata_json = bundle.header.metadata.as_ref().map(|m| serde_json::to_string(m).unwrap());
+        sqlx::query(
+            "INSERT INTO trees (id, root_node_id, ref_count, state, created_at, updated_at, metadata)
+             VALUES (?, ?, 1, 'active', ?, ?, ?)",
+        )
+        .bind(tree_id.to_string())
+        .bind(id_map[&bundle.header.root].to_string())
+        .bind(bundle.header.created_at)
+        .bind(now)
+        .bind(metadata_json)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to create tree: {}", e))?;
+
+        sqlx::query(
+            "INSERT INTO tree_refs (tree_id, owner_id, count, claimed_at) VALUES (?, ?, 1, ?)",
+        )
+        .bind(tree_id.to_string())
+        .bind(owner_id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to create tree ref: {}", e))?;
+
+        // Walk from the root so parents exist before their children
+        let mut queue = std::collections::VecDeque::from([(bundle.header.root, None)]);
+        while let Some((old_id, new_parent)) = queue.pop_front() {
+            let node = by_id
+                .get(&old_id)
+                .ok_or_else(|| format!("Bundle references missing node: {}", old_id))?;
+            let new_id = id_map[&old_id];
+
+            let (data, metadata) = match (&node.data, &node.resolved) {
+                (NodeType::External { handle }, Some(resolved)) if inline_resolved => {
+                    let content = resolved
+                        .get("content")
+                        .and_then(|c| c.as_str())
+                        .map(str::to_string)
+                        .unwrap_or_else(|| resolved.to_string());
+                    let mut metadata = node.metadata.clone().unwrap_or_else(|| serde_json::json!({}));
+                    if let Some(obj) = metadata.as_object_mut() {
+                        obj.insert("imported_handle".to_string(), serde_json::json!(handle.to_string()));
+                    }
+                    (NodeType::Text { content }, Some(metadata))
+                }
+                _ => (node.data.clone(), node.metadata.clone()),
+            };
+
+            insert_node(&mut tx, &tree_id, &new_id, new_parent, &data, metadata.as_ref(), node.created_at).await?;
+            if let Some(parent_id) = new_parent {
+                append_child(&mut tx, &parent_id, &new_id).await?;
+            }
+
+            for child in &node.children {
+                queue.push_back((*child, Some(new_id)));
+            }
+        }
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.publish(ArborEvent::TreeCreated { tree_id });
+        Ok(tree_id)
+    }
+
+    // ========================================================================
+    // Copy / Clone
+    // ========================================================================
+
+    /// Copy the active subtree under `src_node` to a new parent, possibly in another tree
+    ///
+    /// Every copied node gets a fresh ID, a `now` creation time and its own
+    /// implicit tree reference. External nodes keep pointing at the same
+    /// handles. Owner claims are not copied.
+    ///
+    /// Returns the ID of the copy of `src_node`.
+    pub async fn node_copy_subtree(
+        &self,
+        src_tree: &TreeId,
+        src_node: &NodeId,
+        dst_tree: &TreeId,
+        dst_parent: &NodeId,
+    ) -> Result<NodeId, ArborError> {
+        let src = self.tree_get(src_tree).await?;
+        if !src.nodes.contains_key(src_node) {
+            return Err(format!("Node not found: {}", src_node).into());
+        }
+
+        let parent = self.node_get(dst_tree, dst_parent).await?;
+        if parent.state != Some(ResourceState::Active) {
+            return Err(format!("Destination parent is not active: {}", dst_parent).into());
+        }
+
+        let now = current_timestamp();
+        let new_root = NodeId::new();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        check_tree_active(&mut tx, dst_tree).await?;
+
+        let created = copy_subtree(&mut tx, &src, src_node, dst_tree, Some(*dst_parent), new_root, now).await?;
+        touch_tree(&mut tx, dst_tree, now).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        for (node_id, parent) in created {
+            self.publish(ArborEvent::NodeCreated { tree_id: *dst_tree, node_id, parent });
+        }
+        Ok(new_root)
+    }
+
+    /// Clone a tree into a new tree owned by `owner_id`
+    ///
+    /// Without `from_node`, the whole active tree is copied. With `from_node`,
+    /// only the path from the root down to that node is copied, which is the
+    /// usual way to start a fresh conversation from a useful prefix; every
+    /// node on that path must be active. Copies follow the same rules as
+    /// `node_copy_subtree`.
+    ///
+    /// Returns the new tree ID and the copy of `from_node` (or the new root).
+    pub async fn tree_clone(
+        &self,
+        tree_id: &TreeId,
+        from_node: Option<NodeId>,
+        owner_id: &str,
+    ) -> Result<(TreeId, NodeId), ArborError> {
+        let src = self.tree_get(tree_id).await?;
+        let new_tree = TreeId::new();
+        let new_root = NodeId::new();
+        let now = current_timestamp();
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        let metadata_json = src.metadata.as_ref().map(|m| serde_json::to_string(m).unwrap());
+        sqlx::query(
+            "INSERT INTO trees (id, root_node_id, ref_count, state, created_at, updated_at, metadata)
+             VALUES (?, ?, 1, 'active', ?, ?, ?)",
+        )
+        .bind(new_tree.to_string())
+        .bind(new_root.to_string())
+        .bind(now)
+        .bind(now)
+        .bind(metadata_json)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to create tree: {}", e))?;
+
+        sqlx::query(
+            "INSERT INTO tree_refs (tree_id, owner_id, count, claimed_at) VALUES (?, ?, 1, ?)",
+        )
+        .bind(new_tree.to_string())
+        .bind(owner_id)
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to create tree ref: {}", e))?;
+
+        let head = match from_node {
+            None => {
+                copy_subtree(&mut tx, &src, &src.root, &new_tree, None, new_root, now).await?;
+                new_root
+            }
+            Some(from_node) => {
+                // Collect the root-to-node path from the parent links
+                let mut path = Vec::new();
+                let mut current = Some(from_node);
+                while let Some(id) = current {
+                    let node = src
+                        .nodes
+                        .get(&id)
+                        .ok_or_else(|| format!("Node not found: {}", id))?;
+                    if node.state.as_ref().is_some_and(|s| *s != ResourceState::Active) {
+                        return Err(format!("Node is not active: {}", id).into());
+                    }
+                    path.push(node);
+                    current = node.parent;
+                }
+                path.reverse();
+
+                let mut parent = None;
+                for (i, node) in path.iter().enumerate() {
+                    let new_id = if i == 0 { new_root } else { NodeId::new() };
+                    insert_node(&mut tx, &new_tree, &new_id, parent, &node.data, node.metadata.as_ref(), now).await?;
+                    if let Some(parent_id) = parent {
+                        append_child(&mut tx, &parent_id, &new_id).await?;
+                    }
+                    parent = Some(new_id);
+                }
+                parent.unwrap_or(new_root)
+            }
+        };
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.publish(ArborEvent::TreeCreated { tree_id: new_tree });
+        Ok((new_tree, head))
+    }
+
+    // ========================================================================
+    // Node Versioning
+    // ========================================================================
+
+    /// Update a node copy-on-write
+    ///
+    /// Writes a new node carrying the changed content or handle, puts it in the
+    /// old node's place (same parent and child position, or as the tree root),
+    /// and re-parents the old node's children onto it. Owner claims move to the
+    /// new node, the old node is scheduled for deletion, and the old→new
+    /// lineage is recorded so `node_history` can still walk it and holders of
+    /// the old ID (e.g. cone heads) can follow it with `node_current_version`.
+    ///
+    /// Fields left as `None` are carried over from the old node. Passing both
+    /// `content` and `handle` is an error.
+    ///
+    /// Returns the ID of the new node.
+    pub async fn node_update(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        content: Option<String>,
+        handle: Option<Handle>,
+        metadata: Option<Value>,
+    ) -> Result<NodeId, ArborError> {
+        let data = match (content, handle) {
+            (Some(_), Some(_)) => {
+                return Err("Specify either content or handle, not both".into());
+            }
+            (Some(content), None) => Some(NodeType::Text { content }),
+            (None, Some(handle)) => Some(NodeType::External { handle }),
+            (None, None) => None,
+        };
+
+        let new_id = NodeId::new();
+        let now = current_timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        let row = sqlx::query(&format!(
+            "SELECT {} FROM nodes WHERE tree_id = ? AND id = ?",
+            NODE_COLUMNS
+        ))
+        .bind(tree_id.to_string())
+        .bind(node_id.to_string())
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to fetch node: {}", e))?
+        .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+        let ref_count: i64 = row.get("ref_count");
+        let old = build_node(&row, Vec::new(), ResourceRefs { ref_count, owners: HashMap::new() })?;
+        if old.state != Some(ResourceState::Active) {
+            return Err(format!("Node is not active: {}", node_id).into());
+        }
+
+        let data = data.unwrap_or(old.data);
+        let metadata = metadata.or(old.metadata);
+
+        insert_node(&mut tx, tree_id, &new_id, old.parent, &data, metadata.as_ref(), now).await?;
+
+        // Owner claims follow the content to its new version
+        sqlx::query("UPDATE node_refs SET node_id = ? WHERE node_id = ?")
+            .bind(new_id.to_string())
+            .bind(node_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to move node refs: {}", e))?;
+
+        sqlx::query("UPDATE nodes SET ref_count = ? WHERE id = ?")
+            .bind(ref_count)
+            .bind(new_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to update node ref_count: {}", e))?;
+
+        // Take the old node's place
+        match old.parent {
+            Some(parent_id) => {
+                sqlx::query(
+                    "UPDATE node_children SET child_id = ? WHERE parent_id = ? AND child_id = ?",
+                )
+                .bind(new_id.to_string())
+                .bind(parent_id.to_string())
+                .bind(node_id.to_string())
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to replace child: {}", e))?;
+            }
+            None => {
+                sqlx::query("UPDATE trees SET root_node_id = ? WHERE id = ?")
+                    .bind(new_id.to_string())
+                    .bind(tree_id.to_string())
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| format!("Failed to replace root node: {}", e))?;
+            }
+        }
+
+        // Re-parent the old node's children onto the new node
+        sqlx::query("UPDATE nodes SET parent_id = ? WHERE tree_id = ? AND parent_id = ?")
+            .bind(new_id.to_string())
+            .bind(tree_id.to_string())
+            .bind(node_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to re-parent children: {}", e))?;
+
+        sqlx::query("UPDATE node_children SET parent_id = ? WHERE parent_id = ?")
+            .bind(new_id.to_string())
+            .bind(node_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to re-parent children: {}", e))?;
+
+        schedule_node_deletion(&mut tx, tree_id, node_id, now).await?;
+
+        sqlx::query(
+            "INSERT INTO node_versions (old_node_id, new_node_id, tree_id, created_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(node_id.to_string())
+        .bind(new_id.to_string())
+        .bind(tree_id.to_string())
+        .bind(now)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to record node version: {}", e))?;
+
+        touch_tree(&mut tx, tree_id, now).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.paths.invalidate_tree(tree_id);
+
+        self.publish(ArborEvent::NodeUpdated { tree_id: *tree_id, old_id: *node_id, new_id });
+        Ok(new_id)
+    }
+
+    /// Get the version lineage of a node, oldest first
+    ///
+    /// Walks `node_update` lineage in both directions from `node_id`, so any
+    /// version in the chain returns the same history.
+    pub async fn node_history(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+    ) -> Result<Vec<NodeId>, ArborError> {
+        let mut history = vec![*node_id];
+
+        // Walk back to the oldest version
+        loop {
+            let row = sqlx::query(
+                "SELECT old_node_id FROM node_versions WHERE tree_id = ? AND new_node_id = ?",
+            )
+            .bind(tree_id.to_string())
+            .bind(history[0].to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to fetch node version: {}", e))?;
+
+            let Some(row) = row else { break };
+            let old_id: String = row.get("old_node_id");
+            let old_id = ArborId::parse_str(&old_id)
+                .map_err(|e| format!("Invalid node ID: {}", e))?;
+            history.insert(0, old_id);
+        }
+
+        // Walk forward to the newest version
+        loop {
+            let row = sqlx::query(
+                "SELECT new_node_id FROM node_versions WHERE tree_id = ? AND old_node_id = ?",
+            )
+            .bind(tree_id.to_string())
+            .bind(history[history.len() - 1].to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to fetch node version: {}", e))?;
+
+            let Some(row) = row else { break };
+            let new_id: String = row.get("new_node_id");
+            let new_id = ArborId::parse_str(&new_id)
+                .map_err(|e| format!("Invalid node ID: {}", e))?;
+            history.push(new_id);
+        }
+
+        Ok(history)
+    }
+
+    /// Follow `node_update` lineage to the newest version of a node
+    ///
+    /// Returns `node_id` itself when it was never updated. Lineage outlives
+    /// purging, so this also works for old versions that no longer exist.
+    pub async fn node_current_version(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+    ) -> Result<NodeId, ArborError> {
+        let row = sqlx::query(
+            "WITH RECURSIVE lineage(id, depth) AS (
+                 SELECT ?, 0
+                 UNION ALL
+                 SELECT v.new_node_id, l.depth + 1
+                 FROM node_versions v JOIN lineage l ON v.old_node_id = l.id
+                 WHERE v.tree_id = ?
+             )
+             SELECT id FROM lineage ORDER BY depth DESC LIMIT 1",
+        )
+        .bind(node_id.to_string())
+        .bind(tree_id.to_string())
+        .fetch_one(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to fetch node version: {}", e))?;
+
+        parse_id(&row, "id")
+    }
+
+    // ========================================================================
+    // Node Structure
+    // ========================================================================
+
+    /// Move a node (and its subtree) under a new parent
+    ///
+    /// `position` is the index among the new parent's active children to
+    /// insert at; children scheduled for deletion are skipped when counting,
+    /// and `None` or an index past the end appends. Moving within the same parent
+    /// just changes the node's position. Only active nodes can be moved, the
+    /// root cannot be moved, and a node cannot be moved under itself or one of
+    /// its descendants.
+    ///
+    /// Returns the old parent and the node's position under the new parent.
+    pub async fn node_move(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        new_parent: &NodeId,
+        position: Option<usize>,
+    ) -> Result<(NodeId, usize), ArborError> {
+        let now = current_timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        let node_row = sqlx::query("SELECT parent_id FROM nodes WHERE tree_id = ? AND id = ? AND state = 'active'")
+            .bind(tree_id.to_string())
+            .bind(node_id.to_string())
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to fetch node: {}", e))?
+            .ok_or_else(|| format!("Node not found or not active: {}", node_id))?;
+
+        let old_parent: Option<String> = node_row.get("parent_id");
+        let old_parent = old_parent.ok_or("Cannot move the root node")?;
+
+        sqlx::query("SELECT id FROM nodes WHERE tree_id = ? AND id = ? AND state = 'active'")
+            .bind(tree_id.to_string())
+            .bind(new_parent.to_string())
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to fetch new parent: {}", e))?
+            .ok_or_else(|| format!("New parent not found or not active: {}", new_parent))?;
+
+        // The new parent must not be the node itself or one of its descendants
+        let cycle = sqlx::query(
+            "WITH RECURSIVE ancestors(id) AS (
+                 SELECT ?
+                 UNION ALL
+                 SELECT n.parent_id FROM nodes n JOIN ancestors a ON n.id = a.id
+                 WHERE n.parent_id IS NOT NULL
+             )
+             SELECT 1 FROM ancestors WHERE id = ?",
+        )
+        .bind(new_parent.to_string())
+        .bind(node_id.to_string())
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to check ancestry: {}", e))?;
+
+        if cycle.is_some() {
+            return Err(format!("Cannot move node {} under its own subtree", node_id).into());
+        }
+
+        sqlx::query("DELETE FROM node_children WHERE parent_id = ? AND child_id = ?")
+            .bind(&old_parent)
+            .bind(node_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to detach node: {}", e))?;
+        compact_child_positions(&mut tx, &old_parent).await?;
+
+        // `position` counts active siblings only; map it onto the raw child
+        // list, which also holds children scheduled for deletion
+        let siblings = sqlx::query(
+            "SELECT c.position, n.state FROM node_children c
+             JOIN nodes n ON n.id = c.child_id
+             WHERE c.parent_id = ?
+             ORDER BY c.position",
+        )
+        .bind(new_parent.to_string())
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to fetch children: {}", e))?;
+
+        let active: Vec<i64> = siblings
+            .iter()
+            .filter(|row| row.get::<String, _>("state") == "active")
+            .map(|row| row.get("position"))
+            .collect();
+        let index = position.map_or(active.len(), |p| p.min(active.len()));
+        let raw_position = active.get(index).copied().unwrap_or(siblings.len() as i64);
+
+        sqlx::query("UPDATE node_children SET position = position + 1 WHERE parent_id = ? AND position >= ?")
+            .bind(new_parent.to_string())
+            .bind(raw_position)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to shift siblings: {}", e))?;
+
+        sqlx::query("INSERT INTO node_children (parent_id, child_id, position) VALUES (?, ?, ?)")
+            .bind(new_parent.to_string())
+            .bind(node_id.to_string())
+            .bind(raw_position)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to attach node: {}", e))?;
+
+        sqlx::query("UPDATE nodes SET parent_id = ? WHERE id = ?")
+            .bind(new_parent.to_string())
+            .bind(node_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to re-parent node: {}", e))?;
+
+        touch_tree(&mut tx, tree_id, now).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+        self.paths.invalidate_tree(tree_id);
+
+        let old_parent = ArborId::parse_str(&old_parent)
+            .map_err(|e| format!("Invalid parent ID: {}", e))?;
+        self.publish(ArborEvent::NodeMoved {
+            tree_id: *tree_id,
+            node_id: *node_id,
+            old_parent,
+            new_parent: *new_parent,
+            position: index,
+        });
+        Ok((old_parent, index))
+    }
+
+    /// Reorder a node's children
+    ///
+    /// `children` must list exactly the node's current children (including
+    /// ones scheduled for deletion), in the new order.
+    pub async fn node_reorder_children(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        children: &[NodeId],
+    ) -> Result<(), ArborError> {
+        let now = current_timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        sqlx::query("SELECT id FROM nodes WHERE tree_id = ? AND id = ?")
+            .bind(tree_id.to_string())
+            .bind(node_id.to_string())
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to fetch node: {}", e))?
+            .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+        let rows = sqlx::query("SELECT child_id FROM node_children WHERE parent_id = ?")
+            .bind(node_id.to_string())
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to fetch node children: {}", e))?;
+
+        let current: HashSet<NodeId> = rows
+            .iter()
+            .map(|row| parse_id(row, "child_id"))
+            .collect::<Result<_, _>>()?;
+        let requested: HashSet<NodeId> = children.iter().copied().collect();
+
+        if requested.len() != children.len() || requested != current {
+            return Err(format!(
+                "Children must be a reordering of the node's {} current children",
+                current.len()
+            )
+            .into());
+        }
+
+        for (position, child_id) in children.iter().enumerate() {
+            sqlx::query("UPDATE node_children SET position = ? WHERE parent_id = ? AND child_id = ?")
+                .bind(position as i64)
+                .bind(node_id.to_string())
+                .bind(child_id.to_string())
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to reorder children: {}", e))?;
+        }
+
+        touch_tree(&mut tx, tree_id, now).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.publish(ArborEvent::ChildrenReordered {
+            tree_id: *tree_id,
+            node_id: *node_id,
+            children: children.to_vec(),
+        });
+        Ok(())
+    }
+
+    // ========================================================================
+    // Node Metadata
+    // ========================================================================
+
+    /// Merge a patch into a node's metadata (JSON merge patch, RFC 7396)
+    ///
+    /// Object keys in `patch` are merged recursively, `null` values remove
+    /// keys, and any other value replaces what was there. Unlike
+    /// `node_update`, this edits the node in place. Returns the new metadata.
+    pub async fn node_update_metadata(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        patch: Value,
+    ) -> Result<Value, ArborError> {
+        let now = current_timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        let metadata = patch_node_metadata(&mut tx, tree_id, node_id, &patch).await?;
+
+        touch_tree(&mut tx, tree_id, now).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.publish(ArborEvent::NodeMetadataUpdated {
+            tree_id: *tree_id,
+            node_id: *node_id,
+            metadata: metadata.clone(),
+        });
+        Ok(metadata)
+    }
+
+    /// Find active nodes whose metadata matches every key/value in `filter`
+    ///
+    /// `filter` must be a non-empty JSON object; each top-level key must be
+    /// present in the node's metadata with an equal value. Searches all
+    /// active trees unless `tree_id` is given. Results are oldest first.
+    pub async fn node_query(
+        &self,
+        tree_id: Option<&TreeId>,
+        filter: &Value,
+        limit: usize,
+    ) -> Result<Vec<NodeQueryMatch>, ArborError> {
+        let conditions = filter
+            .as_object()
+            .filter(|f| !f.is_empty())
+            .ok_or("Filter must be a non-empty JSON object")?;
+
+        let mut sql = format!(
+            "SELECT {} FROM nodes
+             WHERE state = 'active'
+               AND tree_id IN (SELECT id FROM trees WHERE state = 'active')",
+            NODE_COLUMNS
+        );
+        if tree_id.is_some() {
+            sql.push_str(" AND tree_id = ?");
+        }
+        let binds = push_metadata_conditions(&mut sql, conditions)?;
+        sql.push_str(" ORDER BY created_at, rowid LIMIT ?");
+
+        let mut query = sqlx::query(&sql);
+        if let Some(tree_id) = tree_id {
+            query = query.bind(tree_id.to_string());
+        }
+        for bind in binds {
+            query = query.bind(bind);
+        }
+
+        let rows = query
+            .bind(limit as i64)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to query nodes: {}", e))?;
+
+        let nodes = self.nodes_from_rows(&rows).await?;
+        rows.iter()
+            .zip(nodes)
+            .map(|(row, node)| {
+                Ok(NodeQueryMatch {
+                    tree_id: parse_id(row, "tree_id")?,
+                    node,
+                })
+            })
+            .collect()
+    }
+
+    // ========================================================================
+    // Batch Operations
+    // ========================================================================
+
+    /// Apply a list of operations to a tree in a single transaction
+    ///
+    /// Operations run in order and may refer to nodes created by earlier
+    /// operations with `BatchNodeRef::Op`. Returns the node each operation
+    /// created or touched. If any operation fails, nothing is applied.
+    pub async fn batch(&self, tree_id: &TreeId, ops: Vec<BatchOp>) -> Result<Vec<NodeId>, ArborError> {
+        let now = current_timestamp();
+        let mut node_ids: Vec<NodeId> = Vec::with_capacity(ops.len());
+        let mut events = Vec::with_capacity(ops.len());
+
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        for (i, op) in ops.into_iter().enumerate() {
+            let (node_id, event) = apply_batch_op(&mut tx, tree_id, op, &node_ids, now)
+                .await
+                .map_err(|e| format!("Batch operation {} failed: {}", i, e.message))?;
+            node_ids.push(node_id);
+            events.push(event);
+        }
+
+        touch_tree(&mut tx, tree_id, now).await?;
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        for event in events {
+            self.publish(event);
+        }
+        Ok(node_ids)
+    }
+
+    // ========================================================================
+    // Node Reference Counting
+    // ========================================================================
+
+    /// Claim ownership of a node (increment reference count)
+    ///
+    /// Claiming a node scheduled for deletion moves it back to `active`.
+    /// Archived nodes, and nodes below an inactive ancestor, cannot be claimed.
+    pub async fn node_claim(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        owner_id: &str,
+        count: i64,
+    ) -> Result<i64, ArborError> {
+        let now = current_timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        let new_count = claim_node(&mut tx, tree_id, node_id, owner_id, count, now).await?;
+
+        touch_tree(&mut tx, tree_id, now).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.publish(ArborEvent::NodeClaimed {
+            tree_id: *tree_id,
+            node_id: *node_id,
+            owner_id: owner_id.to_string(),
+            new_count,
+        });
+        Ok(new_count)
+    }
+
+    /// Release ownership of a node (decrement reference count)
+    ///
+    /// Every node also holds one implicit reference from its tree, which only
+    /// `node_delete`/`node_prune_subtree` drop. Once the count reaches 0 the
+    /// node is scheduled for deletion.
+    pub async fn node_release(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        owner_id: &str,
+        count: i64,
+    ) -> Result<i64, ArborError> {
+        let now = current_timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        sqlx::query("SELECT id FROM nodes WHERE tree_id = ? AND id = ?")
+            .bind(tree_id.to_string())
+            .bind(node_id.to_string())
+            .fetch_optional(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to fetch node: {}", e))?
+            .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+        // Check current ref count for this owner
+        let owner_ref = sqlx::query(
+            "SELECT count FROM node_refs WHERE node_id = ? AND owner_id = ?",
+        )
+        .bind(node_id.to_string())
+        .bind(owner_id)
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to fetch node ref: {}", e))?
+        .ok_or_else(|| format!("No reference found for owner {}", owner_id))?;
+
+        let current_count: i64 = owner_ref.get("count");
+        if current_count < count {
+            return Err(format!(
+                "Cannot release {} references, owner only has {}",
+                count, current_count
+            )
+            .into());
+        }
+
+        let new_count = current_count - count;
+
+        // Update or delete node_ref
+        if new_count == 0 {
+            sqlx::query("DELETE FROM node_refs WHERE node_id = ? AND owner_id = ?")
+                .bind(node_id.to_string())
+                .bind(owner_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to delete node ref: {}", e))?;
+        } else {
+            sqlx::query(
+                "UPDATE node_refs SET count = ?, claimed_at = ? WHERE node_id = ? AND owner_id = ?",
+            )
+            .bind(new_count)
+            .bind(now)
+            .bind(node_id.to_string())
+            .bind(owner_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to update node ref: {}", e))?;
+        }
+
+        // Update node ref_count
+        sqlx::query("UPDATE nodes SET ref_count = ref_count - ? WHERE id = ?")
+            .bind(count)
+            .bind(node_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to update node ref_count: {}", e))?;
+
+        // Check if ref_count reached 0, schedule for deletion
+        let node_row = sqlx::query("SELECT ref_count FROM nodes WHERE id = ?")
+            .bind(node_id.to_string())
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to fetch node: {}", e))?;
+
+        let ref_count: i64 = node_row.get("ref_count");
+        if ref_count == 0 {
+            sqlx::query(
+                "UPDATE nodes SET state = 'scheduled_delete', scheduled_deletion_at = ? WHERE id = ?",
+            )
+            .bind(now)
+            .bind(node_id.to_string())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to schedule node deletion: {}", e))?;
+        }
+
+        touch_tree(&mut tx, tree_id, now).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.publish(ArborEvent::NodeReleased {
+            tree_id: *tree_id,
+            node_id: *node_id,
+            owner_id: owner_id.to_string(),
+            new_count: ref_count,
+        });
+        if ref_count == 0 {
+            self.publish(ArborEvent::NodeScheduledDeletion {
+                tree_id: *tree_id,
+                node_id: *node_id,
+                scheduled_at: now,
+            });
+        }
+        Ok(ref_count)
+    }
+
+    /// Get reference information for a node in a tree
+    pub async fn node_get_refs(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+    ) -> Result<ResourceRefs, ArborError> {
+        sqlx::query("SELECT id FROM nodes WHERE tree_id = ? AND id = ?")
+            .bind(tree_id.to_string())
+            .bind(node_id.to_string())
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to fetch node: {}", e))?
+            .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+        self.get_node_refs(node_id).await
+    }
+
+    /// Restore a node scheduled for deletion or archived
+    ///
+    /// Moves the node back to `active` and claims one reference for
+    /// `owner_id`. The tree and the parent must be active, so restore a
+    /// released tree first and a pruned branch from the top down. Returns the
+    /// new reference count.
+    pub async fn node_restore(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+        owner_id: &str,
+    ) -> Result<i64, ArborError> {
+        let now = current_timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+        check_tree_active(&mut tx, tree_id).await?;
+
+        let node_row = sqlx::query(
+            "SELECT n.state, n.parent_id, p.state AS parent_state
+             FROM nodes n LEFT JOIN nodes p ON p.id = n.parent_id
+             WHERE n.tree_id = ? AND n.id = ?",
+        )
+        .bind(tree_id.to_string())
+        .bind(node_id.to_string())
+        .fetch_optional(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to fetch node: {}", e))?
+        .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+        let state_str: String = node_row.get("state");
+        if ResourceState::from_str(&state_str) == Some(ResourceState::Active) {
+            return Err(format!("Node is already active: {}", node_id).into());
+        }
+
+        let parent_id: Option<String> = node_row.get("parent_id");
+        let parent_state: Option<String> = node_row.get("parent_state");
+        if let Some(parent_id) = parent_id {
+            let parent_state = parent_state
+                .ok_or_else(|| format!("Parent node {} has been purged, the node cannot be restored", parent_id))?;
+            if ResourceState::from_str(&parent_state) != Some(ResourceState::Active) {
+                return Err(format!("Parent node {} is not active, restore it first", parent_id).into());
+            }
+        }
+
+        sqlx::query(
+            "UPDATE nodes SET state = 'active', scheduled_deletion_at = NULL, archived_at = NULL WHERE id = ?",
+        )
+        .bind(node_id.to_string())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to restore node: {}", e))?;
+
+        let new_count = claim_node(&mut tx, tree_id, node_id, owner_id, 1, now).await?;
+
+        touch_tree(&mut tx, tree_id, now).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.publish(ArborEvent::NodeRestored {
+            tree_id: *tree_id,
+            node_id: *node_id,
+            owner_id: owner_id.to_string(),
+            new_count,
+        });
+        Ok(new_count)
+    }
+
+    /// List nodes scheduled for deletion in a tree
+    pub async fn node_list_scheduled(&self, tree_id: &TreeId) -> Result<Vec<NodeId>, ArborError> {
+        let rows = sqlx::query(
+            "SELECT id FROM nodes WHERE tree_id = ? AND state = 'scheduled_delete'",
+        )
+        .bind(tree_id.to_string())
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to list scheduled nodes: {}", e))?;
+
+        let node_ids: Result<Vec<NodeId>, ArborError> = rows
+            .iter()
+            .map(|row| {
+                let id_str: String = row.get("id");
+                ArborId::parse_str(&id_str)
+                    .map_err(|e| format!("Invalid node ID: {}", e).into())
+            })
+            .collect();
+
+        node_ids
+    }
+
+    // ========================================================================
+    // Node Deletion
+    // ========================================================================
+
+    /// Delete a leaf node (schedule it for deletion)
+    ///
+    /// Drops the tree's implicit reference and moves the node to
+    /// `scheduled_delete`. Fails while other owners still hold claims on it.
+    /// The node stays in its parent's child list until it is purged, so it can
+    /// still be claimed back in the meantime. Nodes with active children must
+    /// be removed with `node_prune_subtree`.
+    ///
+    /// Returns the scheduled deletion timestamp.
+    pub async fn node_delete(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+    ) -> Result<i64, ArborError> {
+        let now = current_timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        check_node_deletable(&mut tx, tree_id, node_id).await?;
+
+        let child_row = sqlx::query(
+            "SELECT COUNT(*) as active_children FROM nodes WHERE parent_id = ? AND state = 'active'",
+        )
+        .bind(node_id.to_string())
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to count node children: {}", e))?;
+
+        let active_children: i64 = child_row.get("active_children");
+        if active_children > 0 {
+            return Err(format!(
+                "Node {} has {} active children, use node_prune_subtree()",
+                node_id, active_children
+            )
+            .into());
+        }
+
+        schedule_node_deletion(&mut tx, tree_id, node_id, now).await?;
+        touch_tree(&mut tx, tree_id, now).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        self.publish(ArborEvent::NodeScheduledDeletion {
+            tree_id: *tree_id,
+            node_id: *node_id,
+            scheduled_at: now,
+        });
+        Ok(now)
+    }
+
+    /// Delete a node and all of its active descendants (schedule them for deletion)
+    ///
+    /// Fails without changes if any node in the subtree is claimed by an owner.
+    /// Returns the scheduled node IDs in breadth-first order, starting with
+    /// `node_id` itself.
+    pub async fn node_prune_subtree(
+        &self,
+        tree_id: &TreeId,
+        node_id: &NodeId,
+    ) -> Result<Vec<NodeId>, ArborError> {
+        let now = current_timestamp();
+        let mut tx = self.pool.begin().await.map_err(|e| e.to_string())?;
+
+        check_node_deletable(&mut tx, tree_id, node_id).await?;
+
+        // Collect the active subtree breadth-first
+        let mut pruned = vec![*node_id];
+        let mut index = 0;
+        while index < pruned.len() {
+            let rows = sqlx::query(
+                "SELECT id FROM nodes WHERE tree_id = ? AND parent_id = ? AND state = 'active'",
+            )
+            .bind(tree_id.to_string())
+            .bind(pruned[index].to_string())
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to fetch children: {}", e))?;
+
+            for row in rows {
+                let id_str: String = row.get("id");
+                pruned.push(
+                    ArborId::parse_str(&id_str)
+                        .map_err(|e| format!("Invalid node ID: {}", e))?,
+                );
+            }
+            index += 1;
+        }
+
+        for id in &pruned {
+            schedule_node_deletion(&mut tx, tree_id, id, now).await?;
+        }
+        touch_tree(&mut tx, tree_id, now).await?;
+
+        tx.commit().await.map_err(|e| e.to_string())?;
+
+        for id in &pruned {
+            self.publish(ArborEvent::NodeScheduledDeletion {
+                tree_id: *tree_id,
+                node_id: *id,
+                scheduled_at: now,
+            });
+        }
+        Ok(pruned)
+    }
+
+    // ========================================================================
+    // Lifecycle Cleanup
+    // ========================================================================
+
+    /// Cleanup task: Archive trees scheduled for deletion (after the scheduled deletion window)
+    pub async fn cleanup_scheduled_trees(&self) -> Result<usize, ArborError> {
+        archive_scheduled_trees(&self.pool, &self.config, &self.events).await
+    }
+
+    /// Cleanup task: Archive nodes scheduled for deletion (after the scheduled deletion window)
+    pub async fn cleanup_scheduled_nodes(&self) -> Result<usize, ArborError> {
+        archive_scheduled_nodes(&self.pool, &self.config, &self.events).await
+    }
+
+    /// Cleanup task: Purge archived trees (after the archive window)
+    ///
+    /// Trees holding pinned nodes (nodes with owner claims) are kept until
+    /// every pin is released.
+    pub async fn purge_archived_trees(&self) -> Result<usize, ArborError> {
+        purge_archived_trees(&self.pool, &self.config, &self.events, &self.paths).await
+    }
+
+    /// Cleanup task: Purge archived nodes (after the archive window)
+    ///
+    /// Removes the node rows and compacts the positions of the remaining
+    /// siblings so `node_children` stays densely ordered. Archived nodes that
+    /// still have children are kept until those children are purged, so the
+    /// paths through them stay intact.
+    pub async fn purge_archived_nodes(&self) -> Result<usize, ArborError> {
+        purge_archived_nodes(&self.pool, &self.config, &self.events, &self.paths).await
+    }
+
+    /// Run every cleanup stage once and record the report
+    ///
+    /// This is what the background worker does on each tick.
+    pub async fn cleanup_run(&self) -> Result<CleanupReport, ArborError> {
+        run_cleanup(&self.pool, &self.config, &self.last_cleanup, &self.events, &self.paths).await
+    }
+
+    /// Get the cleanup configuration and the report of the last run
+    pub fn cleanup_status(&self) -> CleanupStatus {
+        CleanupStatus {
+            auto_cleanup: self.config.auto_cleanup,
+            cleanup_interval: self.config.cleanup_interval,
+            scheduled_deletion_window: self.config.scheduled_deletion_window,
+            archive_window: self.config.archive_window,
+            last_run: self.last_cleanup.lock().unwrap().clone(),
+        }
+    }
+}
+
+/// Background worker: run every cleanup stage each `cleanup_interval` seconds
+async fn cleanup_worker(
+    pool: SqlitePool,
+    config: ArborConfig,
+    last_cleanup: Arc<Mutex<Option<CleanupReport>>>,
+    events: broadcast::Sender<ArborEvent>,
+    paths: PathCache,
+) {
+    let period = Duration::from_secs(config.cleanup_interval.max(1) as u64);
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+        match run_cleanup(&pool, &config, &last_cleanup, &events, &paths).await {
+            Ok(report) => tracing::debug!(?report, "Arbor cleanup finished"),
+            Err(e) => tracing::error!("Arbor cleanup failed: {}", e),
+        }
+    }
+}
+
+/// Run every cleanup stage once, archiving before purging
+async fn run_cleanup(
+    pool: &SqlitePool,
+    config: &ArborConfig,
+    last_cleanup: &Mutex<Option<CleanupReport>>,
+    events: &broadcast::Sender<ArborEvent>,
+    paths: &PathCache,
+) -> Result<CleanupReport, ArborError> {
+    let started_at = current_timestamp();
+
+    let trees_archived = archive_scheduled_trees(pool, config, events).await?;
+    let nodes_archived = archive_scheduled_nodes(pool, config, events).await?;
+    let trees_purged = purge_archived_trees(pool, config, events, paths).await?;
+    let nodes_purged = purge_archived_nodes(pool, config, events, paths).await?;
+
+    let report = CleanupReport {
+        started_at,
+        finished_at: current_timestamp(),
+        trees_archived,
+        nodes_archived,
+        trees_purged,
+        nodes_purged,
+    };
+
+    *last_cleanup.lock().unwrap() = Some(report.clone());
+    Ok(report)
+}
+
+/// Move trees past the scheduled deletion window to `archived`
+async fn archive_scheduled_trees(
+    pool: &SqlitePool,
+    config: &ArborConfig,
+    events: &broadcast::Sender<ArborEvent>,
+) -> Result<usize, ArborError> {
+    let now = current_timestamp();
+    let cutoff = now - config.scheduled_deletion_window;
+
+    let rows = sqlx::query(
+        "UPDATE trees
+         SET state = 'archived', archived_at = ?, updated_at = ?
+         WHERE state = 'scheduled_delete' AND scheduled_deletion_at < ?
+         RETURNING id",
+    )
+    .bind(now)
+    .bind(now)
+    .bind(cutoff)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to archive trees: {}", e))?;
+
+    for row in &rows {
+        let tree_id = parse_id(row, "id")?;
+        let _ = events.send(ArborEvent::TreeArchived { tree_id, archived_at: now });
+    }
+
+    Ok(rows.len())
+}
+
+/// Move nodes past the scheduled deletion window to `archived`
+async fn archive_scheduled_nodes(
+    pool: &SqlitePool,
+    config: &ArborConfig,
+    events: &broadcast::Sender<ArborEvent>,
+) -> Result<usize, ArborError> {
+    let now = current_timestamp();
+    let cutoff = now - config.scheduled_deletion_window;
+
+    let rows = sqlx::query(
+        "UPDATE nodes
+         SET state = 'archived', archived_at = ?
+         WHERE state = 'scheduled_delete' AND scheduled_deletion_at < ?
+         RETURNING id, tree_id",
+    )
+    .bind(now)
+    .bind(cutoff)
+    .fetch_all(pool)
+    .await
+    .map_err(|e| format!("Failed to archive nodes: {}", e))?;
+
+    for row in &rows {
+        let _ = events.send(ArborEvent::NodeArchived {
+            tree_id: parse_id(row, "tree_id")?,
+            node_id: parse_id(row, "id")?,
+            archived_at: now,
+        });
+    }
+
+    Ok(rows.len())
+}
+
+/// Delete archived trees past the archive window, skipping trees with pinned nodes
+async fn purge_archived_trees(
+    pool: &SqlitePool,
+    config: &ArborConfig,
+    events: &broadcast::Sender<ArborEvent>,
+    paths: &PathCache,
+) -> Result<usize, ArborError> {
+    let cutoff = current_timestamp() - config.archive_window;
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+    let rows = sqlx::query(
+        "SELECT id FROM trees
+         WHERE state = 'archived' AND archived_at < ?
+         AND NOT EXISTS (
+             SELECT 1 FROM node_refs JOIN nodes ON nodes.id = node_refs.node_id
+             WHERE nodes.tree_id = trees.id
+         )",
+    )
+    .bind(cutoff)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(|e| format!("Failed to fetch archived trees: {}", e))?;
+
+    for row in &rows {
+        let tree_id: String = row.get("id");
+
+        for statement in [
+            "DELETE FROM node_children WHERE parent_id IN (SELECT id FROM nodes WHERE tree_id = ?)",
+            "DELETE FROM node_resolved WHERE node_id IN (SELECT id FROM nodes WHERE tree_id = ?)",
+            "DELETE FROM node_resolve_failures WHERE node_id IN (SELECT id FROM nodes WHERE tree_id = ?)",
+            "DELETE FROM node_versions WHERE tree_id = ?",
+            "DELETE FROM nodes WHERE tree_id = ?",
+            "DELETE FROM tree_refs WHERE tree_id = ?",
+            "DELETE FROM trees WHERE id = ?",
+        ] {
+            sqlx::query(statement)
+                .bind(&tree_id)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to purge tree: {}", e))?;
+        }
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    for row in &rows {
+        let tree_id = parse_id(row, "id")?;
+        paths.invalidate_tree(&tree_id);
+        let _ = events.send(ArborEvent::TreeDeleted { tree_id });
+    }
+    Ok(rows.len())
+}
+
+/// Delete archived nodes past the archive window and compact their siblings' positions
+async fn purge_archived_nodes(
+    pool: &SqlitePool,
+    config: &ArborConfig,
+    events: &broadcast::Sender<ArborEvent>,
+    paths: &PathCache,
+) -> Result<usize, ArborError> {
+    let cutoff = current_timestamp() - config.archive_window;
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+
+    // Only leaves are purged, so no surviving node loses its parent; each
+    // pass can free the parents of the nodes the previous one removed
+    let mut rows = Vec::new();
+    let mut parents = HashSet::new();
+    loop {
+        let batch = sqlx::query(
+            "SELECT id, tree_id, parent_id FROM nodes
+             WHERE state = 'archived' AND archived_at < ?
+             AND NOT EXISTS (SELECT 1 FROM nodes AS child WHERE child.parent_id = nodes.id)",
+        )
+        .bind(cutoff)
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to fetch archived nodes: {}", e))?;
+        if batch.is_empty() {
+            break;
+        }
+
+        for row in &batch {
+            let node_id: String = row.get("id");
+            let parent_id: Option<String> = row.get("parent_id");
+
+            for statement in [
+                "DELETE FROM node_refs WHERE node_id = ?",
+                "DELETE FROM node_resolved WHERE node_id = ?",
+                "DELETE FROM node_resolve_failures WHERE node_id = ?",
+                "DELETE FROM node_children WHERE child_id = ?",
+                "DELETE FROM nodes WHERE id = ?",
+            ] {
+                sqlx::query(statement)
+                    .bind(&node_id)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| format!("Failed to purge node: {}", e))?;
+            }
+
+            if let Some(parent_id) = parent_id {
+                parents.insert(parent_id);
+            }
+        }
+        rows.extend(batch);
+    }
+
+    for parent_id in &parents {
+        compact_child_positions(&mut tx, parent_id).await?;
+    }
+
+    tx.commit().await.map_err(|e| e.to_string())?;
+
+    for row in &rows {
+        let tree_id = parse_id(row, "tree_id")?;
+        paths.invalidate_tree(&tree_id);
+        let _ = events.send(ArborEvent::NodeDeleted {
+            tree_id,
+            node_id: parse_id(row, "id")?,
+        });
+    }
+    Ok(rows.len())
+}
+
+/// Copy the active subtree under `src_node` of `src` into `dst_tree`
+///
+/// The copy of `src_node` gets `new_root` as its ID and is appended under
+/// `dst_parent`. Returns every created node with its parent, breadth-first.
+async fn copy_subtree(
+    conn: &mut SqliteConnection,
+    src: &Tree,
+    src_node: &NodeId,
+    dst_tree: &TreeId,
+    dst_parent: Option<NodeId>,
+    new_root: NodeId,
+    now: i64,
+) -> Result<Vec<(NodeId, Option<NodeId>)>, ArborError> {
+    let mut created = Vec::new();
+    let mut queue = std::collections::VecDeque::from([(*src_node, dst_parent, new_root)]);
+
+    while let Some((src_id, parent, new_id)) = queue.pop_front() {
+        let Some(node) = src.nodes.get(&src_id) else {
+            continue;
+        };
+
+        insert_node(conn, dst_tree, &new_id, parent, &node.data, node.metadata.as_ref(), now).await?;
+        if let Some(parent_id) = parent {
+            append_child(conn, &parent_id, &new_id).await?;
+        }
+        created.push((new_id, parent));
+
+        for child_id in &node.children {
+            let active = src
+                .nodes
+                .get(child_id)
+                .map_or(false, |c| c.state.as_ref().map_or(true, |s| *s == ResourceState::Active));
+            if active {
+                queue.push_back((*child_id, Some(new_id), NodeId::new()));
+            }
+        }
+    }
+
+    Ok(created)
+}
+
+/// Insert an active node row (does not touch `node_children`)
+async fn insert_node(
+    conn: &mut SqliteConnection,
+    tree_id: &TreeId,
+    node_id: &NodeId,
+    parent: Option<NodeId>,
+    data: &NodeType,
+    metadata: Option<&Value>,
+    now: i64,
+) -> Result<(), ArborError> {
+    let metadata_json = metadata.map(|m| serde_json::to_string(m).unwrap());
+
+    let query = match data {
+        NodeType::Text { content } => sqlx::query(
+            "INSERT INTO nodes (id, tree_id, parent_id, ref_count, state, node_type, content, metadata, created_at)
+             VALUES (?, ?, ?, 1, 'active', 'text', ?, ?, ?)",
+        )
+        .bind(node_id.to_string())
+        .bind(tree_id.to_string())
+        .bind(parent.map(|p| p.to_string()))
+        .bind(content.clone())
+        .bind(metadata_json)
+        .bind(now),
+        NodeType::External { handle } => sqlx::query(
+            "INSERT INTO nodes (id, tree_id, parent_id, ref_count, state, node_type, handle_plugin_id, handle_version, handle_method, handle_meta, metadata, created_at)
+             VALUES (?, ?, ?, 1, 'active', 'external', ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(node_id.to_string())
+        .bind(tree_id.to_string())
+        .bind(parent.map(|p| p.to_string()))
+        .bind(handle.plugin_id.to_string())
+        .bind(handle.version.clone())
+        .bind(handle.method.clone())
+        .bind(serde_json::to_string(&handle.meta).unwrap())
+        .bind(metadata_json)
+        .bind(now),
+    };
+
+    query
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to create node: {}", e))?;
+
+    Ok(())
+}
+
+/// Append a child to the end of a parent's child list
+async fn append_child(
+    conn: &mut SqliteConnection,
+    parent_id: &NodeId,
+    child_id: &NodeId,
+) -> Result<(), ArborError> {
+    let row = sqlx::query(
+        "SELECT COALESCE(MAX(position), -1) + 1 as next_pos FROM node_children WHERE parent_id = ?",
+    )
+    .bind(parent_id.to_string())
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to get next position: {}", e))?;
+
+    let next_pos: i64 = row.get("next_pos");
+
+    sqlx::query("INSERT INTO node_children (parent_id, child_id, position) VALUES (?, ?, ?)")
+        .bind(parent_id.to_string())
+        .bind(child_id.to_string())
+        .bind(next_pos)
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to add child to parent: {}", e))?;
+
+    Ok(())
+}
+
+/// Apply one batch operation; `done` holds the nodes of earlier operations
+async fn apply_batch_op(
+    conn: &mut SqliteConnection,
+    tree_id: &TreeId,
+    op: BatchOp,
+    done: &[NodeId],
+    now: i64,
+) -> Result<(NodeId, ArborEvent), ArborError> {
+    let resolve = |node_ref: BatchNodeRef| -> Result<NodeId, ArborError> {
+        match node_ref {
+            BatchNodeRef::Node(id) => Ok(id),
+            BatchNodeRef::Op { op } => done
+                .get(op)
+                .copied()
+                .ok_or_else(|| format!("Reference to operation {} which has not run yet", op).into()),
+        }
+    };
+
+    match op {
+        BatchOp::CreateText { parent, content, metadata } => {
+            let parent = resolve(parent)?;
+            let data = NodeType::Text { content };
+            let node_id = batch_create(conn, tree_id, parent, &data, metadata.as_ref(), now).await?;
+            Ok((node_id, ArborEvent::NodeCreated { tree_id: *tree_id, node_id, parent: Some(parent) }))
+        }
+        BatchOp::CreateExternal { parent, handle, metadata } => {
+            let parent = resolve(parent)?;
+            let data = NodeType::External { handle };
+            let node_id = batch_create(conn, tree_id, parent, &data, metadata.as_ref(), now).await?;
+            Ok((node_id, ArborEvent::NodeCreated { tree_id: *tree_id, node_id, parent: Some(parent) }))
+        }
+        BatchOp::UpdateMetadata { node, patch } => {
+            let node_id = resolve(node)?;
+            let metadata = patch_node_metadata(conn, tree_id, &node_id, &patch).await?;
+            Ok((node_id, ArborEvent::NodeMetadataUpdated { tree_id: *tree_id, node_id, metadata }))
+        }
+        BatchOp::Claim { node, owner_id, count } => {
+            let node_id = resolve(node)?;
+            let new_count = claim_node(conn, tree_id, &node_id, &owner_id, count, now).await?;
+            Ok((node_id, ArborEvent::NodeClaimed { tree_id: *tree_id, node_id, owner_id, new_count }))
+        }
+    }
+}
+
+/// Create a node under an active parent in the same tree
+async fn batch_create(
+    conn: &mut SqliteConnection,
+    tree_id: &TreeId,
+    parent: NodeId,
+    data: &NodeType,
+    metadata: Option<&Value>,
+    now: i64,
+) -> Result<NodeId, ArborError> {
+    sqlx::query("SELECT 1 FROM nodes WHERE tree_id = ? AND id = ? AND state = 'active'")
+        .bind(tree_id.to_string())
+        .bind(parent.to_string())
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to fetch parent: {}", e))?
+        .ok_or_else(|| format!("Active parent node not found: {}", parent))?;
+
+    let node_id = NodeId::new();
+    insert_node(conn, tree_id, &node_id, Some(parent), data, metadata, now).await?;
+    append_child(conn, &parent, &node_id).await?;
+    Ok(node_id)
+}
+
+/// Add `count` references from `owner_id` to a node, reviving it if it was
+/// scheduled for deletion. Returns the node's new ref_count.
+async fn claim_node(
+    conn: &mut SqliteConnection,
+    tree_id: &TreeId,
+    node_id: &NodeId,
+    owner_id: &str,
+    count: i64,
+    now: i64,
+) -> Result<i64, ArborError> {
+    // Check if node exists and is claimable (active or scheduled_delete)
+    let node_row = sqlx::query("SELECT state FROM nodes WHERE tree_id = ? AND id = ?")
+        .bind(tree_id.to_string())
+        .bind(node_id.to_string())
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to fetch node: {}", e))?
+        .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+    let state_str: String = node_row.get("state");
+    let state = ResourceState::from_str(&state_str).unwrap_or(ResourceState::Active);
+
+    if state == ResourceState::Archived {
+        return Err("Cannot claim archived node".into());
+    }
+
+    // Nodes can only be pinned (or revived) on a fully active branch
+    let inactive_ancestor = sqlx::query(
+        "WITH RECURSIVE ancestors(id) AS (
+             SELECT parent_id FROM nodes WHERE id = ?
+             UNION ALL
+             SELECT n.parent_id FROM nodes n JOIN ancestors a ON n.id = a.id
+             WHERE n.parent_id IS NOT NULL
+         )
+         SELECT n.id FROM ancestors a JOIN nodes n ON n.id = a.id
+         WHERE n.state != 'active'
+         LIMIT 1",
+    )
+    .bind(node_id.to_string())
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to check node ancestry: {}", e))?;
+
+    if let Some(row) = inactive_ancestor {
+        let ancestor_id: String = row.get("id");
+        return Err(format!("Ancestor node {} is not active, restore it first", ancestor_id).into());
+    }
+
+    // If scheduled for deletion, reactivate it
+    if state == ResourceState::ScheduledDelete {
+        sqlx::query(
+            "UPDATE nodes SET state = 'active', scheduled_deletion_at = NULL WHERE id = ?",
+        )
+        .bind(node_id.to_string())
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to reactivate node: {}", e))?;
+    }
+
+    // Update or insert node_ref
+    sqlx::query(
+        "INSERT INTO node_refs (node_id, owner_id, count, claimed_at)
+         VALUES (?, ?, ?, ?)
+         ON CONFLICT(node_id, owner_id) DO UPDATE SET
+            count = count + excluded.count,
+            claimed_at = excluded.claimed_at",
+    )
+    .bind(node_id.to_string())
+    .bind(owner_id)
+    .bind(count)
+    .bind(now)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to claim node: {}", e))?;
+
+    // Update node ref_count
+    sqlx::query("UPDATE nodes SET ref_count = ref_count + ? WHERE id = ?")
+        .bind(count)
+        .bind(node_id.to_string())
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to update node ref_count: {}", e))?;
+
+    let new_count_row = sqlx::query("SELECT ref_count FROM nodes WHERE id = ?")
+        .bind(node_id.to_string())
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to fetch new ref_count: {}", e))?;
+
+    Ok(new_count_row.get("ref_count"))
+}
+
+/// Merge a JSON merge patch into a node's metadata, returning the result
+async fn patch_node_metadata(
+    conn: &mut SqliteConnection,
+    tree_id: &TreeId,
+    node_id: &NodeId,
+    patch: &Value,
+) -> Result<Value, ArborError> {
+    let patch_json = serde_json::to_string(patch)
+        .map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+
+    let row = sqlx::query(
+        "UPDATE nodes SET metadata = json_patch(COALESCE(metadata, '{}'), ?)
+         WHERE tree_id = ? AND id = ? AND state = 'active'
+         RETURNING metadata",
+    )
+    .bind(patch_json)
+    .bind(tree_id.to_string())
+    .bind(node_id.to_string())
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to update node metadata: {}", e))?
+    .ok_or_else(|| format!("Active node not found: {}", node_id))?;
+
+    let metadata_json: Option<String> = row.get("metadata");
+    Ok(metadata_json
+        .and_then(|s| serde_json::from_str(&s).ok())
+        .unwrap_or(Value::Null))
+}
+
+/// Check that a tree exists and is active
+async fn check_tree_active(conn: &mut SqliteConnection, tree_id: &TreeId) -> Result<(), ArborError> {
+    let row = sqlx::query("SELECT state FROM trees WHERE id = ?")
+        .bind(tree_id.to_string())
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to fetch tree: {}", e))?
+        .ok_or_else(|| format!("Tree not found: {}", tree_id))?;
+
+    let state_str: String = row.get("state");
+    if ResourceState::from_str(&state_str) != Some(ResourceState::Active) {
+        return Err(format!("Tree is not active: {}", tree_id).into());
+    }
+
+    Ok(())
+}
+
+/// Check that a node exists in the tree, is active, and is not the root
+async fn check_node_deletable(
+    conn: &mut SqliteConnection,
+    tree_id: &TreeId,
+    node_id: &NodeId,
+) -> Result<(), ArborError> {
+    let row = sqlx::query("SELECT parent_id, state FROM nodes WHERE tree_id = ? AND id = ?")
+        .bind(tree_id.to_string())
+        .bind(node_id.to_string())
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to fetch node: {}", e))?
+        .ok_or_else(|| format!("Node not found: {}", node_id))?;
+
+    let parent_id: Option<String> = row.get("parent_id");
+    if parent_id.is_none() {
+        return Err("Cannot delete the root node, release the tree instead".into());
+    }
+
+    let state_str: String = row.get("state");
+    if ResourceState::from_str(&state_str) != Some(ResourceState::Active) {
+        return Err(format!("Node is not active: {}", node_id).into());
+    }
+
+    Ok(())
+}
+
+/// Drop the tree's implicit reference to a node and move it to `scheduled_delete`
+///
+/// Fails while any owner still holds a claim on the node: pins outlive the
+/// tree's own reference and must be released by their owners first.
+async fn schedule_node_deletion(
+    conn: &mut SqliteConnection,
+    tree_id: &TreeId,
+    node_id: &NodeId,
+    now: i64,
+) -> Result<(), ArborError> {
+    let owners: Vec<String> = sqlx::query("SELECT owner_id FROM node_refs WHERE node_id = ? ORDER BY owner_id")
+        .bind(node_id.to_string())
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to fetch node refs: {}", e))?
+        .iter()
+        .map(|row| row.get("owner_id"))
+        .collect();
+    if !owners.is_empty() {
+        return Err(format!(
+            "Node {} is still claimed by {}, release those references first",
+            node_id,
+            owners.join(", ")
+        )
+        .into());
+    }
+
+    sqlx::query(
+        "UPDATE nodes SET ref_count = 0, state = 'scheduled_delete', scheduled_deletion_at = ?
+         WHERE tree_id = ? AND id = ?",
+    )
+    .bind(now)
+    .bind(tree_id.to_string())
+    .bind(node_id.to_string())
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to schedule node deletion: {}", e))?;
+
+    Ok(())
+}
+
+/// Renumber a parent's children to consecutive positions, keeping their order
+async fn compact_child_positions(
+    conn: &mut SqliteConnection,
+    parent_id: &str,
+) -> Result<(), ArborError> {
+    sqlx::query(
+        "UPDATE node_children
+         SET position = (
+             SELECT COUNT(*) FROM node_children AS sibling
+             WHERE sibling.parent_id = node_children.parent_id
+             AND sibling.position < node_children.position
+         )
+         WHERE parent_id = ?",
+    )
+    .bind(parent_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(|e| format!("Failed to compact child positions: {}", e))?;
+
+    Ok(())
+}
+
+/// Bump a tree's updated_at timestamp
+async fn touch_tree(
+    conn: &mut SqliteConnection,
+    tree_id: &TreeId,
+    now: i64,
+) -> Result<(), ArborError> {
+    sqlx::query("UPDATE trees SET updated_at = ? WHERE id = ?")
+        .bind(now)
+        .bind(tree_id.to_string())
+        .execute(&mut *conn)
+        .await
+        .map_err(|e| format!("Failed to update tree: {}", e))?;
+
+    Ok(())
+}
+
+/// Append one `AND` condition per filter key, matching `metadata` values by
+/// equality. Null values are rejected, since `json_extract` cannot tell a
+/// null from a missing key. Returns the values to bind, in order.
+fn push_metadata_conditions(
+    sql: &mut String,
+    conditions: &serde_json::Map<String, Value>,
+) -> Result<Vec<String>, ArborError> {
+    let mut binds = Vec::with_capacity(conditions.len() * 2);
+    for (key, value) in conditions {
+        if key.contains('"') {
+            return Err(format!("Invalid metadata key: {}", key).into());
+        }
+        if value.is_null() {
+            return Err(format!("Cannot filter on a null metadata value: {}", key).into());
+        }
+        // Compare both sides as SQLite JSON values so strings, numbers,
+        // booleans and nested values all match by equality
+        sql.push_str(" AND json_extract(metadata, ?) IS json_extract(?, '$')");
+        binds.push(format!("$.\"{}\"", key));
+        binds.push(value.to_string());
+    }
+    Ok(binds)
+}
+
+/// Turn free text into an FTS5 query that ANDs every term as a quoted phrase
+///
+/// Quoting keeps user input like `what?` or `foo-bar` from being parsed as
+/// FTS5 query syntax.
+fn fts_phrase_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Parse a `tree_list_summaries` cursor (`<updated_at>:<tree id>`)
+fn parse_tree_cursor(cursor: &str) -> Result<(i64, TreeId), ArborError> {
+    let invalid = || ArborError::from(format!("Invalid cursor: {}", cursor));
+    let (updated_at, id) = cursor.split_once(':').ok_or_else(invalid)?;
+    let updated_at = updated_at.parse().map_err(|_| invalid())?;
+    let id = ArborId::parse_str(id).map_err(|_| invalid())?;
+    Ok((updated_at, id))
+}
+
+/// Build a node from a row selected with `NODE_COLUMNS` and its already
+/// loaded children and references
+fn build_node(row: &SqliteRow, children: Vec<NodeId>, refs: ResourceRefs) -> Result<Node, ArborError> {
+    let node_id_str: String = row.get("id");
+    let node_id = ArborId::parse_str(&node_id_str)
+        .map_err(|e| format!("Invalid node ID: {}", e))?;
+
+    let parent_id_str: Option<String> = row.get("parent_id");
+    let parent_id = parent_id_str
+        .map(|s| ArborId::parse_str(&s).map_err(|e| format!("Invalid parent ID: {}", e)))
+        .transpose()?;
+
+    let node_type_str: String = row.get("node_type");
+    let data = match node_type_str.as_str() {
+        "text" => {
+            let content: String = row.get("content");
+            NodeType::Text { content }
+        }
+        "external" => {
+            let plugin_id_str: String = row.get("handle_plugin_id");
+            let plugin_id = Uuid::parse_str(&plugin_id_str)
+                .map_err(|e| format!("Invalid handle plugin_id: {}", e))?;
+            let version: String = row.get("handle_version");
+            let method: String = row.get("handle_method");
+            let meta_json: Option<String> = row.get("handle_meta");
+            let meta: Vec<String> = meta_json
+                .and_then(|s| serde_json::from_str(&s).ok())
+                .unwrap_or_default();
+
+            NodeType::External {
+                handle: Handle::new(plugin_id, version, method)
+                    .with_meta(meta),
+            }
+        }
+        _ => return Err(format!("Unknown node type: {}", node_type_str).into()),
+    };
+
+    let state_str: String = row.get("state");
+    let state = ResourceState::from_str(&state_str).unwrap_or(ResourceState::Active);
+
+    let metadata_json: Option<String> = row.get("metadata");
+    let metadata = metadata_json.and_then(|s| serde_json::from_str(&s).ok());
+
+    Ok(Node {
+        id: node_id,
+        parent: parent_id,
+        children,
+        data,
+        state: Some(state),
+        refs: Some(refs),
+        scheduled_deletion_at: row.get("scheduled_deletion_at"),
+        archived_at: row.get("archived_at"),
+        created_at: row.get("created_at"),
+        metadata,
+    })
+}
+
+/// Parse an ID column from a row
+fn parse_id(row: &SqliteRow, column: &str) -> Result<ArborId, ArborError> {
+    let id_str: String = row.get(column);
+    ArborId::parse_str(&id_str).map_err(|e| format!("Invalid ID in {}: {}", column, e).into())
 }
 
 /// Get current Unix timestamp in seconds
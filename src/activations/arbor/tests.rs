@@ -0,0 +1,1090 @@
+//! Tests for Arbor storage
+//!
+//! Exercises ArborStorage directly against temp databases.
+
+use super::*;
+use std::collections::HashSet;
+use tempfile::{tempdir, TempDir};
+
+/// Create a test storage instance with a temp database
+async fn create_test_storage(config: ArborConfig) -> (ArborStorage, TempDir) {
+    let dir = tempdir().unwrap();
+    let config = ArborConfig {
+        db_path: dir.path().join("test_arbor.db"),
+        auto_cleanup: false,
+        ..config
+    };
+    let storage = ArborStorage::new(config).await.unwrap();
+    (storage, dir)
+}
+
+// ============================================================================
+// Node deletion
+// ============================================================================
+
+#[tokio::test]
+async fn test_node_delete_requires_leaf() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    let a = storage.node_create_text(&tree_id, Some(root), "a".into(), None).await.unwrap();
+    let b = storage.node_create_text(&tree_id, Some(a), "b".into(), None).await.unwrap();
+
+    assert!(storage.node_delete(&tree_id, &root).await.is_err(), "root cannot be deleted");
+    assert!(storage.node_delete(&tree_id, &a).await.is_err(), "a has an active child");
+
+    storage.node_delete(&tree_id, &b).await.unwrap();
+    let node = storage.node_get(&tree_id, &b).await.unwrap();
+    assert_eq!(node.state, Some(ResourceState::ScheduledDelete));
+
+    // Once b is gone, a is a leaf again
+    assert_eq!(storage.context_list_leaves(&tree_id).await.unwrap(), vec![a]);
+    storage.node_delete(&tree_id, &a).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_node_prune_subtree_lifecycle() {
+    // Negative windows make everything immediately eligible for the next stage
+    let (storage, _dir) = create_test_storage(ArborConfig {
+        scheduled_deletion_window: -1,
+        archive_window: -1,
+        ..Default::default()
+    })
+    .await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    let first = storage.node_create_text(&tree_id, Some(root), "first".into(), None).await.unwrap();
+    let dead_end = storage.node_create_text(&tree_id, Some(root), "dead end".into(), None).await.unwrap();
+    let nested = storage.node_create_text(&tree_id, Some(dead_end), "nested".into(), None).await.unwrap();
+    let last = storage.node_create_text(&tree_id, Some(root), "last".into(), None).await.unwrap();
+
+    let pruned = storage.node_prune_subtree(&tree_id, &dead_end).await.unwrap();
+    assert_eq!(pruned, vec![dead_end, nested]);
+
+    assert_eq!(storage.cleanup_scheduled_nodes().await.unwrap(), 2);
+    assert_eq!(
+        storage.node_get(&tree_id, &nested).await.unwrap().state,
+        Some(ResourceState::Archived)
+    );
+
+    assert_eq!(storage.purge_archived_nodes().await.unwrap(), 2);
+    let tree = storage.tree_get(&tree_id).await.unwrap();
+    assert!(!tree.nodes.contains_key(&dead_end));
+    assert!(!tree.nodes.contains_key(&nested));
+    assert_eq!(tree.nodes[&root].children, vec![first, last]);
+
+    // New children still append after the surviving siblings
+    let appended = storage.node_create_text(&tree_id, Some(root), "appended".into(), None).await.unwrap();
+    let tree = storage.tree_get(&tree_id).await.unwrap();
+    assert_eq!(tree.nodes[&root].children, vec![first, last, appended]);
+}
+
+#[tokio::test]
+async fn test_restore_trees_and_nodes() {
+    let (storage, _dir) = create_test_storage(ArborConfig {
+        scheduled_deletion_window: -1,
+        ..Default::default()
+    })
+    .await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+    let branch = storage.node_create_text(&tree_id, Some(root), "branch".into(), None).await.unwrap();
+    let leaf = storage.node_create_text(&tree_id, Some(branch), "leaf".into(), None).await.unwrap();
+
+    // Released by the wrong script, then archived by cleanup
+    storage.tree_release(&tree_id, "test", 1).await.unwrap();
+    assert_eq!(storage.cleanup_scheduled_trees().await.unwrap(), 1);
+    assert!(storage.tree_get(&tree_id).await.is_err());
+    assert!(storage.tree_claim(&tree_id, "test", 1).await.is_err());
+    assert_eq!(
+        storage.tree_get_archived(&tree_id).await.unwrap().state,
+        Some(ResourceState::Archived)
+    );
+
+    assert_eq!(storage.tree_restore(&tree_id, "rescuer").await.unwrap(), 1);
+    let tree = storage.tree_get(&tree_id).await.unwrap();
+    assert_eq!(tree.state, Some(ResourceState::Active));
+    assert_eq!(tree.archived_at, None);
+    assert_eq!(tree.refs.unwrap().owners.get("rescuer"), Some(&1));
+    assert!(storage.tree_restore(&tree_id, "rescuer").await.is_err(), "already active");
+
+    // A pruned branch comes back from the top down
+    storage.node_prune_subtree(&tree_id, &branch).await.unwrap();
+    assert_eq!(storage.cleanup_scheduled_nodes().await.unwrap(), 2);
+    assert!(storage.node_restore(&tree_id, &leaf, "rescuer").await.is_err(), "parent is archived");
+
+    assert_eq!(storage.node_restore(&tree_id, &branch, "rescuer").await.unwrap(), 1);
+    assert_eq!(storage.node_restore(&tree_id, &leaf, "rescuer").await.unwrap(), 1);
+    assert!(storage.node_restore(&tree_id, &leaf, "rescuer").await.is_err(), "already active");
+
+    let node = storage.node_get(&tree_id, &leaf).await.unwrap();
+    assert_eq!(node.state, Some(ResourceState::Active));
+    assert_eq!(node.archived_at, None);
+    assert_eq!(storage.context_list_leaves(&tree_id).await.unwrap(), vec![leaf]);
+
+    // Nodes in a released tree wait for the tree to come back
+    storage.node_release(&tree_id, &leaf, "rescuer", 1).await.unwrap();
+    storage.tree_release(&tree_id, "rescuer", 1).await.unwrap();
+    assert!(storage.node_restore(&tree_id, &leaf, "rescuer").await.is_err(), "tree is released");
+    storage.tree_restore(&tree_id, "rescuer").await.unwrap();
+    assert_eq!(storage.node_restore(&tree_id, &leaf, "rescuer").await.unwrap(), 1);
+}
+
+// ============================================================================
+// Copy-on-write updates
+// ============================================================================
+
+#[tokio::test]
+async fn test_node_update_reparents_and_records_lineage() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    let sibling = storage.node_create_text(&tree_id, Some(root), "sibling".into(), None).await.unwrap();
+    let prompt = storage.node_create_text(&tree_id, Some(root), "teh prompt".into(), None).await.unwrap();
+    let reply = storage.node_create_text(&tree_id, Some(prompt), "reply".into(), None).await.unwrap();
+    storage.node_claim(&tree_id, &prompt, "citations", 1).await.unwrap();
+
+    let fixed = storage
+        .node_update(&tree_id, &prompt, Some("the prompt".into()), None, None)
+        .await
+        .unwrap();
+    let fixed_again = storage
+        .node_update(&tree_id, &fixed, Some("The prompt".into()), None, None)
+        .await
+        .unwrap();
+
+    let tree = storage.tree_get(&tree_id).await.unwrap();
+    assert_eq!(tree.nodes[&root].children, vec![sibling, fixed_again]);
+    assert_eq!(tree.nodes[&fixed_again].children, vec![reply]);
+    assert_eq!(
+        tree.nodes[&fixed_again].data,
+        NodeType::Text { content: "The prompt".into() }
+    );
+    assert_eq!(tree.nodes[&prompt].state, Some(ResourceState::ScheduledDelete));
+
+    // Paths through the old position now run through the new version
+    assert_eq!(
+        storage.node_get_path(&tree_id, &reply).await.unwrap(),
+        vec![root, fixed_again, reply]
+    );
+
+    let history = storage.node_history(&tree_id, &fixed).await.unwrap();
+    assert_eq!(history, vec![prompt, fixed, fixed_again]);
+
+    // Claims move with the content; holders of old IDs can follow the lineage
+    let refs = storage.node_get_refs(&tree_id, &fixed_again).await.unwrap();
+    assert_eq!(refs.owners.get("citations"), Some(&1));
+    assert!(storage.node_get_refs(&tree_id, &prompt).await.unwrap().owners.is_empty());
+    assert_eq!(storage.node_current_version(&tree_id, &prompt).await.unwrap(), fixed_again);
+    assert_eq!(storage.node_current_version(&tree_id, &reply).await.unwrap(), reply);
+
+    // Only active nodes can be updated
+    assert!(storage.node_update(&tree_id, &prompt, Some("stale".into()), None, None).await.is_err());
+}
+
+// ============================================================================
+// Node reference counting
+// ============================================================================
+
+#[tokio::test]
+async fn test_node_claim_release_lifecycle() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+    let cited = storage.node_create_text(&tree_id, Some(root), "cited".into(), None).await.unwrap();
+
+    // Implicit tree reference plus one pin
+    assert_eq!(storage.node_claim(&tree_id, &cited, "citations", 1).await.unwrap(), 2);
+    let refs = storage.node_get_refs(&tree_id, &cited).await.unwrap();
+    assert_eq!(refs.owners.get("citations"), Some(&1));
+
+    // Pins outlive the tree's reference: deleting (or pruning above) a pinned
+    // node is refused until its owners release it
+    assert!(storage.node_delete(&tree_id, &cited).await.is_err());
+    let parent = storage.node_create_text(&tree_id, Some(root), "parent".into(), None).await.unwrap();
+    let pinned_child = storage.node_create_text(&tree_id, Some(parent), "child".into(), None).await.unwrap();
+    storage.node_claim(&tree_id, &pinned_child, "citations", 1).await.unwrap();
+    assert!(storage.node_prune_subtree(&tree_id, &parent).await.is_err());
+    assert_eq!(storage.node_get(&tree_id, &parent).await.unwrap().state, Some(ResourceState::Active));
+
+    assert_eq!(storage.node_release(&tree_id, &cited, "citations", 1).await.unwrap(), 1);
+    storage.node_delete(&tree_id, &cited).await.unwrap();
+    assert_eq!(storage.node_list_scheduled(&tree_id).await.unwrap(), vec![cited]);
+
+    // A fresh claim brings it back
+    assert_eq!(storage.node_claim(&tree_id, &cited, "citations", 1).await.unwrap(), 1);
+    assert!(storage.node_list_scheduled(&tree_id).await.unwrap().is_empty());
+
+    // Releasing the last reference schedules it again
+    assert!(storage.node_release(&tree_id, &cited, "someone-else", 1).await.is_err());
+    assert_eq!(storage.node_release(&tree_id, &cited, "citations", 1).await.unwrap(), 0);
+    assert_eq!(
+        storage.node_get(&tree_id, &cited).await.unwrap().state,
+        Some(ResourceState::ScheduledDelete)
+    );
+}
+
+#[tokio::test]
+async fn test_released_node_keeps_its_active_children_reachable() {
+    let (storage, _dir) = create_test_storage(ArborConfig {
+        scheduled_deletion_window: -1,
+        archive_window: -1,
+        ..Default::default()
+    })
+    .await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    // A deleted node revived by a claim, which then gains a child
+    let revived = storage.node_create_text(&tree_id, Some(root), "revived".into(), None).await.unwrap();
+    storage.node_delete(&tree_id, &revived).await.unwrap();
+    storage.node_claim(&tree_id, &revived, "citations", 1).await.unwrap();
+    let child = storage.node_create_text(&tree_id, Some(revived), "child".into(), None).await.unwrap();
+
+    // Releasing the last reference schedules it despite the child...
+    assert_eq!(storage.node_release(&tree_id, &revived, "citations", 1).await.unwrap(), 0);
+    // ...and nothing below it can be pinned until it is restored
+    assert!(storage.node_claim(&tree_id, &child, "citations", 1).await.is_err());
+
+    // Cleanup archives it but keeps the row while the child still hangs off it
+    let report = storage.cleanup_run().await.unwrap();
+    assert_eq!(report.nodes_purged, 0);
+    assert_eq!(storage.node_get(&tree_id, &revived).await.unwrap().state, Some(ResourceState::Archived));
+    assert_eq!(storage.node_get_path(&tree_id, &child).await.unwrap(), vec![root, revived, child]);
+    let context: Vec<NodeId> = storage
+        .context_get_path(&tree_id, &child)
+        .await
+        .unwrap()
+        .iter()
+        .map(|n| n.id)
+        .collect();
+    assert_eq!(context, vec![root, revived, child]);
+
+    // Once the child goes too, both are purged in the same run
+    storage.node_delete(&tree_id, &child).await.unwrap();
+    let report = storage.cleanup_run().await.unwrap();
+    assert_eq!(report.nodes_purged, 2);
+    assert!(storage.node_get(&tree_id, &revived).await.is_err());
+    assert_eq!(storage.node_get(&tree_id, &root).await.unwrap().children, Vec::<NodeId>::new());
+}
+
+// ============================================================================
+// Lifecycle cleanup
+// ============================================================================
+
+#[tokio::test]
+async fn test_cleanup_run_purges_released_trees_but_keeps_pins() {
+    let (storage, _dir) = create_test_storage(ArborConfig {
+        scheduled_deletion_window: -1,
+        archive_window: -1,
+        ..Default::default()
+    })
+    .await;
+
+    let released = storage.tree_create(None, "test").await.unwrap();
+    let pinned = storage.tree_create(None, "test").await.unwrap();
+    let pinned_root = storage.tree_get(&pinned).await.unwrap().root;
+    let cited = storage.node_create_text(&pinned, Some(pinned_root), "cited".into(), None).await.unwrap();
+    storage.node_claim(&pinned, &cited, "citations", 1).await.unwrap();
+
+    storage.tree_release(&released, "test", 1).await.unwrap();
+    storage.tree_release(&pinned, "test", 1).await.unwrap();
+
+    let report = storage.cleanup_run().await.unwrap();
+    assert_eq!(report.trees_archived, 2);
+    assert_eq!(report.trees_purged, 1);
+    assert_eq!(storage.cleanup_status().last_run, Some(report));
+
+    assert!(storage.tree_get_archived(&released).await.is_err());
+    let kept = storage.tree_get_archived(&pinned).await.unwrap();
+    assert!(kept.nodes.contains_key(&cited));
+}
+
+// ============================================================================
+// Export / import
+// ============================================================================
+
+#[tokio::test]
+async fn test_tree_export_import_roundtrip() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage
+        .tree_create(Some(serde_json::json!({"name": "migration"})), "test")
+        .await
+        .unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+    let a = storage.node_create_text(&tree_id, Some(root), "a".into(), None).await.unwrap();
+    storage.node_create_text(&tree_id, Some(a), "a1".into(), None).await.unwrap();
+    storage.node_create_text(&tree_id, Some(a), "a2".into(), None).await.unwrap();
+    let dropped = storage.node_create_text(&tree_id, Some(root), "dropped".into(), None).await.unwrap();
+    storage.node_delete(&tree_id, &dropped).await.unwrap();
+
+    let bundle = storage.tree_export(&tree_id).await.unwrap();
+    assert_eq!(bundle.nodes.len(), 4, "scheduled nodes are not exported");
+
+    for format in [BundleFormat::Json, BundleFormat::Jsonl] {
+        let parsed = TreeBundle::parse(&bundle.serialize(format).unwrap()).unwrap();
+        assert_eq!(parsed, bundle);
+
+        let imported_id = storage.tree_import(&parsed, "importer", false).await.unwrap();
+        assert_ne!(imported_id, tree_id);
+
+        let imported = storage.tree_get(&imported_id).await.unwrap();
+        assert_eq!(imported.metadata, Some(serde_json::json!({"name": "migration"})));
+        assert_eq!(imported.nodes.len(), 4);
+        assert_eq!(
+            imported.render(),
+            "└── \n    └── a\n        ├── a1\n        └── a2\n"
+        );
+    }
+}
+
+// ============================================================================
+// Change feed
+// ============================================================================
+
+#[tokio::test]
+async fn test_change_feed_publishes_writes() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    let mut events = storage.subscribe();
+    let node_id = storage.node_create_text(&tree_id, Some(root), "hi".into(), None).await.unwrap();
+    storage.tree_claim(&tree_id, "watcher", 1).await.unwrap();
+
+    match events.recv().await.unwrap() {
+        ArborEvent::NodeCreated { tree_id: t, node_id: n, parent } => {
+            assert_eq!((t, n, parent), (tree_id, node_id, Some(root)));
+        }
+        other => panic!("Expected NodeCreated, got {:?}", other),
+    }
+    match events.recv().await.unwrap() {
+        ArborEvent::TreeClaimed { owner_id, new_count, .. } => {
+            assert_eq!((owner_id.as_str(), new_count), ("watcher", 2));
+        }
+        other => panic!("Expected TreeClaimed, got {:?}", other),
+    }
+}
+
+// ============================================================================
+// Search
+// ============================================================================
+
+#[tokio::test]
+async fn test_search_ranks_content_and_metadata() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+
+    let migration = storage
+        .tree_create(Some(serde_json::json!({"name": "schema work"})), "test")
+        .await
+        .unwrap();
+    let root = storage.tree_get(&migration).await.unwrap().root;
+    let question = storage
+        .node_create_text(&migration, Some(root), "How should we run the migration?".into(), None)
+        .await
+        .unwrap();
+    let answer = storage
+        .node_create_text(&migration, Some(question), "Run the migration in a transaction".into(), None)
+        .await
+        .unwrap();
+
+    let other = storage.tree_create(None, "test").await.unwrap();
+    let other_root = storage.tree_get(&other).await.unwrap().root;
+    let tagged = storage
+        .node_create_text(&other, Some(other_root), "unrelated".into(), Some(serde_json::json!({"tool": "migration"})))
+        .await
+        .unwrap();
+
+    let hits = storage.search("migration", None, false, 20).await.unwrap();
+    let found: Vec<NodeId> = hits.iter().map(|h| h.node_id).collect();
+    assert_eq!(found.len(), 3);
+    assert!(found.contains(&question) && found.contains(&answer) && found.contains(&tagged));
+    // Scores are normalised per source: best match first at 1.0
+    assert_eq!(hits[0].score, 1.0);
+    assert!(hits.iter().all(|h| h.score > 0.0 && h.score <= 1.0));
+
+    let answer_hit = hits.iter().find(|h| h.node_id == answer).unwrap();
+    assert_eq!(answer_hit.path, vec![root, question, answer]);
+    assert!(answer_hit.snippet.contains("[migration]"));
+
+    // Terms are ANDed, punctuation is not query syntax, and tree_id scopes results
+    let hits = storage.search("migration transaction?", None, false, 20).await.unwrap();
+    assert_eq!(hits.iter().map(|h| h.node_id).collect::<Vec<_>>(), vec![answer]);
+    let hits = storage.search("run migration", Some(migration), false, 20).await.unwrap();
+    assert_eq!(hits.len(), 2);
+
+    // Tree metadata hits land on the root; deleted nodes drop out
+    let hits = storage.search("schema", None, false, 20).await.unwrap();
+    assert_eq!((hits[0].source, hits[0].node_id), (SearchSource::Tree, root));
+    storage.node_delete(&other, &tagged).await.unwrap();
+    assert_eq!(storage.search("migration", None, false, 20).await.unwrap().len(), 2);
+}
+
+#[tokio::test]
+async fn test_unresolved_handles_are_batched_and_back_off() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    let mut handles = Vec::new();
+    for i in 0..3 {
+        let handle = Handle::new(uuid::Uuid::new_v4(), "1.0.0".to_string(), "chat".to_string())
+            .with_meta(vec![format!("msg-{}", i)]);
+        handles.push(storage.node_create_external(&tree_id, Some(root), handle, None).await.unwrap());
+    }
+
+    let batch = storage.search_unresolved_handles(Some(tree_id), 2).await.unwrap();
+    assert_eq!(batch.len(), 2);
+    assert_eq!(storage.search_unresolved_count(Some(tree_id)).await.unwrap(), 3);
+
+    // Cached and failed handles both drop out of the next batch
+    storage.node_set_resolved(&handles[0], "resolved text").await.unwrap();
+    storage.node_set_resolve_failed(&handles[1], "[unresolved: chat - no such plugin]").await.unwrap();
+    let batch = storage.search_unresolved_handles(Some(tree_id), 10).await.unwrap();
+    assert_eq!(batch.into_iter().map(|(id, _)| id).collect::<Vec<_>>(), vec![handles[2]]);
+    assert_eq!(storage.search_unresolved_count(None).await.unwrap(), 1);
+
+    // A later success clears the failure
+    storage.node_set_resolved(&handles[1], "resolved later").await.unwrap();
+    assert_eq!(storage.search("later", Some(tree_id), true, 20).await.unwrap().len(), 1);
+}
+
+// ============================================================================
+// Copy / clone
+// ============================================================================
+
+#[tokio::test]
+async fn test_copy_subtree_and_clone_prefix() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let src = storage.tree_create(Some(serde_json::json!({"name": "src"})), "test").await.unwrap();
+    let root = storage.tree_get(&src).await.unwrap().root;
+    let setup = storage.node_create_text(&src, Some(root), "setup".into(), None).await.unwrap();
+    let useful = storage.node_create_text(&src, Some(setup), "useful".into(), None).await.unwrap();
+    storage.node_create_text(&src, Some(useful), "failed attempt".into(), None).await.unwrap();
+    storage.node_claim(&src, &useful, "pin", 1).await.unwrap();
+
+    // Clone only the useful prefix
+    let (cloned, head) = storage.tree_clone(&src, Some(useful), "cloner").await.unwrap();
+    let tree = storage.tree_get(&cloned).await.unwrap();
+    assert_eq!(tree.metadata, Some(serde_json::json!({"name": "src"})));
+    assert_eq!(tree.nodes.len(), 3);
+    assert_eq!(tree.nodes[&head].data, NodeType::Text { content: "useful".into() });
+    assert!(tree.nodes[&head].children.is_empty());
+    assert_eq!(storage.node_get_path(&cloned, &head).await.unwrap().len(), 3);
+
+    // Copy the subtree under `setup` into the clone's head; pins stay behind
+    let copy = storage.node_copy_subtree(&src, &setup, &cloned, &head).await.unwrap();
+    let tree = storage.tree_get(&cloned).await.unwrap();
+    assert_eq!(tree.nodes.len(), 6);
+    assert_eq!(tree.nodes[&head].children, vec![copy]);
+    assert!(tree.nodes.values().all(|n| n.refs.as_ref().unwrap().owners.is_empty()));
+    assert_eq!(
+        tree.render(),
+        "└── \n    └── setup\n        └── useful\n            └── setup\n                └── useful\n                    └── failed attempt\n"
+    );
+
+    // A released tree accepts no new copies
+    storage.tree_release(&cloned, "cloner", 1).await.unwrap();
+    assert!(storage.node_copy_subtree(&src, &setup, &cloned, &head).await.is_err());
+}
+
+#[tokio::test]
+async fn test_copies_hold_their_own_references() {
+    let (storage, _dir) = create_test_storage(ArborConfig {
+        scheduled_deletion_window: -1,
+        archive_window: -1,
+        ..Default::default()
+    })
+    .await;
+    let src = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&src).await.unwrap().root;
+    let handle = Handle::new(uuid::Uuid::new_v4(), "1.0.0".to_string(), "chat".to_string())
+        .with_meta(vec!["msg-1".to_string()]);
+    let cited = storage.node_create_external(&src, Some(root), handle.clone(), None).await.unwrap();
+    storage.node_claim(&src, &cited, "pin", 1).await.unwrap();
+    let scrapped = storage.node_create_text(&src, Some(cited), "scrapped".into(), None).await.unwrap();
+    storage.node_delete(&src, &scrapped).await.unwrap();
+
+    // Only active paths can be cloned
+    assert!(storage.tree_clone(&src, Some(scrapped), "cloner").await.is_err());
+    let (cloned, head) = storage.tree_clone(&src, Some(cited), "cloner").await.unwrap();
+    let copy = storage.node_get(&cloned, &head).await.unwrap();
+    assert_eq!(copy.data, NodeType::External { handle: handle.clone() });
+    assert!(copy.refs.unwrap().owners.is_empty());
+    assert!(copy.created_at >= storage.node_get(&src, &cited).await.unwrap().created_at);
+
+    // The copy holds its own implicit reference: deleting it leaves the source
+    // (and its pin) alone, and purging the source tree leaves the copy intact
+    storage.node_delete(&cloned, &head).await.unwrap();
+    assert_eq!(storage.node_get(&src, &cited).await.unwrap().state, Some(ResourceState::Active));
+    assert_eq!(storage.node_get_refs(&src, &cited).await.unwrap().owners.get("pin"), Some(&1));
+    storage.node_claim(&cloned, &head, "cloner", 1).await.unwrap();
+
+    storage.node_release(&src, &cited, "pin", 1).await.unwrap();
+    storage.tree_release(&src, "test", 1).await.unwrap();
+    storage.cleanup_run().await.unwrap();
+    assert!(storage.tree_get_archived(&src).await.is_err());
+    let copy = storage.node_get(&cloned, &head).await.unwrap();
+    assert_eq!(copy.state, Some(ResourceState::Active));
+    assert_eq!(copy.data, NodeType::External { handle });
+}
+
+// ============================================================================
+// Branch comparison
+// ============================================================================
+
+#[tokio::test]
+async fn test_common_ancestor_and_branch_diff() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+    let prompt = storage.node_create_text(&tree_id, Some(root), "prompt".into(), None).await.unwrap();
+    let left_1 = storage.node_create_text(&tree_id, Some(prompt), "left 1".into(), None).await.unwrap();
+    let left_2 = storage.node_create_text(&tree_id, Some(left_1), "left 2".into(), None).await.unwrap();
+    let right_1 = storage.node_create_text(&tree_id, Some(prompt), "right 1".into(), None).await.unwrap();
+
+    assert_eq!(storage.node_common_ancestor(&tree_id, &left_2, &right_1).await.unwrap(), prompt);
+    assert_eq!(storage.node_common_ancestor(&tree_id, &left_1, &left_2).await.unwrap(), left_1);
+
+    let (ancestor, left, right) = storage.branch_diff(&tree_id, &left_2, &right_1).await.unwrap();
+    assert_eq!(ancestor, prompt);
+    assert_eq!(left.iter().map(|n| n.id).collect::<Vec<_>>(), vec![left_1, left_2]);
+    assert_eq!(right.iter().map(|n| n.id).collect::<Vec<_>>(), vec![right_1]);
+}
+
+// ============================================================================
+// Partial retrieval
+// ============================================================================
+
+#[tokio::test]
+async fn test_subtree_depth_and_paging() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    let a = storage.node_create_text(&tree_id, Some(root), "a".into(), None).await.unwrap();
+    let a1 = storage.node_create_text(&tree_id, Some(a), "a1".into(), None).await.unwrap();
+    let deep = storage.node_create_text(&tree_id, Some(a1), "deep".into(), None).await.unwrap();
+    let mut children = vec![a];
+    for i in 0..4 {
+        children.push(storage.node_create_text(&tree_id, Some(root), format!("c{}", i), None).await.unwrap());
+    }
+
+    // Depth 1 from a: a and a1, with a1 still listing its child
+    let subtree = storage.tree_get_subtree(&tree_id, &a, Some(1)).await.unwrap();
+    assert_eq!(subtree.iter().map(|n| n.id).collect::<Vec<_>>(), vec![a, a1]);
+    assert_eq!(subtree[1].children, vec![deep]);
+    assert_eq!(storage.tree_get_subtree(&tree_id, &a, None).await.unwrap().len(), 3);
+
+    // Pages of two, in child order
+    let mut paged = Vec::new();
+    let mut cursor = None;
+    loop {
+        let (page, next) = storage
+            .node_get_children_page(&tree_id, &root, cursor.as_ref(), 2)
+            .await
+            .unwrap();
+        assert!(page.len() <= 2);
+        paged.extend(page);
+        match next {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(paged, children);
+
+    // Batches cover every node exactly once
+    let mut batched = HashSet::new();
+    let mut after = None;
+    loop {
+        let (nodes, next) = storage.tree_get_nodes_batch(&tree_id, after.as_ref(), 3).await.unwrap();
+        batched.extend(nodes.into_iter().map(|n| n.id));
+        match next {
+            Some(next) => after = Some(next),
+            None => break,
+        }
+    }
+    let all: HashSet<NodeId> = storage.tree_get(&tree_id).await.unwrap().nodes.into_keys().collect();
+    assert_eq!(batched, all);
+    assert_eq!(storage.tree_node_count(&tree_id).await.unwrap(), all.len());
+}
+
+// ============================================================================
+// Node metadata
+// ============================================================================
+
+#[tokio::test]
+async fn test_node_metadata_patch_and_query() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let first = storage.tree_create(None, "test").await.unwrap();
+    let second = storage.tree_create(None, "test").await.unwrap();
+    let first_root = storage.tree_get(&first).await.unwrap().root;
+    let second_root = storage.tree_get(&second).await.unwrap().root;
+
+    let a = storage
+        .node_create_text(&first, Some(first_root), "a".into(), Some(serde_json::json!({"tool": "bash"})))
+        .await
+        .unwrap();
+    let b = storage.node_create_text(&second, Some(second_root), "b".into(), None).await.unwrap();
+
+    let metadata = storage
+        .node_update_metadata(&first, &a, serde_json::json!({"rating": "good", "score": 3}))
+        .await
+        .unwrap();
+    assert_eq!(metadata, serde_json::json!({"tool": "bash", "rating": "good", "score": 3}));
+
+    // null removes a key
+    let metadata = storage
+        .node_update_metadata(&first, &a, serde_json::json!({"score": null}))
+        .await
+        .unwrap();
+    assert_eq!(metadata, serde_json::json!({"tool": "bash", "rating": "good"}));
+
+    storage
+        .node_update_metadata(&second, &b, serde_json::json!({"rating": "good", "score": 3}))
+        .await
+        .unwrap();
+
+    let ids = |matches: Vec<NodeQueryMatch>| matches.into_iter().map(|m| (m.tree_id, m.node.id)).collect::<Vec<_>>();
+
+    let good = storage.node_query(None, &serde_json::json!({"rating": "good"}), 100).await.unwrap();
+    assert_eq!(ids(good), vec![(first, a), (second, b)]);
+
+    let bash = storage
+        .node_query(None, &serde_json::json!({"rating": "good", "tool": "bash"}), 100)
+        .await
+        .unwrap();
+    assert_eq!(ids(bash), vec![(first, a)]);
+
+    // Numbers compare as numbers, and the tree filter applies
+    let scored = storage.node_query(None, &serde_json::json!({"score": 3}), 100).await.unwrap();
+    assert_eq!(ids(scored), vec![(second, b)]);
+    let in_first = storage.node_query(Some(&first), &serde_json::json!({"score": 3}), 100).await.unwrap();
+    assert!(in_first.is_empty());
+
+    assert!(storage.node_query(None, &serde_json::json!({}), 100).await.is_err());
+    // null would also match nodes missing the key
+    assert!(storage.node_query(None, &serde_json::json!({"score": null}), 100).await.is_err());
+}
+
+// ============================================================================
+// Render formats
+// ============================================================================
+
+#[tokio::test]
+async fn test_render_formats() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    let long = "a \"quoted\" question ".repeat(10);
+    let question = storage
+        .node_create_text(&tree_id, Some(root), long.clone(), Some(serde_json::json!({"role": "user"})))
+        .await
+        .unwrap();
+    let answer = storage.node_create_text(&tree_id, Some(question), "42".into(), None).await.unwrap();
+
+    let tree = storage.tree_get(&tree_id).await.unwrap();
+    let resolved = ResolvedHandles::new();
+
+    let dot = tree.render_dot(&resolved);
+    assert!(dot.starts_with(&format!("digraph \"{}\"", tree_id)));
+    assert!(dot.contains(&format!("\"{}\" -> \"{}\";", question, answer)));
+    assert!(dot.contains("[user] a \\\"quoted\\\" question"));
+
+    let mermaid = tree.render_mermaid(&resolved);
+    assert!(mermaid.starts_with("graph TD\n"));
+    assert!(mermaid.contains("    n0 --> n1\n    n1 --> n2\n"));
+    assert!(mermaid.contains("#quot;quoted#quot;"));
+
+    let json = tree.render_json(&resolved);
+    assert_eq!(json["root"]["children"][0]["content"], serde_json::json!(long));
+    assert_eq!(json["root"]["children"][0]["children"][0]["content"], "42");
+
+    // Markdown keeps the full content and skips the empty root
+    let markdown = tree.render_markdown(&answer, &resolved).unwrap();
+    assert_eq!(markdown, format!("**user**\n\n{}\n\n---\n\n**text**\n\n42\n", long.trim_end()));
+}
+
+// ============================================================================
+// Batch operations
+// ============================================================================
+
+#[tokio::test]
+async fn test_batch_applies_atomically() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    let ops = vec![
+        BatchOp::CreateText { parent: BatchNodeRef::Node(root), content: "q".into(), metadata: None },
+        BatchOp::CreateText { parent: BatchNodeRef::Op { op: 0 }, content: "a".into(), metadata: None },
+        BatchOp::UpdateMetadata { node: BatchNodeRef::Op { op: 1 }, patch: serde_json::json!({"rating": "good"}) },
+        BatchOp::Claim { node: BatchNodeRef::Op { op: 1 }, owner_id: "importer".into(), count: 1 },
+    ];
+    let ids = storage.batch(&tree_id, ops).await.unwrap();
+    assert_eq!(ids.len(), 4);
+    assert_eq!(ids[1], ids[2]);
+    assert_eq!(ids[1], ids[3]);
+
+    let answer = storage.node_get(&tree_id, &ids[1]).await.unwrap();
+    assert_eq!(answer.parent, Some(ids[0]));
+    assert_eq!(answer.metadata, Some(serde_json::json!({"rating": "good"})));
+    assert_eq!(answer.refs.unwrap().ref_count, 2);
+
+    // A failing operation rolls back the ones before it
+    let before = storage.tree_node_count(&tree_id).await.unwrap();
+    let ops = vec![
+        BatchOp::CreateText { parent: BatchNodeRef::Node(root), content: "kept?".into(), metadata: None },
+        BatchOp::CreateText { parent: BatchNodeRef::Node(NodeId::new()), content: "orphan".into(), metadata: None },
+    ];
+    assert!(storage.batch(&tree_id, ops).await.is_err());
+    assert_eq!(storage.tree_node_count(&tree_id).await.unwrap(), before);
+
+    // Forward references are rejected
+    let ops = vec![BatchOp::CreateText { parent: BatchNodeRef::Op { op: 0 }, content: "x".into(), metadata: None }];
+    assert!(storage.batch(&tree_id, ops).await.is_err());
+
+    // The wire format accepts node IDs and {"op": i} references
+    let parsed: Vec<BatchOp> = serde_json::from_value(serde_json::json!([
+        {"type": "create_text", "parent": root.to_string(), "content": "q"},
+        {"type": "claim", "node": {"op": 0}, "owner_id": "o"}
+    ]))
+    .unwrap();
+    assert!(matches!(parsed[1], BatchOp::Claim { node: BatchNodeRef::Op { op: 0 }, count: 1, .. }));
+}
+
+// ============================================================================
+// Tree listing
+// ============================================================================
+
+#[tokio::test]
+async fn test_tree_list_summaries() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+
+    let mut created = Vec::new();
+    for i in 0..3 {
+        let tree_id = storage
+            .tree_create(Some(serde_json::json!({"name": format!("t{}", i), "kind": "chat"})), "test")
+            .await
+            .unwrap();
+        created.push(tree_id);
+    }
+    let other = storage.tree_create(Some(serde_json::json!({"kind": "notes"})), "test").await.unwrap();
+
+    // t1: root -> a -> {b, c}
+    let root = storage.tree_get(&created[1]).await.unwrap().root;
+    let a = storage.node_create_text(&created[1], Some(root), "a".into(), None).await.unwrap();
+    storage.node_create_text(&created[1], Some(a), "b".into(), None).await.unwrap();
+    storage.node_create_text(&created[1], Some(a), "c".into(), None).await.unwrap();
+
+    let summary = storage.tree_summary(&created[1]).await.unwrap();
+    assert_eq!(summary.name.as_deref(), Some("t1"));
+    assert_eq!((summary.node_count, summary.leaf_count, summary.depth), (4, 2, 2));
+    assert_eq!(summary.state, ResourceState::Active);
+
+    // Page through the chat trees two at a time
+    let mut query = TreeListQuery {
+        metadata: Some(serde_json::json!({"kind": "chat"})),
+        order: ListOrder::Asc,
+        limit: 2,
+        ..Default::default()
+    };
+    let (first, cursor) = storage.tree_list_summaries(&query).await.unwrap();
+    assert_eq!(first.len(), 2);
+    assert!(cursor.is_some());
+    query.cursor = cursor;
+    let (second, cursor) = storage.tree_list_summaries(&query).await.unwrap();
+    assert_eq!(second.len(), 1);
+    assert!(cursor.is_none());
+
+    let listed: HashSet<TreeId> = first.iter().chain(&second).map(|t| t.id).collect();
+    assert_eq!(listed, created.into_iter().collect::<HashSet<_>>());
+
+    // Scheduled trees only show up when asked for
+    storage.tree_release(&other, "test", 1).await.unwrap();
+    let scheduled = TreeListQuery { states: vec![ResourceState::ScheduledDelete], ..Default::default() };
+    let (trees, _) = storage.tree_list_summaries(&scheduled).await.unwrap();
+    assert_eq!(trees.iter().map(|t| t.id).collect::<Vec<_>>(), vec![other]);
+}
+
+#[tokio::test]
+async fn test_tree_stats() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    // root -> q -> {a1, tool -> a2}, plus a deleted branch that must not count
+    let q = storage.node_create_text(&tree_id, Some(root), "12345678".into(), None).await.unwrap();
+    let a1 = storage.node_create_text(&tree_id, Some(q), "1234".into(), None).await.unwrap();
+    let handle = Handle::new(uuid::Uuid::new_v4(), "1.0.0".to_string(), "execute".to_string());
+    let tool = storage.node_create_external(&tree_id, Some(q), handle, None).await.unwrap();
+    let a2 = storage.node_create_text(&tree_id, Some(tool), "é".into(), None).await.unwrap();
+    let gone = storage.node_create_text(&tree_id, Some(root), "x".repeat(100), None).await.unwrap();
+    storage.node_delete(&tree_id, &gone).await.unwrap();
+
+    let stats = storage.tree_stats(&tree_id).await.unwrap();
+    assert_eq!(stats.node_count, 5);
+    assert_eq!(stats.node_types, NodeTypeCounts { text: 4, external: 1 });
+    assert_eq!((stats.leaf_count, stats.depth, stats.max_children), (2, 3, 2));
+    assert_eq!(stats.branching_factor, 4.0 / 3.0);
+    assert_eq!((stats.text_bytes, stats.resolved_bytes, stats.unresolved_count), (14, 0, 1));
+
+    // Largest context first; the unresolved tool output is flagged, not sized
+    let leaves: Vec<_> = stats.leaf_paths.iter().map(|l| (l.leaf_id, l.bytes, l.tokens, l.unresolved)).collect();
+    assert_eq!(leaves, vec![(a1, 12, 3, 0), (a2, 10, 3, 1)]);
+
+    // Cached resolved content counts once available
+    storage.node_set_resolved(&tool, &"y".repeat(30)).await.unwrap();
+    let stats = storage.tree_stats(&tree_id).await.unwrap();
+    assert_eq!((stats.resolved_bytes, stats.unresolved_count), (30, 0));
+    assert_eq!(stats.leaf_paths[0].leaf_id, a2);
+    assert_eq!((stats.leaf_paths[0].bytes, stats.leaf_paths[0].tokens), (40, 10));
+}
+
+// ============================================================================
+// Backends
+// ============================================================================
+
+/// Run the same tree/node/ref script against any backend
+async fn exercise_backend(backend: &dyn ArborBackend) {
+    let tree_id = backend.tree_create(Some(serde_json::json!({"name": "t"})), "owner").await.unwrap();
+    let tree = backend.tree_get(&tree_id).await.unwrap();
+    assert_eq!(tree.nodes.len(), 1);
+    assert_eq!(tree.refs.unwrap().owners.get("owner"), Some(&1));
+    let root = tree.root;
+
+    // root -> a -> {b, ephemeral}
+    let a = backend.node_create_text(&tree_id, Some(root), "a".into(), None).await.unwrap();
+    let handle = Handle::new(uuid::Uuid::new_v4(), "1.0.0".to_string(), "chat".to_string())
+        .with_meta(vec!["m".to_string()]);
+    let b = backend.node_create_external(&tree_id, Some(a), handle.clone(), None).await.unwrap();
+    let ephemeral = backend
+        .node_create_external_ephemeral(&tree_id, Some(a), handle.clone(), None)
+        .await
+        .unwrap();
+
+    assert_eq!(backend.node_get_children(&tree_id, &a).await.unwrap(), vec![b]);
+    assert_eq!(backend.node_get_parent(&tree_id, &b).await.unwrap(), Some(a));
+    assert_eq!(backend.node_get_path(&tree_id, &b).await.unwrap(), vec![root, a, b]);
+    assert_eq!(backend.context_list_leaves(&tree_id).await.unwrap(), vec![b]);
+    assert_eq!(backend.context_get_handles(&tree_id, &b).await.unwrap(), vec![handle]);
+    let path = backend.context_get_path(&tree_id, &ephemeral).await.unwrap();
+    assert_eq!(path.last().unwrap().state, Some(ResourceState::ScheduledDelete));
+
+    // Metadata patches merge recursively, null removes keys, inactive nodes are read-only
+    backend.node_update_metadata(&tree_id, &a, serde_json::json!({"x": {"k": 1}, "y": 2})).await.unwrap();
+    let metadata = backend.node_update_metadata(&tree_id, &a, serde_json::json!({"x": {"j": 3}, "y": null})).await.unwrap();
+    assert_eq!(metadata, serde_json::json!({"x": {"k": 1, "j": 3}}));
+    assert!(backend.node_update_metadata(&tree_id, &ephemeral, serde_json::json!({"x": 1})).await.is_err());
+
+    // Node refs: claim, release, then drop the implicit reference too
+    assert_eq!(backend.node_claim(&tree_id, &b, "reader", 2).await.unwrap(), 3);
+    assert_eq!(backend.node_get_refs(&tree_id, &b).await.unwrap().owners.get("reader"), Some(&2));
+    assert!(backend.node_release(&tree_id, &b, "reader", 3).await.is_err());
+    assert_eq!(backend.node_release(&tree_id, &b, "reader", 2).await.unwrap(), 1);
+    assert!(backend.node_release(&tree_id, &b, "reader", 1).await.is_err(), "owner has no refs left");
+
+    // Tree refs: releasing the last one schedules the tree, claiming revives it
+    backend.tree_update_metadata(&tree_id, serde_json::json!({"name": "renamed"})).await.unwrap();
+    assert_eq!(backend.tree_claim(&tree_id, "other", 1).await.unwrap(), 2);
+    assert_eq!(backend.tree_release(&tree_id, "owner", 1).await.unwrap(), 1);
+    assert_eq!(backend.tree_release(&tree_id, "other", 1).await.unwrap(), 0);
+    assert!(backend.tree_list(false).await.unwrap().is_empty());
+    assert_eq!(backend.tree_list(true).await.unwrap(), vec![tree_id]);
+    assert_eq!(backend.tree_list_scheduled().await.unwrap(), vec![tree_id]);
+    assert!(backend.tree_list_archived().await.unwrap().is_empty());
+
+    assert_eq!(backend.tree_claim(&tree_id, "owner", 1).await.unwrap(), 1);
+    let tree = backend.tree_get(&tree_id).await.unwrap();
+    assert_eq!(tree.state, Some(ResourceState::Active));
+    assert_eq!(tree.metadata, Some(serde_json::json!({"name": "renamed"})));
+    assert_eq!(tree.nodes.len(), 4);
+}
+
+#[tokio::test]
+async fn test_sqlite_backend() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    exercise_backend(&storage).await;
+}
+
+#[tokio::test]
+async fn test_memory_backend() {
+    exercise_backend(&MemoryArborBackend::new()).await;
+}
+
+// ============================================================================
+// Moving and reordering
+// ============================================================================
+
+#[tokio::test]
+async fn test_node_move_and_reorder() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    // root -> {user, assistant -> tool_call}, with tool_result misplaced under root
+    let user = storage.node_create_text(&tree_id, Some(root), "user".into(), None).await.unwrap();
+    let assistant = storage.node_create_text(&tree_id, Some(root), "assistant".into(), None).await.unwrap();
+    let tool_call = storage.node_create_text(&tree_id, Some(assistant), "call".into(), None).await.unwrap();
+    let tool_result = storage.node_create_text(&tree_id, Some(root), "result".into(), None).await.unwrap();
+
+    // Cycles and root moves are rejected
+    assert!(storage.node_move(&tree_id, &root, &user, None).await.is_err());
+    assert!(storage.node_move(&tree_id, &assistant, &assistant, None).await.is_err());
+    assert!(storage.node_move(&tree_id, &assistant, &tool_call, None).await.is_err());
+
+    // Move the result under the assistant turn, before the call
+    let (old_parent, position) = storage
+        .node_move(&tree_id, &tool_result, &assistant, Some(0))
+        .await
+        .unwrap();
+    assert_eq!((old_parent, position), (root, 0));
+
+    let tree = storage.tree_get(&tree_id).await.unwrap();
+    assert_eq!(tree.nodes[&root].children, vec![user, assistant]);
+    assert_eq!(tree.nodes[&assistant].children, vec![tool_result, tool_call]);
+    assert_eq!(tree.nodes[&tool_result].parent, Some(assistant));
+    assert_eq!(
+        storage.node_get_path(&tree_id, &tool_result).await.unwrap(),
+        vec![root, assistant, tool_result]
+    );
+
+    // Moving within the same parent only changes the position; past the end appends
+    let (_, position) = storage.node_move(&tree_id, &tool_result, &assistant, Some(9)).await.unwrap();
+    assert_eq!(position, 1);
+    let children = storage.node_get(&tree_id, &assistant).await.unwrap().children;
+    assert_eq!(children, vec![tool_call, tool_result]);
+
+    // Reordering must name exactly the current children
+    assert!(storage.node_reorder_children(&tree_id, &root, &[assistant]).await.is_err());
+    assert!(storage.node_reorder_children(&tree_id, &root, &[assistant, assistant]).await.is_err());
+    assert!(storage.node_reorder_children(&tree_id, &root, &[assistant, tool_call]).await.is_err());
+
+    storage.node_reorder_children(&tree_id, &root, &[assistant, user]).await.unwrap();
+    let children = storage.node_get(&tree_id, &root).await.unwrap().children;
+    assert_eq!(children, vec![assistant, user]);
+
+    // New children still append after the reordered ones
+    let last = storage.node_create_text(&tree_id, Some(root), "last".into(), None).await.unwrap();
+    let children = storage.node_get(&tree_id, &root).await.unwrap().children;
+    assert_eq!(children, vec![assistant, user, last]);
+}
+
+#[tokio::test]
+async fn test_node_move_position_skips_scheduled_siblings() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    // root -> {a, hidden, b}, with hidden scheduled for deletion
+    let a = storage.node_create_text(&tree_id, Some(root), "a".into(), None).await.unwrap();
+    let hidden = storage.node_create_text(&tree_id, Some(root), "hidden".into(), None).await.unwrap();
+    let b = storage.node_create_text(&tree_id, Some(root), "b".into(), None).await.unwrap();
+    let moved = storage.node_create_text(&tree_id, Some(a), "moved".into(), None).await.unwrap();
+    storage.node_delete(&tree_id, &hidden).await.unwrap();
+
+    // Index 1 among active children lands before b, not before hidden
+    let (old_parent, position) = storage.node_move(&tree_id, &moved, &root, Some(1)).await.unwrap();
+    assert_eq!((old_parent, position), (a, 1));
+    let children = storage.node_get(&tree_id, &root).await.unwrap().children;
+    assert_eq!(children, vec![a, hidden, moved, b]);
+
+    // Past the active end appends after everything, scheduled or not
+    let (_, position) = storage.node_move(&tree_id, &moved, &root, Some(3)).await.unwrap();
+    assert_eq!(position, 2);
+    let children = storage.node_get(&tree_id, &root).await.unwrap().children;
+    assert_eq!(children, vec![a, hidden, b, moved]);
+
+    // Scheduled nodes stay where they are
+    assert!(storage.node_move(&tree_id, &hidden, &a, None).await.is_err());
+    let children = storage.node_get(&tree_id, &root).await.unwrap().children;
+    assert_eq!(children, vec![a, hidden, b, moved]);
+}
+
+// ============================================================================
+// Ancestry queries
+// ============================================================================
+
+const DEEP_PATH_DEPTH: usize = 1000;
+
+/// A single 1,000-deep chain under the root, built in one transaction
+async fn create_deep_chain(storage: &ArborStorage) -> (TreeId, NodeId, Vec<NodeId>) {
+    let tree_id = storage.tree_create(None, "test").await.unwrap();
+    let root = storage.tree_get(&tree_id).await.unwrap().root;
+
+    let ops = (0..DEEP_PATH_DEPTH)
+        .map(|i| BatchOp::CreateText {
+            parent: if i == 0 { BatchNodeRef::Node(root) } else { BatchNodeRef::Op { op: i - 1 } },
+            content: format!("turn {}", i),
+            metadata: None,
+        })
+        .collect();
+    let chain = storage.batch(&tree_id, ops).await.unwrap();
+    (tree_id, root, chain)
+}
+
+#[tokio::test]
+async fn test_deep_path_queries() {
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let (tree_id, root, chain) = create_deep_chain(&storage).await;
+    let head = *chain.last().unwrap();
+
+    // One query per ancestor gives the reference answer
+    let mut naive = vec![head];
+    while let Some(parent) = storage.node_get_parent(&tree_id, naive.last().unwrap()).await.unwrap() {
+        naive.push(parent);
+    }
+    naive.reverse();
+
+    let path = storage.node_get_path(&tree_id, &head).await.unwrap();
+    let cached = storage.node_get_path(&tree_id, &head).await.unwrap();
+    let nodes = storage.context_get_path(&tree_id, &head).await.unwrap();
+
+    assert_eq!(path.len(), DEEP_PATH_DEPTH + 1);
+    assert_eq!(path, naive);
+    assert_eq!(cached, naive);
+    assert_eq!(nodes.iter().map(|n| n.id).collect::<Vec<_>>(), naive);
+
+    // Leaves come from the same kind of query
+    assert_eq!(storage.context_list_leaves(&tree_id).await.unwrap(), vec![head]);
+
+    // Moving a node invalidates cached paths in its tree
+    storage.node_move(&tree_id, &head, &root, None).await.unwrap();
+    assert_eq!(storage.node_get_path(&tree_id, &head).await.unwrap(), vec![root, head]);
+    let leaves: HashSet<NodeId> = storage.context_list_leaves(&tree_id).await.unwrap().into_iter().collect();
+    assert_eq!(leaves, HashSet::from([head, chain[DEEP_PATH_DEPTH - 2]]));
+}
+
+/// Timing comparison; run with `cargo test -- --ignored`
+#[tokio::test]
+#[ignore]
+async fn test_deep_path_timings() {
+    use std::time::Instant;
+
+    let (storage, _dir) = create_test_storage(ArborConfig::default()).await;
+    let (tree_id, _root, chain) = create_deep_chain(&storage).await;
+    let head = *chain.last().unwrap();
+
+    // Baseline: one query per ancestor, as node_get_path used to do
+    let started = Instant::now();
+    let mut naive = vec![head];
+    while let Some(parent) = storage.node_get_parent(&tree_id, naive.last().unwrap()).await.unwrap() {
+        naive.push(parent);
+    }
+    let naive_elapsed = started.elapsed();
+
+    let started = Instant::now();
+    storage.node_get_path(&tree_id, &head).await.unwrap();
+    let cold_elapsed = started.elapsed();
+
+    let started = Instant::now();
+    storage.node_get_path(&tree_id, &head).await.unwrap();
+    let warm_elapsed = started.elapsed();
+
+    assert!(cold_elapsed < naive_elapsed, "recursive query should beat the per-parent walk");
+    assert!(warm_elapsed < cold_elapsed, "cache hit should beat the recursive query");
+}
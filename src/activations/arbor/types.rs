@@ -1,3 +1,4 @@
+use super::bundle::BundleFormat;
 pub use crate::types::Handle;
 use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
@@ -407,6 +408,257 @@ impl From<&Tree> for TreeSkeleton {
     }
 }
 
+// ============================================================================
+// Tree Listing
+// ============================================================================
+
+/// Sort direction for `tree_list`, by last update
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ListOrder {
+    /// Most recently updated first
+    #[default]
+    Desc,
+    /// Least recently updated first
+    Asc,
+}
+
+/// Filters and paging for `ArborStorage::tree_list_summaries`
+#[derive(Debug, Clone)]
+pub struct TreeListQuery {
+    /// Metadata keys and the values they must equal
+    pub metadata: Option<serde_json::Value>,
+    /// States to include (empty means active only)
+    pub states: Vec<ResourceState>,
+    pub order: ListOrder,
+    /// `next_cursor` from the previous page
+    pub cursor: Option<String>,
+    pub limit: usize,
+}
+
+impl Default for TreeListQuery {
+    fn default() -> Self {
+        Self {
+            metadata: None,
+            states: Vec::new(),
+            order: ListOrder::default(),
+            cursor: None,
+            limit: 50,
+        }
+    }
+}
+
+/// One row of `tree_list`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct TreeSummary {
+    pub id: TreeId,
+    /// `name` from the tree metadata, if set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub state: ResourceState,
+    /// Active nodes, including the root
+    pub node_count: usize,
+    /// Active nodes without active children
+    pub leaf_count: usize,
+    /// Longest active path below the root, in edges
+    pub depth: usize,
+    pub created_at: i64,
+    pub updated_at: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+}
+
+// ============================================================================
+// Tree Statistics
+// ============================================================================
+
+/// Rough bytes-per-token ratio used for token estimates
+pub const BYTES_PER_TOKEN: usize = 4;
+
+/// Approximate the token count of `bytes` of text
+///
+/// A provider-agnostic heuristic (about four bytes per token for English);
+/// good enough to spot contexts nearing a limit, not to bill against.
+pub fn estimate_tokens(bytes: usize) -> usize {
+    bytes.div_ceil(BYTES_PER_TOKEN)
+}
+
+/// Active node counts per `NodeType`
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct NodeTypeCounts {
+    pub text: usize,
+    pub external: usize,
+}
+
+/// Size of the context ending at one leaf
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct LeafPathStats {
+    pub leaf_id: NodeId,
+    /// Edges between the root and the leaf
+    pub depth: usize,
+    /// Text bytes plus cached resolved bytes along the path
+    pub bytes: usize,
+    /// Approximate tokens for `bytes`
+    pub tokens: usize,
+    /// External nodes on the path without cached content (not counted in `bytes`)
+    pub unresolved: usize,
+}
+
+/// Shape and size statistics for the active part of a tree
+///
+/// Only nodes reachable from the root through active nodes are counted.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct TreeStats {
+    pub tree_id: TreeId,
+    pub node_count: usize,
+    pub node_types: NodeTypeCounts,
+    pub leaf_count: usize,
+    /// Longest path below the root, in edges
+    pub depth: usize,
+    /// Mean number of children of nodes that have any
+    pub branching_factor: f64,
+    /// Most children under a single node
+    pub max_children: usize,
+    /// UTF-8 bytes of text node content
+    pub text_bytes: usize,
+    /// UTF-8 bytes of cached resolved content for external nodes
+    pub resolved_bytes: usize,
+    /// External nodes without cached resolved content
+    pub unresolved_count: usize,
+    /// One entry per leaf, largest context first
+    pub leaf_paths: Vec<LeafPathStats>,
+}
+
+// ============================================================================
+// Search
+// ============================================================================
+
+/// Which index a search hit came from
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchSource {
+    /// Text node content or node metadata
+    Node,
+    /// Tree metadata (reported on the root node)
+    Tree,
+    /// Resolved content of an external handle
+    Resolved,
+}
+
+/// A ranked full-text search match
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct SearchHit {
+    pub tree_id: TreeId,
+    pub node_id: NodeId,
+    /// Path from root to the matching node
+    pub path: Vec<NodeId>,
+    pub source: SearchSource,
+    /// Excerpt around the match, with matched terms in [brackets]
+    pub snippet: String,
+    /// BM25 rank relative to the best match from the same source, in
+    /// (0, 1] (higher is better)
+    pub score: f64,
+}
+
+// ============================================================================
+// Metadata Queries
+// ============================================================================
+
+/// A node matched by `node_query`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+pub struct NodeQueryMatch {
+    pub tree_id: TreeId,
+    pub node: Node,
+}
+
+// ============================================================================
+// Batch Operations
+// ============================================================================
+
+/// A node referenced by a batch operation
+///
+/// Either an existing node ID, or `{"op": i}` for the node created or
+/// touched by operation `i` earlier in the same batch.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum BatchNodeRef {
+    Node(NodeId),
+    Op { op: usize },
+}
+
+/// One operation in an `ArborStorage::batch`
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchOp {
+    /// Create a text node under `parent`
+    CreateText {
+        parent: BatchNodeRef,
+        content: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<serde_json::Value>,
+    },
+    /// Create an external node under `parent`
+    CreateExternal {
+        parent: BatchNodeRef,
+        handle: Handle,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        metadata: Option<serde_json::Value>,
+    },
+    /// Merge a patch into a node's metadata (see `node_update_metadata`)
+    UpdateMetadata {
+        node: BatchNodeRef,
+        patch: serde_json::Value,
+    },
+    /// Add references to a node (see `node_claim`)
+    Claim {
+        node: BatchNodeRef,
+        owner_id: String,
+        #[serde(default = "default_claim_count")]
+        count: i64,
+    },
+}
+
+fn default_claim_count() -> i64 {
+    1
+}
+
+// ============================================================================
+// Lifecycle Cleanup
+// ============================================================================
+
+/// What a single cleanup run did
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct CleanupReport {
+    /// Run start timestamp (Unix seconds)
+    pub started_at: i64,
+    /// Run end timestamp (Unix seconds)
+    pub finished_at: i64,
+    /// Trees moved from scheduled_delete to archived
+    pub trees_archived: usize,
+    /// Nodes moved from scheduled_delete to archived
+    pub nodes_archived: usize,
+    /// Archived trees permanently deleted
+    pub trees_purged: usize,
+    /// Archived nodes permanently deleted
+    pub nodes_purged: usize,
+}
+
+/// Cleanup configuration and the most recent run
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+pub struct CleanupStatus {
+    /// Whether the background worker is running
+    pub auto_cleanup: bool,
+    /// Background worker interval (seconds)
+    pub cleanup_interval: i64,
+    /// Duration before scheduled resources move to archived (seconds)
+    pub scheduled_deletion_window: i64,
+    /// Duration before archived resources are purged (seconds)
+    pub archive_window: i64,
+    /// Report of the most recent run, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run: Option<CleanupReport>,
+}
+
 // ============================================================================
 // Stream Events
 // ============================================================================
@@ -428,6 +680,17 @@ pub enum ArborEvent {
     #[serde(rename = "tree_list")]
     TreeList { tree_ids: Vec<TreeId> },
 
+    #[serde(rename = "tree_summaries")]
+    TreeSummaries {
+        trees: Vec<TreeSummary>,
+        /// Cursor for the next page, if there is one
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        next_cursor: Option<String>,
+    },
+
+    #[serde(rename = "tree_stats")]
+    TreeStats { stats: TreeStats },
+
     // Reference counting events
     #[serde(rename = "tree_claimed")]
     TreeClaimed {
@@ -449,6 +712,13 @@ pub enum ArborEvent {
     #[serde(rename = "tree_archived")]
     TreeArchived { tree_id: TreeId, archived_at: i64 },
 
+    #[serde(rename = "tree_restored")]
+    TreeRestored {
+        tree_id: TreeId,
+        owner_id: String,
+        new_count: i64,
+    },
+
     #[serde(rename = "tree_refs")]
     TreeRefs { tree_id: TreeId, refs: ResourceRefs },
 
@@ -467,6 +737,53 @@ pub enum ArborEvent {
         new_id: NodeId,
     },
 
+    #[serde(rename = "node_moved")]
+    NodeMoved {
+        tree_id: TreeId,
+        node_id: NodeId,
+        old_parent: NodeId,
+        new_parent: NodeId,
+        /// Position under the new parent
+        position: usize,
+    },
+
+    #[serde(rename = "children_reordered")]
+    ChildrenReordered {
+        tree_id: TreeId,
+        node_id: NodeId,
+        /// Children in their new order
+        children: Vec<NodeId>,
+    },
+
+    #[serde(rename = "node_metadata_updated")]
+    NodeMetadataUpdated {
+        tree_id: TreeId,
+        node_id: NodeId,
+        /// Metadata after the patch was applied
+        metadata: serde_json::Value,
+    },
+
+    #[serde(rename = "node_query_results")]
+    NodeQueryResults {
+        filter: serde_json::Value,
+        matches: Vec<NodeQueryMatch>,
+    },
+
+    #[serde(rename = "batch_applied")]
+    BatchApplied {
+        tree_id: TreeId,
+        /// Node created or touched by each operation, in order
+        node_ids: Vec<NodeId>,
+    },
+
+    #[serde(rename = "node_history")]
+    NodeHistory {
+        tree_id: TreeId,
+        node_id: NodeId,
+        /// Versions of the node, oldest first
+        versions: Vec<NodeId>,
+    },
+
     #[serde(rename = "node_deleted")]
     NodeDeleted { tree_id: TreeId, node_id: NodeId },
 
@@ -500,6 +817,14 @@ pub enum ArborEvent {
         archived_at: i64,
     },
 
+    #[serde(rename = "node_restored")]
+    NodeRestored {
+        tree_id: TreeId,
+        node_id: NodeId,
+        owner_id: String,
+        new_count: i64,
+    },
+
     #[serde(rename = "node_refs")]
     NodeRefs {
         tree_id: TreeId,
@@ -514,6 +839,23 @@ pub enum ArborEvent {
     #[serde(rename = "tree_skeleton")]
     TreeSkeleton { skeleton: TreeSkeleton },
 
+    #[serde(rename = "tree_subtree")]
+    TreeSubtree {
+        tree_id: TreeId,
+        root: NodeId,
+        max_depth: Option<usize>,
+        /// Nodes breadth-first from `root`
+        nodes: Vec<Node>,
+    },
+
+    /// First event of a batched tree_get: the tree without its nodes
+    #[serde(rename = "tree_header")]
+    TreeHeader { tree: Tree, node_count: usize },
+
+    /// One batch of nodes following a `tree_header`
+    #[serde(rename = "tree_nodes")]
+    TreeNodes { tree_id: TreeId, nodes: Vec<Node> },
+
     #[serde(rename = "node_data")]
     NodeData { tree_id: TreeId, node: Node },
 
@@ -522,6 +864,9 @@ pub enum ArborEvent {
         tree_id: TreeId,
         node_id: NodeId,
         children: Vec<NodeId>,
+        /// Cursor for the next page, when paging and more children remain
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        next_cursor: Option<NodeId>,
     },
 
     #[serde(rename = "node_parent")]
@@ -549,6 +894,25 @@ pub enum ArborEvent {
         leaves: Vec<NodeId>,
     },
 
+    // Branch comparison
+    #[serde(rename = "common_ancestor")]
+    CommonAncestor {
+        tree_id: TreeId,
+        a: NodeId,
+        b: NodeId,
+        ancestor: NodeId,
+    },
+
+    #[serde(rename = "branch_diff")]
+    BranchDiff {
+        tree_id: TreeId,
+        ancestor: NodeId,
+        /// Nodes below the ancestor leading to `a`
+        left: Vec<Node>,
+        /// Nodes below the ancestor leading to `b`
+        right: Vec<Node>,
+    },
+
     // Scheduled deletion queries
     #[serde(rename = "trees_scheduled")]
     TreesScheduled { tree_ids: Vec<TreeId> },
@@ -563,9 +927,131 @@ pub enum ArborEvent {
     #[serde(rename = "trees_archived")]
     TreesArchived { tree_ids: Vec<TreeId> },
 
+    // Copy / clone
+    #[serde(rename = "subtree_copied")]
+    SubtreeCopied {
+        tree_id: TreeId,
+        /// Copy of the source node
+        node_id: NodeId,
+        parent: NodeId,
+    },
+
+    #[serde(rename = "tree_cloned")]
+    TreeCloned {
+        tree_id: TreeId,
+        source_tree_id: TreeId,
+        /// Copy of `from_node`, or the new root when the whole tree was cloned
+        head: NodeId,
+    },
+
+    // Search
+    #[serde(rename = "search_results")]
+    SearchResults { query: String, hits: Vec<SearchHit> },
+
+    #[serde(rename = "handles_indexed")]
+    HandlesIndexed {
+        /// Tree that was indexed (None for all trees)
+        tree_id: Option<TreeId>,
+        /// Handles resolved and cached in this call
+        resolved: usize,
+        /// Handles that failed to resolve (retried later)
+        failed: usize,
+        /// Uncached handles still eligible for indexing
+        remaining: usize,
+    },
+
+    // Export / import
+    #[serde(rename = "tree_exported")]
+    TreeExported {
+        tree_id: TreeId,
+        format: BundleFormat,
+        /// Serialized bundle
+        bundle: String,
+    },
+
+    #[serde(rename = "tree_imported")]
+    TreeImported {
+        tree_id: TreeId,
+        /// Tree ID recorded in the bundle
+        source_tree_id: TreeId,
+        node_count: usize,
+    },
+
+    // Lifecycle cleanup
+    #[serde(rename = "cleanup_report")]
+    CleanupReport { report: CleanupReport },
+
+    #[serde(rename = "cleanup_status")]
+    CleanupStatus { status: CleanupStatus },
+
     // Render
     #[serde(rename = "tree_render")]
     TreeRender { tree_id: TreeId, render: String },
+
+    // Errors
+    /// A streaming operation failed part way through
+    #[serde(rename = "error")]
+    Error { message: String },
+}
+
+impl ArborEvent {
+    /// The tree an event refers to, if it is about a single tree
+    pub fn tree_id(&self) -> Option<TreeId> {
+        match self {
+            ArborEvent::TreeCreated { tree_id }
+            | ArborEvent::TreeDeleted { tree_id }
+            | ArborEvent::TreeUpdated { tree_id }
+            | ArborEvent::TreeClaimed { tree_id, .. }
+            | ArborEvent::TreeReleased { tree_id, .. }
+            | ArborEvent::TreeScheduledDeletion { tree_id, .. }
+            | ArborEvent::TreeArchived { tree_id, .. }
+            | ArborEvent::TreeRestored { tree_id, .. }
+            | ArborEvent::TreeRefs { tree_id, .. }
+            | ArborEvent::NodeCreated { tree_id, .. }
+            | ArborEvent::NodeUpdated { tree_id, .. }
+            | ArborEvent::NodeMoved { tree_id, .. }
+            | ArborEvent::ChildrenReordered { tree_id, .. }
+            | ArborEvent::NodeMetadataUpdated { tree_id, .. }
+            | ArborEvent::BatchApplied { tree_id, .. }
+            | ArborEvent::NodeHistory { tree_id, .. }
+            | ArborEvent::NodeDeleted { tree_id, .. }
+            | ArborEvent::NodeClaimed { tree_id, .. }
+            | ArborEvent::NodeReleased { tree_id, .. }
+            | ArborEvent::NodeScheduledDeletion { tree_id, .. }
+            | ArborEvent::NodeArchived { tree_id, .. }
+            | ArborEvent::NodeRestored { tree_id, .. }
+            | ArborEvent::NodeRefs { tree_id, .. }
+            | ArborEvent::NodeData { tree_id, .. }
+            | ArborEvent::NodeChildren { tree_id, .. }
+            | ArborEvent::NodeParent { tree_id, .. }
+            | ArborEvent::ContextPath { tree_id, .. }
+            | ArborEvent::ContextPathData { tree_id, .. }
+            | ArborEvent::ContextHandles { tree_id, .. }
+            | ArborEvent::ContextLeaves { tree_id, .. }
+            | ArborEvent::CommonAncestor { tree_id, .. }
+            | ArborEvent::BranchDiff { tree_id, .. }
+            | ArborEvent::NodesScheduled { tree_id, .. }
+            | ArborEvent::SubtreeCopied { tree_id, .. }
+            | ArborEvent::TreeCloned { tree_id, .. }
+            | ArborEvent::TreeExported { tree_id, .. }
+            | ArborEvent::TreeImported { tree_id, .. }
+            | ArborEvent::TreeSubtree { tree_id, .. }
+            | ArborEvent::TreeNodes { tree_id, .. }
+            | ArborEvent::TreeRender { tree_id, .. } => Some(*tree_id),
+            ArborEvent::TreeData { tree } | ArborEvent::TreeHeader { tree, .. } => Some(tree.id),
+            ArborEvent::TreeSkeleton { skeleton } => Some(skeleton.id),
+            ArborEvent::TreeStats { stats } => Some(stats.tree_id),
+            ArborEvent::HandlesIndexed { tree_id, .. } => *tree_id,
+            ArborEvent::TreeList { .. }
+            | ArborEvent::TreeSummaries { .. }
+            | ArborEvent::TreesScheduled { .. }
+            | ArborEvent::TreesArchived { .. }
+            | ArborEvent::NodeQueryResults { .. }
+            | ArborEvent::SearchResults { .. }
+            | ArborEvent::CleanupReport { .. }
+            | ArborEvent::CleanupStatus { .. } => None,
+        }
+    }
 }
 
 // ============================================================================
@@ -1,4 +1,5 @@
 use super::types::{ChangelogEntry, QueueEntry, QueueStatus};
+use crate::migrations::{self, Migration};
 use sqlx::sqlite::{SqliteConnectOptions, SqlitePool};
 use sqlx::{ConnectOptions, Row};
 use std::path::PathBuf;
@@ -17,31 +18,13 @@ impl Default for ChangelogStorageConfig {
     }
 }
 
-/// Storage for changelog entries and hash tracking
-pub struct ChangelogStorage {
-    pool: SqlitePool,
-}
-
-impl ChangelogStorage {
-    pub async fn new(config: ChangelogStorageConfig) -> Result<Self, String> {
-        let mut options = SqliteConnectOptions::new()
-            .filename(&config.db_path)
-            .create_if_missing(true);
-        options.disable_statement_logging();
-
-        let pool = SqlitePool::connect_with(options.clone())
-            .await
-            .map_err(|e| format!("Failed to connect to changelog database: {}", e))?;
-
-        let storage = Self { pool };
-        storage.init_schema().await?;
-        Ok(storage)
-    }
-
-    async fn init_schema(&self) -> Result<(), String> {
-        // Table for changelog entries
-        sqlx::query(
-            r#"
+/// Changelog schema migrations, applied in order by `init_schema`
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "changelog entries, hash state, queue",
+        sql: r#"
+            -- Changelog entries
             CREATE TABLE IF NOT EXISTS changelog_entries (
                 hash TEXT PRIMARY KEY,
                 previous_hash TEXT,
@@ -50,30 +33,16 @@ impl ChangelogStorage {
                 details TEXT,
                 author TEXT,
                 queue_id TEXT
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to create changelog_entries table: {}", e))?;
+            );
 
-        // Table for tracking the last known hash
-        sqlx::query(
-            r#"
+            -- The last known hash
             CREATE TABLE IF NOT EXISTS hash_state (
                 id INTEGER PRIMARY KEY CHECK (id = 1),
                 last_hash TEXT NOT NULL,
                 updated_at INTEGER NOT NULL
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to create hash_state table: {}", e))?;
+            );
 
-        // Table for queue entries (planned changes)
-        sqlx::query(
-            r#"
+            -- Queue entries (planned changes)
             CREATE TABLE IF NOT EXISTS queue_entries (
                 id TEXT PRIMARY KEY,
                 description TEXT NOT NULL,
@@ -82,13 +51,34 @@ impl ChangelogStorage {
                 status TEXT NOT NULL,
                 completed_hash TEXT,
                 completed_at INTEGER
-            )
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to create queue_entries table: {}", e))?;
+            );
+        "#,
+    },
+];
+
+/// Storage for changelog entries and hash tracking
+pub struct ChangelogStorage {
+    pool: SqlitePool,
+}
 
+impl ChangelogStorage {
+    pub async fn new(config: ChangelogStorageConfig) -> Result<Self, String> {
+        let mut options = SqliteConnectOptions::new()
+            .filename(&config.db_path)
+            .create_if_missing(true);
+        options.disable_statement_logging();
+
+        let pool = SqlitePool::connect_with(options.clone())
+            .await
+            .map_err(|e| format!("Failed to connect to changelog database: {}", e))?;
+
+        let storage = Self { pool };
+        storage.init_schema().await?;
+        Ok(storage)
+    }
+
+    async fn init_schema(&self) -> Result<(), String> {
+        migrations::run_migrations(&self.pool, "changelog", MIGRATIONS).await?;
         Ok(())
     }
 
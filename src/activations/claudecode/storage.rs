@@ -3,8 +3,9 @@ use super::types::{
     ClaudeCodeInfo, Message, MessageId, MessageRole, Model, Position, StreamId,
     StreamInfo, StreamStatus,
 };
-use crate::activations::arbor::{ArborStorage, NodeId, TreeId};
+use crate::activations::arbor::{ArborBackend, NodeId, TreeId};
 use serde_json::Value;
+use crate::migrations::{self, Migration};
 use sqlx::{sqlite::{SqliteConnectOptions, SqlitePool}, ConnectOptions, Row};
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -13,6 +14,60 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use tokio::sync::RwLock;
 use uuid::Uuid;
 
+/// ClaudeCode schema migrations, applied in order by `run_migrations`
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "sessions, messages, unknown events",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS claudecode_sessions (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL UNIQUE,
+                claude_session_id TEXT,
+                tree_id TEXT NOT NULL,
+                canonical_head TEXT NOT NULL,
+                working_dir TEXT NOT NULL,
+                model TEXT NOT NULL,
+                system_prompt TEXT,
+                mcp_config TEXT,
+                loopback_enabled INTEGER NOT NULL DEFAULT 0,
+                metadata TEXT,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL
+            );
+
+            CREATE TABLE IF NOT EXISTS claudecode_messages (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                role TEXT NOT NULL,
+                content TEXT NOT NULL,
+                model_id TEXT,
+                input_tokens INTEGER,
+                output_tokens INTEGER,
+                cost_usd REAL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES claudecode_sessions(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_claudecode_sessions_name ON claudecode_sessions(name);
+            CREATE INDEX IF NOT EXISTS idx_claudecode_sessions_tree ON claudecode_sessions(tree_id);
+            CREATE INDEX IF NOT EXISTS idx_claudecode_messages_session ON claudecode_messages(session_id);
+
+            CREATE TABLE IF NOT EXISTS claudecode_unknown_events (
+                id TEXT PRIMARY KEY,
+                session_id TEXT,
+                event_type TEXT NOT NULL,
+                data TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                FOREIGN KEY (session_id) REFERENCES claudecode_sessions(id) ON DELETE CASCADE
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_claudecode_unknown_events_session ON claudecode_unknown_events(session_id);
+            CREATE INDEX IF NOT EXISTS idx_claudecode_unknown_events_type ON claudecode_unknown_events(event_type);
+        "#,
+    },
+];
+
 /// Configuration for ClaudeCode storage
 #[derive(Debug, Clone)]
 pub struct ClaudeCodeStorageConfig {
@@ -40,7 +95,7 @@ struct ActiveStreamBuffer {
 /// Storage layer for ClaudeCode sessions
 pub struct ClaudeCodeStorage {
     pool: SqlitePool,
-    arbor: Arc<ArborStorage>,
+    arbor: Arc<dyn ArborBackend>,
     /// In-memory buffers for active streams
     streams: RwLock<HashMap<StreamId, ActiveStreamBuffer>>,
 }
@@ -49,7 +104,7 @@ impl ClaudeCodeStorage {
     /// Create a new ClaudeCode storage instance with a shared Arbor storage
     pub async fn new(
         config: ClaudeCodeStorageConfig,
-        arbor: Arc<ArborStorage>,
+        arbor: Arc<dyn ArborBackend>,
     ) -> Result<Self, ClaudeCodeError> {
         let db_url = format!("sqlite:{}?mode=rwc", config.db_path.display());
         let mut connect_options: SqliteConnectOptions = db_url.parse()
@@ -71,63 +126,12 @@ impl ClaudeCodeStorage {
 
     /// Run database migrations
     async fn run_migrations(&self) -> Result<(), ClaudeCodeError> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS claudecode_sessions (
-                id TEXT PRIMARY KEY,
-                name TEXT NOT NULL UNIQUE,
-                claude_session_id TEXT,
-                tree_id TEXT NOT NULL,
-                canonical_head TEXT NOT NULL,
-                working_dir TEXT NOT NULL,
-                model TEXT NOT NULL,
-                system_prompt TEXT,
-                mcp_config TEXT,
-                loopback_enabled INTEGER NOT NULL DEFAULT 0,
-                metadata TEXT,
-                created_at INTEGER NOT NULL,
-                updated_at INTEGER NOT NULL
-            );
-
-            CREATE TABLE IF NOT EXISTS claudecode_messages (
-                id TEXT PRIMARY KEY,
-                session_id TEXT NOT NULL,
-                role TEXT NOT NULL,
-                content TEXT NOT NULL,
-                model_id TEXT,
-                input_tokens INTEGER,
-                output_tokens INTEGER,
-                cost_usd REAL,
-                created_at INTEGER NOT NULL,
-                FOREIGN KEY (session_id) REFERENCES claudecode_sessions(id) ON DELETE CASCADE
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_claudecode_sessions_name ON claudecode_sessions(name);
-            CREATE INDEX IF NOT EXISTS idx_claudecode_sessions_tree ON claudecode_sessions(tree_id);
-            CREATE INDEX IF NOT EXISTS idx_claudecode_messages_session ON claudecode_messages(session_id);
-
-            CREATE TABLE IF NOT EXISTS claudecode_unknown_events (
-                id TEXT PRIMARY KEY,
-                session_id TEXT,
-                event_type TEXT NOT NULL,
-                data TEXT NOT NULL,
-                created_at INTEGER NOT NULL,
-                FOREIGN KEY (session_id) REFERENCES claudecode_sessions(id) ON DELETE CASCADE
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_claudecode_unknown_events_session ON claudecode_unknown_events(session_id);
-            CREATE INDEX IF NOT EXISTS idx_claudecode_unknown_events_type ON claudecode_unknown_events(event_type);
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to run claudecode migrations: {}", e))?;
-
+        migrations::run_migrations(&self.pool, "claudecode", MIGRATIONS).await?;
         Ok(())
     }
 
-    /// Get access to the underlying arbor storage
-    pub fn arbor(&self) -> &ArborStorage {
+    /// Get access to the underlying arbor backend
+    pub fn arbor(&self) -> &dyn ArborBackend {
         &self.arbor
     }
 
@@ -245,7 +249,7 @@ impl ClaudeCodeStorage {
         .map_err(|e| format!("Failed to fetch session: {}", e))?
         .ok_or_else(|| format!("Session not found: {}", session_id))?;
 
-        self.row_to_config(row)
+        self.with_current_head(self.row_to_config(row)?).await
     }
 
     /// Get a session by name (supports partial matching)
@@ -260,7 +264,7 @@ impl ClaudeCodeStorage {
         .await
         .map_err(|e| format!("Failed to fetch session by name: {}", e))?
         {
-            return self.row_to_config(row);
+            return self.with_current_head(self.row_to_config(row)?).await;
         }
 
         // Try partial match
@@ -276,7 +280,7 @@ impl ClaudeCodeStorage {
 
         match rows.len() {
             0 => Err(ClaudeCodeError::from(format!("Session not found with name: {}", name))),
-            1 => self.row_to_config(rows.into_iter().next().unwrap()),
+            1 => self.with_current_head(self.row_to_config(rows.into_iter().next().unwrap())?).await,
             _ => {
                 let matches: Vec<String> = rows.iter().map(|r| r.get("name")).collect();
                 Err(ClaudeCodeError::from(format!(
@@ -823,6 +827,20 @@ impl ClaudeCodeStorage {
         })
     }
 
+    /// Follow copy-on-write updates of the head node, persisting the new head
+    async fn with_current_head(&self, mut config: ClaudeCodeConfig) -> Result<ClaudeCodeConfig, ClaudeCodeError> {
+        let current = self
+            .arbor
+            .node_current_version(&config.head.tree_id, &config.head.node_id)
+            .await
+            .map_err(|e| format!("Failed to resolve session head: {}", e))?;
+        if current != config.head.node_id {
+            self.session_update_head(&config.id, current, None).await?;
+            config.head = config.head.advance(current);
+        }
+        Ok(config)
+    }
+
     fn row_to_config(&self, row: sqlx::sqlite::SqliteRow) -> Result<ClaudeCodeConfig, ClaudeCodeError> {
         let id_str: String = row.get("id");
         let tree_id_str: String = row.get("tree_id");
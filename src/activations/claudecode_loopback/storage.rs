@@ -1,5 +1,6 @@
 use super::types::{ApprovalId, ApprovalRequest, ApprovalStatus};
 use serde_json::Value;
+use crate::migrations::{self, Migration};
 use sqlx::{sqlite::{SqliteConnectOptions, SqlitePool}, ConnectOptions, Row};
 use std::collections::HashMap;
 use std::path::PathBuf;
@@ -20,6 +21,29 @@ impl Default for LoopbackStorageConfig {
     }
 }
 
+/// ClaudeCode Loopback schema migrations, applied in order by `run_migrations`
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "approvals",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS loopback_approvals (
+                id TEXT PRIMARY KEY,
+                session_id TEXT NOT NULL,
+                tool_name TEXT NOT NULL,
+                tool_use_id TEXT NOT NULL,
+                input TEXT NOT NULL,
+                status TEXT NOT NULL DEFAULT 'pending',
+                response_message TEXT,
+                created_at INTEGER NOT NULL,
+                resolved_at INTEGER
+            );
+            CREATE INDEX IF NOT EXISTS idx_loopback_session ON loopback_approvals(session_id);
+            CREATE INDEX IF NOT EXISTS idx_loopback_status ON loopback_approvals(status);
+        "#,
+    },
+];
+
 pub struct LoopbackStorage {
     pool: SqlitePool,
     /// Maps tool_use_id -> session_id for correlation
@@ -68,24 +92,7 @@ impl LoopbackStorage {
     }
 
     async fn run_migrations(&self) -> Result<(), String> {
-        sqlx::query(r#"
-            CREATE TABLE IF NOT EXISTS loopback_approvals (
-                id TEXT PRIMARY KEY,
-                session_id TEXT NOT NULL,
-                tool_name TEXT NOT NULL,
-                tool_use_id TEXT NOT NULL,
-                input TEXT NOT NULL,
-                status TEXT NOT NULL DEFAULT 'pending',
-                response_message TEXT,
-                created_at INTEGER NOT NULL,
-                resolved_at INTEGER
-            );
-            CREATE INDEX IF NOT EXISTS idx_loopback_session ON loopback_approvals(session_id);
-            CREATE INDEX IF NOT EXISTS idx_loopback_status ON loopback_approvals(status);
-        "#)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Migration failed: {}", e))?;
+        migrations::run_migrations(&self.pool, "claudecode_loopback", MIGRATIONS).await?;
         Ok(())
     }
 
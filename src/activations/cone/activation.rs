@@ -1,16 +1,18 @@
+use super::context::{self, ContextMapping, ContextMappings, ContextMessage, ContextRole};
 use super::methods::ConeIdentifier;
 use super::storage::{ConeStorage, ConeStorageConfig};
+use super::tools::{self, MAX_TOOL_ROUNDS};
 use super::types::{
-    ChatEvent, ChatUsage, CreateResult, DeleteResult, GetResult,
-    ListResult, MessageRole, RegistryResult, ResolveResult, SetHeadResult,
+    ChatEvent, ChatUsage, ConeConfig, ConeError, ConeId, ContextPolicy, CreateResult, DeleteResult,
+    GetResult, ListResult, MessageRole, Position, RegistryResult, ResolveResult, SetHeadResult,
 };
-use crate::activations::arbor::{Node, NodeId, NodeType};
-use crate::activations::bash::Bash;
-use crate::plexus::{HubContext, NoParent};
+use crate::activations::arbor::{ArborError, Node, NodeId, NodeType, TreeId};
+use crate::plexus::{Handle, HubContext, NoParent, PlexusStreamItem};
 use async_stream::stream;
-use cllient::{Message, ModelRegistry};
-use futures::Stream;
+use cllient::ModelRegistry;
+use futures::{Stream, StreamExt};
 use plexus_macros::hub_methods;
+use serde_json::Value;
 use std::marker::PhantomData;
 use std::sync::{Arc, OnceLock};
 
@@ -26,6 +28,10 @@ pub struct Cone<P: HubContext = NoParent> {
     llm_registry: Arc<ModelRegistry>,
     /// Hub reference for resolving foreign handles when walking arbor trees
     hub: Arc<OnceLock<P>>,
+    /// How other plugins' handles read as chat messages
+    context_mappings: ContextMappings,
+    /// Tool specs loaded from plugin schemas, reused across turns
+    tool_specs: tools::ToolSpecCache,
     _phantom: PhantomData<P>,
 }
 
@@ -33,7 +39,7 @@ impl<P: HubContext> Cone<P> {
     /// Create a new Cone with a specific parent context type
     pub async fn with_context_type(
         config: ConeStorageConfig,
-        arbor: Arc<crate::activations::arbor::ArborStorage>,
+        arbor: Arc<dyn crate::activations::arbor::ArborBackend>,
     ) -> Result<Self, String> {
         let storage = ConeStorage::new(config, arbor)
             .await
@@ -46,6 +52,8 @@ impl<P: HubContext> Cone<P> {
             storage: Arc::new(storage),
             llm_registry: Arc::new(llm_registry),
             hub: Arc::new(OnceLock::new()),
+            context_mappings: ContextMappings::default(),
+            tool_specs: tools::ToolSpecCache::default(),
             _phantom: PhantomData,
         })
     }
@@ -76,13 +84,21 @@ impl<P: HubContext> Cone<P> {
     pub fn storage(&self) -> &Arc<ConeStorage> {
         &self.storage
     }
+
+    /// Declare how a plugin's resolved handles appear in cone context
+    ///
+    /// Plugins without a mapping use `ContextMapping::default()`, which reads
+    /// `resolved_message` items (`role` and `content` fields).
+    pub fn register_context_mapping(&self, plugin_id: uuid::Uuid, mapping: ContextMapping) {
+        self.context_mappings.register(plugin_id, mapping);
+    }
 }
 
 /// Convenience constructor and utilities for Cone with NoParent (standalone/testing)
 impl Cone<NoParent> {
     pub async fn new(
         config: ConeStorageConfig,
-        arbor: Arc<crate::activations::arbor::ArborStorage>,
+        arbor: Arc<dyn crate::activations::arbor::ArborBackend>,
     ) -> Result<Self, String> {
         Self::with_context_type(config, arbor).await
     }
@@ -174,6 +190,8 @@ impl<P: HubContext> Cone<P> {
             name = "Human-readable name for the cone",
             model_id = "LLM model ID (e.g., 'gpt-4o-mini', 'claude-3-haiku-20240307')",
             system_prompt = "Optional system prompt / instructions",
+            tools = "Plexus methods the model may call as tools (e.g., ['bash.execute', 'arbor.search'])",
+            context_policy = "How much history each request includes: full (default), max_tokens, last_turns or summarize",
             metadata = "Optional configuration metadata"
         )
     )]
@@ -182,10 +200,16 @@ impl<P: HubContext> Cone<P> {
         name: String,
         model_id: String,
         system_prompt: Option<String>,
+        tools: Option<Vec<String>>,
+        context_policy: Option<ContextPolicy>,
         metadata: Option<serde_json::Value>,
     ) -> impl Stream<Item = CreateResult> + Send + 'static {
         let storage = self.storage.clone();
         let llm_registry = self.llm_registry.clone();
+        let hub = self.hub.clone();
+        let tool_specs = self.tool_specs.clone();
+        let tools = tools.unwrap_or_default();
+        let context_policy = context_policy.unwrap_or_default();
 
         stream! {
             // Validate model exists before creating cone
@@ -196,7 +220,22 @@ impl<P: HubContext> Cone<P> {
                 return;
             }
 
-            match storage.cone_create(name, model_id, system_prompt, metadata).await {
+            // Validate tools against the hub's schemas when we can reach it
+            let checked = match hub.get() {
+                Some(parent) => tool_specs.get_or_load(parent, &tools).await.map(|_| ()),
+                None => tools.iter().try_for_each(|t| tools::split_tool_name(t).map(|_| ())),
+            };
+            if let Err(message) = checked {
+                yield CreateResult::Error { message };
+                return;
+            }
+
+            if let Err(e) = context_policy.validate() {
+                yield CreateResult::Error { message: format!("Invalid context_policy: {}", e) };
+                return;
+            }
+
+            match storage.cone_create(name, model_id, system_prompt, tools, context_policy, metadata).await {
                 Ok(cone) => {
                     yield CreateResult::Created {
                         cone_id: cone.id,
@@ -306,6 +345,9 @@ impl<P: HubContext> Cone<P> {
     ) -> impl Stream<Item = ChatEvent> + Send + 'static {
         let storage = self.storage.clone();
         let llm_registry = self.llm_registry.clone();
+        let hub = self.hub.clone();
+        let context_mappings = self.context_mappings.clone();
+        let tool_specs = self.tool_specs.clone();
 
         stream! {
             let is_ephemeral = ephemeral.unwrap_or(false);
@@ -338,7 +380,7 @@ impl<P: HubContext> Cone<P> {
             };
 
             // Resolve handles to messages
-            let messages = match resolve_context_to_messages(&storage, &context_nodes, &cone.system_prompt).await {
+            let messages = match resolve_context_to_messages(&storage, hub.get(), &context_mappings, &context_nodes).await {
                 Ok(msgs) => msgs,
                 Err(e) => {
                     yield ChatEvent::Error { message: format!("Failed to resolve context: {}", e) };
@@ -346,66 +388,27 @@ impl<P: HubContext> Cone<P> {
                 }
             };
 
-            // 3. Store user message in cone database (ephemeral if requested)
-            let user_message = if is_ephemeral {
-                match storage.message_create_ephemeral(
-                    &cone_id,
-                    MessageRole::User,
-                    prompt.clone(),
-                    None,
-                    None,
-                    None,
-                ).await {
-                    Ok(msg) => msg,
-                    Err(e) => {
-                        yield ChatEvent::Error { message: format!("Failed to store user message: {}", e.message) };
-                        return;
-                    }
-                }
-            } else {
-                match storage.message_create(
-                    &cone_id,
-                    MessageRole::User,
-                    prompt.clone(),
-                    None,
-                    None,
-                    None,
-                ).await {
-                    Ok(msg) => msg,
-                    Err(e) => {
-                        yield ChatEvent::Error { message: format!("Failed to store user message: {}", e.message) };
-                        return;
-                    }
-                }
+            // 3. Store user message and its node (ephemeral if requested)
+            let writer = TurnWriter {
+                storage: storage.clone(),
+                cone_id,
+                tree_id: cone.head.tree_id,
+                ephemeral: is_ephemeral,
             };
 
-            // Create external node with handle pointing to user message (ephemeral if requested)
-            let user_handle = ConeStorage::message_to_handle(&user_message, "user");
-            let user_node_id = if is_ephemeral {
-                match storage.arbor().node_create_external_ephemeral(
-                    &cone.head.tree_id,
-                    Some(cone.head.node_id),
-                    user_handle,
-                    None,
-                ).await {
-                    Ok(id) => id,
-                    Err(e) => {
-                        yield ChatEvent::Error { message: format!("Failed to create user node: {}", e) };
-                        return;
-                    }
+            let user_message = match writer.message(MessageRole::User, prompt.clone(), None, None, None).await {
+                Ok(msg) => msg,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to store user message: {}", e.message) };
+                    return;
                 }
-            } else {
-                match storage.arbor().node_create_external(
-                    &cone.head.tree_id,
-                    Some(cone.head.node_id),
-                    user_handle,
-                    None,
-                ).await {
-                    Ok(id) => id,
-                    Err(e) => {
-                        yield ChatEvent::Error { message: format!("Failed to create user node: {}", e) };
-                        return;
-                    }
+            };
+
+            let user_node_id = match writer.node(cone.head.node_id, &user_message, "user").await {
+                Ok(id) => id,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to create user node: {}", e) };
+                    return;
                 }
             };
 
@@ -417,154 +420,237 @@ impl<P: HubContext> Cone<P> {
                 user_position,
             };
 
-            // 4. Build LLM request with resolved messages + new user prompt
+            // 4. Call the LLM with resolved messages + new user prompt
             let mut llm_messages = messages;
-            llm_messages.push(Message::user(&prompt));
+            llm_messages.push(ContextMessage::prompt(user_node_id, prompt.clone()));
 
-            let request_builder = match llm_registry.from_id(&cone.model_id) {
-                Ok(rb) => rb,
+            let original_head = cone.head;
+            let mut reply = Box::pin(respond(writer, llm_registry, hub, tool_specs, cone, user_node_id, llm_messages));
+
+            while let Some(event) = reply.next().await {
+                match event {
+                    ChatEvent::Complete { response, usage, .. } => {
+                        // 5. Update canonical_head (skip for ephemeral, which
+                        // keeps reporting the original head)
+                        let new_head = match settle_head(&storage, &cone_id, original_head, response, !is_ephemeral).await {
+                            Ok(head) => head,
+                            Err(e) => {
+                                yield ChatEvent::Error { message: format!("Failed to update head: {}", e.message) };
+                                return;
+                            }
+                        };
+
+                        yield ChatEvent::Complete { cone_id, new_head, response, usage };
+                    }
+                    event => yield event,
+                }
+            }
+        }
+    }
+
+    /// Regenerate an answer as a new branch
+    ///
+    /// Finds the user message at or above `node_id` (default: the head),
+    /// re-runs the model over the context ending at that message and attaches
+    /// the new answer as a sibling of the existing ones. The old answers stay
+    /// in the tree.
+    #[plexus_macros::hub_method(
+        streaming,
+        params(
+            identifier = "Cone name or UUID (e.g., 'my-assistant' or '550e8400-e29b-...')",
+            node_id = "Node at or below the user message to answer again (default: current head)",
+            move_head = "Move the head to the new answer (default: true)"
+        )
+    )]
+    async fn regenerate(
+        &self,
+        identifier: ConeIdentifier,
+        node_id: Option<NodeId>,
+        move_head: Option<bool>,
+    ) -> impl Stream<Item = ChatEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        let llm_registry = self.llm_registry.clone();
+        let hub = self.hub.clone();
+        let context_mappings = self.context_mappings.clone();
+        let tool_specs = self.tool_specs.clone();
+
+        stream! {
+            let move_head = move_head.unwrap_or(true);
+
+            let cone_id = match storage.resolve_cone_identifier(&identifier).await {
+                Ok(id) => id,
                 Err(e) => {
-                    yield ChatEvent::Error { message: format!("Failed to create request builder: {}", e) };
+                    yield ChatEvent::Error { message: e.message };
                     return;
                 }
             };
 
-            let mut builder = request_builder;
-            if let Some(ref sys) = cone.system_prompt {
-                builder = builder.system(sys);
-            }
-            builder = builder.messages(llm_messages);
+            let cone = match storage.cone_get(&cone_id).await {
+                Ok(a) => a,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to get cone: {}", e.message) };
+                    return;
+                }
+            };
 
-            // Stream the response
-            let mut stream_result = match builder.stream().await {
-                Ok(s) => s,
+            let from = node_id.unwrap_or(cone.head.node_id);
+            let (user_node_id, context_nodes) = match regenerate_target(&storage, &cone.head.tree_id, &from).await {
+                Ok(target) => target,
+                Err(message) => {
+                    yield ChatEvent::Error { message };
+                    return;
+                }
+            };
+
+            let messages = match resolve_context_to_messages(&storage, hub.get(), &context_mappings, &context_nodes).await {
+                Ok(msgs) => msgs,
                 Err(e) => {
-                    yield ChatEvent::Error { message: format!("Failed to start LLM stream: {}", e) };
+                    yield ChatEvent::Error { message: format!("Failed to resolve context: {}", e) };
                     return;
                 }
             };
 
-            let mut full_response = String::new();
-            let mut input_tokens: Option<i64> = None;
-            let mut output_tokens: Option<i64> = None;
+            yield ChatEvent::Start {
+                cone_id,
+                user_position: cone.head.advance(user_node_id),
+            };
 
-            use futures::StreamExt;
-            while let Some(event) = stream_result.next().await {
+            let writer = TurnWriter {
+                storage: storage.clone(),
+                cone_id,
+                tree_id: cone.head.tree_id,
+                ephemeral: false,
+            };
+            let original_head = cone.head;
+            let mut reply = Box::pin(respond(writer, llm_registry, hub, tool_specs, cone, user_node_id, messages));
+
+            while let Some(event) = reply.next().await {
                 match event {
-                    Ok(cllient::streaming::StreamEvent::Content(text)) => {
-                        full_response.push_str(&text);
-                        yield ChatEvent::Content {
-                            cone_id,
-                            content: text,
+                    ChatEvent::Complete { response, usage, .. } => {
+                        let new_head = match settle_head(&storage, &cone_id, original_head, response, move_head).await {
+                            Ok(head) => head,
+                            Err(e) => {
+                                yield ChatEvent::Error { message: format!("Failed to update head: {}", e.message) };
+                                return;
+                            }
                         };
+
+                        yield ChatEvent::Complete { cone_id, new_head, response, usage };
                     }
-                    Ok(cllient::streaming::StreamEvent::Usage { input_tokens: inp, output_tokens: out, .. }) => {
-                        input_tokens = inp.map(|t| t as i64);
-                        output_tokens = out.map(|t| t as i64);
-                    }
-                    Ok(cllient::streaming::StreamEvent::Error(e)) => {
-                        yield ChatEvent::Error { message: format!("LLM error: {}", e) };
-                        return;
-                    }
-                    Ok(_) => {
-                        // Ignore other events (Start, Finish, Role, Raw)
-                    }
-                    Err(e) => {
-                        yield ChatEvent::Error { message: format!("Stream error: {}", e) };
-                        return;
-                    }
+                    event => yield event,
                 }
             }
+        }
+    }
 
-            // 5. Store assistant response in cone database (ephemeral if requested)
-            let assistant_message = if is_ephemeral {
-                match storage.message_create_ephemeral(
-                    &cone_id,
-                    MessageRole::Assistant,
-                    full_response,
-                    Some(cone.model_id.clone()),
-                    input_tokens,
-                    output_tokens,
-                ).await {
-                    Ok(msg) => msg,
-                    Err(e) => {
-                        yield ChatEvent::Error { message: format!("Failed to store assistant message: {}", e.message) };
-                        return;
-                    }
+    /// Edit a user message and send it again on a new branch
+    ///
+    /// Adds `new_prompt` as a sibling of the user message at `node_id`,
+    /// streams a fresh response to it and moves the head to that response.
+    /// The original message and everything below it stay in the tree.
+    #[plexus_macros::hub_method(
+        streaming,
+        params(
+            identifier = "Cone name or UUID (e.g., 'my-assistant' or '550e8400-e29b-...')",
+            node_id = "UUID of the user message node to edit",
+            new_prompt = "Replacement user message"
+        )
+    )]
+    async fn edit_and_resend(
+        &self,
+        identifier: ConeIdentifier,
+        node_id: NodeId,
+        new_prompt: String,
+    ) -> impl Stream<Item = ChatEvent> + Send + 'static {
+        let storage = self.storage.clone();
+        let llm_registry = self.llm_registry.clone();
+        let hub = self.hub.clone();
+        let context_mappings = self.context_mappings.clone();
+        let tool_specs = self.tool_specs.clone();
+
+        stream! {
+            let cone_id = match storage.resolve_cone_identifier(&identifier).await {
+                Ok(id) => id,
+                Err(e) => {
+                    yield ChatEvent::Error { message: e.message };
+                    return;
                 }
-            } else {
-                match storage.message_create(
-                    &cone_id,
-                    MessageRole::Assistant,
-                    full_response,
-                    Some(cone.model_id.clone()),
-                    input_tokens,
-                    output_tokens,
-                ).await {
-                    Ok(msg) => msg,
-                    Err(e) => {
-                        yield ChatEvent::Error { message: format!("Failed to store assistant message: {}", e.message) };
-                        return;
-                    }
+            };
+
+            let cone = match storage.cone_get(&cone_id).await {
+                Ok(a) => a,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to get cone: {}", e.message) };
+                    return;
                 }
             };
 
-            // Create external node with handle pointing to assistant message (ephemeral if requested)
-            let assistant_handle = ConeStorage::message_to_handle(&assistant_message, &cone.name);
-            let response_node_id = if is_ephemeral {
-                match storage.arbor().node_create_external_ephemeral(
-                    &cone.head.tree_id,
-                    Some(user_node_id),
-                    assistant_handle,
-                    None,
-                ).await {
-                    Ok(id) => id,
-                    Err(e) => {
-                        yield ChatEvent::Error { message: format!("Failed to create response node: {}", e) };
-                        return;
-                    }
+            let (parent_id, context_nodes) = match edit_target(&storage, &cone.head.tree_id, &node_id).await {
+                Ok(target) => target,
+                Err(message) => {
+                    yield ChatEvent::Error { message };
+                    return;
                 }
-            } else {
-                match storage.arbor().node_create_external(
-                    &cone.head.tree_id,
-                    Some(user_node_id),
-                    assistant_handle,
-                    None,
-                ).await {
-                    Ok(id) => id,
-                    Err(e) => {
-                        yield ChatEvent::Error { message: format!("Failed to create response node: {}", e) };
-                        return;
-                    }
+            };
+
+            let messages = match resolve_context_to_messages(&storage, hub.get(), &context_mappings, &context_nodes).await {
+                Ok(msgs) => msgs,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to resolve context: {}", e) };
+                    return;
                 }
             };
 
-            let new_head = user_position.advance(response_node_id);
+            let writer = TurnWriter {
+                storage: storage.clone(),
+                cone_id,
+                tree_id: cone.head.tree_id,
+                ephemeral: false,
+            };
 
-            // 6. Update canonical_head (skip for ephemeral)
-            if !is_ephemeral {
-                if let Err(e) = storage.cone_update_head(&cone_id, response_node_id).await {
-                    yield ChatEvent::Error { message: format!("Failed to update head: {}", e.message) };
+            let user_message = match writer.message(MessageRole::User, new_prompt.clone(), None, None, None).await {
+                Ok(msg) => msg,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to store user message: {}", e.message) };
                     return;
                 }
-            }
+            };
 
-            let usage_info = if input_tokens.is_some() || output_tokens.is_some() {
-                Some(ChatUsage {
-                    input_tokens: input_tokens.map(|t| t as u64),
-                    output_tokens: output_tokens.map(|t| t as u64),
-                    total_tokens: input_tokens.and_then(|i| output_tokens.map(|o| (i + o) as u64)),
-                })
-            } else {
-                None
+            let user_node_id = match writer.node(parent_id, &user_message, "user").await {
+                Ok(id) => id,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to create user node: {}", e) };
+                    return;
+                }
             };
 
-            // For ephemeral, return original head (not the ephemeral node)
-            yield ChatEvent::Complete {
+            yield ChatEvent::Start {
                 cone_id,
-                new_head: if is_ephemeral { cone.head } else { new_head },
-                usage: usage_info,
+                user_position: cone.head.advance(user_node_id),
             };
+
+            let mut llm_messages = messages;
+            llm_messages.push(ContextMessage::prompt(user_node_id, new_prompt.clone()));
+
+            let original_head = cone.head;
+            let mut reply = Box::pin(respond(writer, llm_registry, hub, tool_specs, cone, user_node_id, llm_messages));
+
+            while let Some(event) = reply.next().await {
+                match event {
+                    ChatEvent::Complete { response, usage, .. } => {
+                        let new_head = match settle_head(&storage, &cone_id, original_head, response, true).await {
+                            Ok(head) => head,
+                            Err(e) => {
+                                yield ChatEvent::Error { message: format!("Failed to update head: {}", e.message) };
+                                return;
+                            }
+                        };
+
+                        yield ChatEvent::Complete { cone_id, new_head, response, usage };
+                    }
+                    event => yield event,
+                }
+            }
         }
     }
 
@@ -631,12 +717,429 @@ impl<P: HubContext> Cone<P> {
     }
 }
 
-/// Resolve arbor context path to cllient messages by resolving handles
-async fn resolve_context_to_messages(
+/// Where a chat turn writes its messages and nodes
+struct TurnWriter {
+    storage: Arc<ConeStorage>,
+    cone_id: ConeId,
+    tree_id: TreeId,
+    /// Ephemeral turns are marked for deletion as they are written
+    ephemeral: bool,
+}
+
+impl TurnWriter {
+    /// Store a message in the cone database
+    async fn message(
+        &self,
+        role: MessageRole,
+        content: String,
+        model_id: Option<String>,
+        input_tokens: Option<i64>,
+        output_tokens: Option<i64>,
+    ) -> Result<super::types::Message, ConeError> {
+        if self.ephemeral {
+            self.storage
+                .message_create_ephemeral(&self.cone_id, role, content, model_id, input_tokens, output_tokens)
+                .await
+        } else {
+            self.storage
+                .message_create(&self.cone_id, role, content, model_id, input_tokens, output_tokens)
+                .await
+        }
+    }
+
+    /// Create an external node under `parent` with a handle to `message`
+    async fn node(
+        &self,
+        parent: NodeId,
+        message: &super::types::Message,
+        name: &str,
+    ) -> Result<NodeId, ArborError> {
+        let handle = ConeStorage::message_to_handle(message, name);
+        if self.ephemeral {
+            self.storage
+                .arbor()
+                .node_create_external_ephemeral(&self.tree_id, Some(parent), handle, None)
+                .await
+        } else {
+            self.storage
+                .arbor()
+                .node_create_external(&self.tree_id, Some(parent), handle, None)
+                .await
+        }
+    }
+}
+
+/// Stream the model's reply to `messages`, attaching it under `parent`
+///
+/// When the cone has tools and a hub parent is available, tool calls in the
+/// reply are executed and stored as `tool_use`/`tool_result` nodes, and the
+/// model is called again with their output, up to `MAX_TOOL_ROUNDS` times.
+/// A call made after the last round is not run; it is replaced with a note
+/// saying the limit was hit.
+/// The cone's context policy is applied to `messages` before the first call.
+/// Ends with `Complete` carrying the final assistant node; the caller decides
+/// whether to move the head there.
+fn respond<P: HubContext>(
+    writer: TurnWriter,
+    llm_registry: Arc<ModelRegistry>,
+    hub: Arc<OnceLock<P>>,
+    tool_specs: tools::ToolSpecCache,
+    cone: ConeConfig,
+    parent: NodeId,
+    messages: Vec<ContextMessage>,
+) -> impl Stream<Item = ChatEvent> + Send + 'static {
+    stream! {
+        let cone_id = cone.id;
+
+        let (summary, messages) = match context::apply_context_policy(&writer.storage, &llm_registry, &cone, messages, !writer.ephemeral).await {
+            Ok(applied) => applied,
+            Err(e) => {
+                yield ChatEvent::Error { message: format!("Failed to apply context policy: {}", e) };
+                return;
+            }
+        };
+
+        // Tools are only offered when the hub can run them
+        let tool_hub = hub.get().filter(|_| !cone.tools.is_empty());
+        let specs = match tool_hub {
+            Some(parent) => match tool_specs.get_or_load(parent, &cone.tools).await {
+                Ok(specs) => specs,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to load tools: {}", e) };
+                    return;
+                }
+            },
+            None => Vec::new(),
+        };
+
+        let sections: Vec<String> = cone
+            .system_prompt
+            .clone()
+            .into_iter()
+            .chain(summary.map(|s| format!("Summary of the earlier conversation:\n{}", s)))
+            .chain((!specs.is_empty()).then(|| tools::tools_prompt(&specs)))
+            .collect();
+        let system_prompt = (!sections.is_empty()).then(|| sections.join("\n\n"));
+
+        let mut llm_messages = messages;
+        let mut parent = parent;
+        let mut total_input: Option<i64> = None;
+        let mut total_output: Option<i64> = None;
+
+        for round in 0..=MAX_TOOL_ROUNDS {
+            let request_builder = match llm_registry.from_id(&cone.model_id) {
+                Ok(rb) => rb,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to create request builder: {}", e) };
+                    return;
+                }
+            };
+
+            let mut builder = request_builder;
+            if let Some(ref sys) = system_prompt {
+                builder = builder.system(sys);
+            }
+            builder = builder.messages(llm_messages.iter().map(ContextMessage::to_message).collect());
+
+            // Stream the response
+            let mut stream_result = match builder.stream().await {
+                Ok(s) => s,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to start LLM stream: {}", e) };
+                    return;
+                }
+            };
+
+            let mut full_response = String::new();
+            let mut input_tokens: Option<i64> = None;
+            let mut output_tokens: Option<i64> = None;
+
+            while let Some(event) = stream_result.next().await {
+                match event {
+                    Ok(cllient::streaming::StreamEvent::Content(text)) => {
+                        full_response.push_str(&text);
+                        yield ChatEvent::Content {
+                            cone_id,
+                            content: text,
+                        };
+                    }
+                    Ok(cllient::streaming::StreamEvent::Usage { input_tokens: inp, output_tokens: out, .. }) => {
+                        input_tokens = inp.map(|t| t as i64);
+                        output_tokens = out.map(|t| t as i64);
+                    }
+                    Ok(cllient::streaming::StreamEvent::Error(e)) => {
+                        yield ChatEvent::Error { message: format!("LLM error: {}", e) };
+                        return;
+                    }
+                    Ok(_) => {
+                        // Ignore other events (Start, Finish, Role, Raw)
+                    }
+                    Err(e) => {
+                        yield ChatEvent::Error { message: format!("Stream error: {}", e) };
+                        return;
+                    }
+                }
+            }
+
+            if let Some(t) = input_tokens {
+                total_input = Some(total_input.unwrap_or(0) + t);
+            }
+            if let Some(t) = output_tokens {
+                total_output = Some(total_output.unwrap_or(0) + t);
+            }
+
+            let call = match tool_hub {
+                Some(hub) if round < MAX_TOOL_ROUNDS => tools::parse_tool_call(&full_response).map(|c| (hub, c)),
+                _ => None,
+            };
+
+            let Some((tool_hub, call)) = call else {
+                // Out of tool rounds: the call is not run, so say so instead
+                // of storing the bare request as the answer
+                if let Some(kept) = tool_hub.and_then(|_| tools::strip_tool_call(&full_response)) {
+                    let note = tools::tool_limit_note();
+                    full_response = if kept.is_empty() { note.clone() } else { format!("{}\n\n{}", kept, note) };
+                    yield ChatEvent::Content { cone_id, content: format!("\n\n{}", note) };
+                }
+
+                // Final answer: store assistant response and its node
+                let assistant_message = match writer.message(
+                    MessageRole::Assistant,
+                    full_response,
+                    Some(cone.model_id.clone()),
+                    input_tokens,
+                    output_tokens,
+                ).await {
+                    Ok(msg) => msg,
+                    Err(e) => {
+                        yield ChatEvent::Error { message: format!("Failed to store assistant message: {}", e.message) };
+                        return;
+                    }
+                };
+
+                let response_node_id = match writer.node(parent, &assistant_message, &cone.name).await {
+                    Ok(id) => id,
+                    Err(e) => {
+                        yield ChatEvent::Error { message: format!("Failed to create response node: {}", e) };
+                        return;
+                    }
+                };
+
+                let usage = if total_input.is_some() || total_output.is_some() {
+                    Some(ChatUsage {
+                        input_tokens: total_input.map(|t| t as u64),
+                        output_tokens: total_output.map(|t| t as u64),
+                        total_tokens: total_input.and_then(|i| total_output.map(|o| (i + o) as u64)),
+                    })
+                } else {
+                    None
+                };
+
+                let response = cone.head.advance(response_node_id);
+                yield ChatEvent::Complete {
+                    cone_id,
+                    new_head: response,
+                    response,
+                    usage,
+                };
+                return;
+            };
+
+            // Tool call: store the reply that made it, run it, store the output
+            let tool_name = match &call {
+                Ok(call) => call.name.clone(),
+                Err(_) => "invalid".to_string(),
+            };
+
+            let use_message = match writer.message(
+                MessageRole::ToolUse,
+                full_response.clone(),
+                Some(cone.model_id.clone()),
+                input_tokens,
+                output_tokens,
+            ).await {
+                Ok(msg) => msg,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to store tool call: {}", e.message) };
+                    return;
+                }
+            };
+            let use_node_id = match writer.node(parent, &use_message, &tool_name).await {
+                Ok(id) => id,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to create tool call node: {}", e) };
+                    return;
+                }
+            };
+
+            let tool_use_id = use_message.id.to_string();
+            yield ChatEvent::ToolUse {
+                cone_id,
+                tool_name: tool_name.clone(),
+                tool_use_id: tool_use_id.clone(),
+                input: call.as_ref().map(|c| c.arguments.clone()).unwrap_or_default(),
+                position: cone.head.advance(use_node_id),
+            };
+
+            // Only the cone's configured tools may run
+            let (output, is_error) = match &call {
+                Ok(call) if cone.tools.contains(&call.name) => tools::execute_tool(tool_hub, call).await,
+                Ok(call) => (format!("Tool not available: {}", call.name), true),
+                Err(e) => (format!("Invalid tool call: {}", e), true),
+            };
+
+            let result_message = match writer.message(MessageRole::ToolResult, output.clone(), None, None, None).await {
+                Ok(msg) => msg,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to store tool result: {}", e.message) };
+                    return;
+                }
+            };
+            let result_node_id = match writer.node(use_node_id, &result_message, &tool_name).await {
+                Ok(id) => id,
+                Err(e) => {
+                    yield ChatEvent::Error { message: format!("Failed to create tool result node: {}", e) };
+                    return;
+                }
+            };
+
+            yield ChatEvent::ToolResult {
+                cone_id,
+                tool_use_id,
+                output: output.clone(),
+                is_error,
+                position: cone.head.advance(result_node_id),
+            };
+
+            llm_messages.push(ContextMessage::new(use_node_id, ContextRole::Assistant, full_response.clone()));
+            llm_messages.push(ContextMessage::new(
+                result_node_id,
+                ContextRole::User,
+                tools::format_tool_result(&tool_name, &output),
+            ));
+            parent = result_node_id;
+        }
+    }
+}
+
+/// Whether a node holds a user message written by Cone
+fn is_user_node(node: &Node) -> bool {
+    match &node.data {
+        NodeType::External { handle } => {
+            handle.plugin_id == Cone::<NoParent>::PLUGIN_ID
+                && handle.meta.get(1).map(String::as_str) == Some(MessageRole::User.as_str())
+        }
+        NodeType::Text { .. } => false,
+    }
+}
+
+/// The user message a regenerated answer replies to
+///
+/// Walks up from `from` to the nearest user message and returns it with the
+/// context path ending there. The new answer is attached under that message,
+/// next to the answers it already has.
+pub(super) async fn regenerate_target(
     storage: &ConeStorage,
+    tree_id: &TreeId,
+    from: &NodeId,
+) -> Result<(NodeId, Vec<Node>), String> {
+    let mut context_nodes = storage
+        .arbor()
+        .context_get_path(tree_id, from)
+        .await
+        .map_err(|e| format!("Failed to get context path: {}", e))?;
+
+    let user_index = context_nodes
+        .iter()
+        .rposition(is_user_node)
+        .ok_or_else(|| format!("No user message at or above node {}", from))?;
+    context_nodes.truncate(user_index + 1);
+
+    Ok((context_nodes[user_index].id, context_nodes))
+}
+
+/// Where an edited user message goes
+///
+/// Returns the parent of the user message at `node_id` with the context path
+/// ending there; the edit is attached under that parent as a sibling of the
+/// original, which stays in place. Fails for anything but a user message.
+pub(super) async fn edit_target(
+    storage: &ConeStorage,
+    tree_id: &TreeId,
+    node_id: &NodeId,
+) -> Result<(NodeId, Vec<Node>), String> {
+    let mut context_nodes = storage
+        .arbor()
+        .context_get_path(tree_id, node_id)
+        .await
+        .map_err(|e| format!("Failed to get context path: {}", e))?;
+
+    // The edited message is replaced, so context ends at its parent
+    match context_nodes.pop() {
+        Some(node) if is_user_node(&node) => {}
+        _ => return Err(format!("Node {} is not a user message", node_id)),
+    }
+    let parent_id = context_nodes
+        .last()
+        .map(|n| n.id)
+        .ok_or_else(|| format!("Node {} has no parent", node_id))?;
+
+    Ok((parent_id, context_nodes))
+}
+
+/// Move the cone's head to a finished reply if `move_head` is set
+///
+/// Returns the head the cone ends up with: `response` when it moved, the
+/// original head otherwise.
+pub(super) async fn settle_head(
+    storage: &ConeStorage,
+    cone_id: &ConeId,
+    original_head: Position,
+    response: Position,
+    move_head: bool,
+) -> Result<Position, ConeError> {
+    if !move_head {
+        return Ok(original_head);
+    }
+    storage.cone_update_head(cone_id, response.node_id).await?;
+    Ok(response)
+}
+
+/// Resolve a foreign handle through the hub to all of its data items
+async fn resolve_handle_items<P: HubContext>(parent: &P, handle: &Handle) -> Result<Vec<Value>, String> {
+    let mut stream = parent.resolve_handle(handle).await.map_err(|e| e.to_string())?;
+
+    let mut items = Vec::new();
+    while let Some(item) = stream.next().await {
+        match item {
+            // Resolvers report failures as `{"type": "error", "message": ...}` items
+            PlexusStreamItem::Data { content, .. } if content.get("type").and_then(Value::as_str) == Some("error") => {
+                let message = content.get("message").and_then(Value::as_str).unwrap_or("unknown error");
+                return Err(message.to_string());
+            }
+            PlexusStreamItem::Data { content, .. } => items.push(content),
+            PlexusStreamItem::Error { message, .. } => return Err(message),
+            PlexusStreamItem::Done { .. } => break,
+            _ => continue,
+        }
+    }
+
+    if items.is_empty() {
+        return Err("resolved to nothing".to_string());
+    }
+    Ok(items)
+}
+
+/// Resolve arbor context path to context messages by resolving handles
+///
+/// Cone's own messages are read from storage; other plugins' handles are
+/// resolved through the hub and mapped with their registered `ContextMapping`.
+pub(super) async fn resolve_context_to_messages<P: HubContext>(
+    storage: &ConeStorage,
+    parent: Option<&P>,
+    mappings: &ContextMappings,
     nodes: &[Node],
-    _system_prompt: &Option<String>,
-) -> Result<Vec<Message>, String> {
+) -> Result<Vec<ContextMessage>, String> {
     let mut messages = Vec::new();
 
     for node in nodes {
@@ -645,7 +1148,7 @@ async fn resolve_context_to_messages(
                 // Text nodes shouldn't exist in the new design, but handle gracefully
                 // Skip empty root nodes
                 if !content.is_empty() {
-                    messages.push(Message::user(content));
+                    messages.push(ContextMessage::prompt(node.id, content.clone()));
                 }
             }
             NodeType::External { handle } => {
@@ -659,25 +1162,39 @@ async fn resolve_context_to_messages(
                         .await
                         .map_err(|e| format!("Failed to resolve message handle: {}", e.message))?;
 
-                    let cllient_msg = match msg.role {
-                        MessageRole::User => Message::user(&msg.content),
-                        MessageRole::Assistant => Message::assistant(&msg.content),
-                        MessageRole::System => Message::system(&msg.content),
+                    let context_msg = match msg.role {
+                        MessageRole::User => ContextMessage::prompt(node.id, msg.content),
+                        MessageRole::Assistant | MessageRole::ToolUse => {
+                            ContextMessage::new(node.id, ContextRole::Assistant, msg.content)
+                        }
+                        MessageRole::System => {
+                            ContextMessage::new(node.id, ContextRole::System, msg.content)
+                        }
+                        MessageRole::ToolResult => {
+                            let tool_name = handle.meta.get(2).map(|s| s.as_str()).unwrap_or("tool");
+                            ContextMessage::new(
+                                node.id,
+                                ContextRole::User,
+                                tools::format_tool_result(tool_name, &msg.content),
+                            )
+                        }
                     };
-                    messages.push(cllient_msg);
-                } else if handle.plugin_id == Bash::PLUGIN_ID {
-                    // TODO: Resolve bash output when bash plugin integration is added
-                    let cmd_id = handle.meta.first().map(|s| s.as_str()).unwrap_or("unknown");
-                    messages.push(Message::user(&format!(
-                        "[Tool output from bash: {}]",
-                        cmd_id
-                    )));
+                    messages.push(context_msg);
                 } else {
-                    // Unknown handle plugin - include as reference using Display
-                    messages.push(Message::user(&format!(
-                        "[External reference: {}]",
-                        handle
-                    )));
+                    let resolved = match parent {
+                        Some(parent) => resolve_handle_items(parent, handle).await,
+                        None => Err("no hub to resolve through".to_string()),
+                    };
+                    let context_msg = match resolved {
+                        Ok(items) => mappings.get(&handle.plugin_id).to_context_message(node.id, &items),
+                        // Keep the node visible to the model even when it can't be read
+                        Err(reason) => ContextMessage::new(
+                            node.id,
+                            ContextRole::User,
+                            format!("[External reference: {} ({})]", handle, reason),
+                        ),
+                    };
+                    messages.push(context_msg);
                 }
             }
         }
@@ -0,0 +1,432 @@
+//! Context policies: how much of a cone's history goes into each request
+//!
+//! The root-to-head path is resolved into `ContextMessage`s, the cone's
+//! `ContextPolicy` trims them (or folds older turns into a summary), and only
+//! then are they turned into `cllient` messages.
+//!
+//! Handles from other plugins are resolved through the hub; each plugin can
+//! register a `ContextMapping` saying how its resolved items read as chat
+//! messages.
+
+use super::storage::ConeStorage;
+use super::types::{ConeConfig, ContextPolicy, Position};
+use crate::activations::arbor::{estimate_tokens, NodeId};
+use cllient::{Message, ModelRegistry};
+use futures::StreamExt;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+const SUMMARY_PROMPT: &str = "Summarize the conversation below so the summary can replace it in your \
+     context. Keep facts, decisions, open questions and anything the user asked you to remember. \
+     Reply with the summary only.";
+
+/// Chat role a context message is sent with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContextRole {
+    User,
+    Assistant,
+    System,
+}
+
+impl ContextRole {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContextRole::User => "user",
+            ContextRole::Assistant => "assistant",
+            ContextRole::System => "system",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "user" => Some(ContextRole::User),
+            "assistant" => Some(ContextRole::Assistant),
+            "system" => Some(ContextRole::System),
+            _ => None,
+        }
+    }
+}
+
+/// One message of a cone's context, tied to the node it came from
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContextMessage {
+    pub node_id: NodeId,
+    pub role: ContextRole,
+    pub content: String,
+    /// User prompts start a turn; tool output sent as a user message does not
+    pub starts_turn: bool,
+}
+
+impl ContextMessage {
+    pub fn new(node_id: NodeId, role: ContextRole, content: impl Into<String>) -> Self {
+        Self {
+            node_id,
+            role,
+            content: content.into(),
+            starts_turn: false,
+        }
+    }
+
+    /// A user prompt, which starts a new turn
+    pub fn prompt(node_id: NodeId, content: impl Into<String>) -> Self {
+        Self {
+            starts_turn: true,
+            ..Self::new(node_id, ContextRole::User, content)
+        }
+    }
+
+    pub fn to_message(&self) -> Message {
+        match self.role {
+            ContextRole::User => Message::user(&self.content),
+            ContextRole::Assistant => Message::assistant(&self.content),
+            ContextRole::System => Message::system(&self.content),
+        }
+    }
+
+    fn tokens(&self) -> usize {
+        estimate_tokens(self.content.len())
+    }
+}
+
+/// How a plugin's resolved handles become context messages
+///
+/// A handle resolves to a stream of JSON items. The text of the message is
+/// `content_field` of each item (items without it are skipped), and the role
+/// comes from `role_field` of the first item that has a known role.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextMapping {
+    /// Field holding an item's text
+    pub content_field: String,
+    /// Field holding the sender role ("user", "assistant" or "system")
+    pub role_field: Option<String>,
+    /// Role used when no item carries a known role
+    pub default_role: ContextRole,
+}
+
+impl Default for ContextMapping {
+    /// The `resolved_message` shape Cone and ClaudeCode resolve to
+    fn default() -> Self {
+        Self::new("content", ContextRole::User).with_role_field("role")
+    }
+}
+
+impl ContextMapping {
+    pub fn new(content_field: impl Into<String>, default_role: ContextRole) -> Self {
+        Self {
+            content_field: content_field.into(),
+            role_field: None,
+            default_role,
+        }
+    }
+
+    pub fn with_role_field(mut self, role_field: impl Into<String>) -> Self {
+        self.role_field = Some(role_field.into());
+        self
+    }
+
+    /// Build a node's context message from its resolved items
+    ///
+    /// A user message with an explicit role starts a turn; content that only
+    /// defaults to the user role (e.g. command output) does not. When no item
+    /// has `content_field`, the items are included as JSON.
+    pub fn to_context_message(&self, node_id: NodeId, items: &[Value]) -> ContextMessage {
+        let texts: Vec<String> = items
+            .iter()
+            .filter_map(|item| match item.get(&self.content_field)? {
+                Value::String(text) => Some(text.clone()),
+                other => Some(other.to_string()),
+            })
+            .collect();
+        let content = match (texts.is_empty(), items) {
+            (false, _) => texts.join("\n"),
+            (true, [item]) => item.to_string(),
+            (true, _) => Value::Array(items.to_vec()).to_string(),
+        };
+
+        let role = self.role_field.as_ref().and_then(|field| {
+            items
+                .iter()
+                .find_map(|item| item.get(field).and_then(Value::as_str).and_then(ContextRole::parse))
+        });
+
+        match role {
+            Some(ContextRole::User) => ContextMessage::prompt(node_id, content),
+            Some(role) => ContextMessage::new(node_id, role, content),
+            None => ContextMessage::new(node_id, self.default_role, content),
+        }
+    }
+}
+
+/// Context mappings registered by plugins, keyed by plugin id
+#[derive(Clone, Default)]
+pub struct ContextMappings {
+    inner: Arc<RwLock<HashMap<uuid::Uuid, ContextMapping>>>,
+}
+
+impl ContextMappings {
+    pub fn register(&self, plugin_id: uuid::Uuid, mapping: ContextMapping) {
+        self.inner
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(plugin_id, mapping);
+    }
+
+    /// The plugin's mapping, or the default one
+    pub fn get(&self, plugin_id: &uuid::Uuid) -> ContextMapping {
+        self.inner
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(plugin_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// Apply a cone's context policy
+///
+/// Returns the messages to send and, for `Summarize`, a summary of the
+/// dropped turns to add to the system prompt. New summaries are only cached
+/// when `cache_summary` is set, so ephemeral turns leave nothing behind.
+pub async fn apply_context_policy(
+    storage: &ConeStorage,
+    llm_registry: &ModelRegistry,
+    cone: &ConeConfig,
+    mut messages: Vec<ContextMessage>,
+    cache_summary: bool,
+) -> Result<(Option<String>, Vec<ContextMessage>), String> {
+    let start = match cone.context_policy {
+        ContextPolicy::Full => 0,
+        ContextPolicy::MaxTokens { max_tokens } => max_tokens_start(&messages, max_tokens),
+        ContextPolicy::LastTurns { turns } | ContextPolicy::Summarize { keep_turns: turns } => {
+            last_turns_start(&messages, turns)
+        }
+    };
+    if start == 0 {
+        return Ok((None, messages));
+    }
+
+    let kept = messages.split_off(start);
+    match cone.context_policy {
+        ContextPolicy::Summarize { keep_turns } => {
+            let (summary, covered) =
+                summarize_turns(storage, llm_registry, cone, &messages, keep_turns, cache_summary).await?;
+            // Older turns the summary does not cover yet are sent in full
+            let mut sent = messages.split_off(covered);
+            sent.extend(kept);
+            Ok((Some(summary), sent))
+        }
+        _ => Ok((None, kept)),
+    }
+}
+
+fn turn_starts(messages: &[ContextMessage]) -> Vec<usize> {
+    messages
+        .iter()
+        .enumerate()
+        .filter(|(_, m)| m.starts_turn)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Index where the last `turns` turns begin (0 when there are no more than that)
+pub(super) fn last_turns_start(messages: &[ContextMessage], turns: usize) -> usize {
+    let starts = turn_starts(messages);
+    if starts.len() <= turns.max(1) {
+        0
+    } else {
+        starts[starts.len() - turns.max(1)]
+    }
+}
+
+/// Index of the earliest turn from which the messages fit in `max_tokens`
+///
+/// The last turn is kept even when it alone is over budget.
+pub(super) fn max_tokens_start(messages: &[ContextMessage], max_tokens: usize) -> usize {
+    let starts = turn_starts(messages);
+    let Some(&last) = starts.last() else {
+        return 0;
+    };
+
+    let mut kept: usize = messages.iter().map(ContextMessage::tokens).sum();
+    let mut cut = 0;
+    for start in starts {
+        if kept <= max_tokens || cut == last {
+            break;
+        }
+        kept -= messages[cut..start].iter().map(ContextMessage::tokens).sum::<usize>();
+        cut = start;
+    }
+    cut
+}
+
+/// Summarize `older`, reusing and extending the newest cached summary
+///
+/// A cached summary that misses fewer than `keep_turns` turns is reused as
+/// is and the turns it misses are sent in full, so the model is asked for a
+/// new summary once every `keep_turns` turns rather than on every turn.
+/// Returns the summary and how many messages of `older` it covers.
+async fn summarize_turns(
+    storage: &ConeStorage,
+    llm_registry: &ModelRegistry,
+    cone: &ConeConfig,
+    older: &[ContextMessage],
+    keep_turns: usize,
+    cache: bool,
+) -> Result<(String, usize), String> {
+    let (previous, from) = match find_cached_summary(storage, cone, older).await? {
+        Some((end, summary)) if turn_starts(&older[end + 1..]).len() < keep_turns.max(1) => {
+            return Ok((summary, end + 1));
+        }
+        Some((end, summary)) => (Some(summary), end + 1),
+        None => (None, 0),
+    };
+
+    let summary = complete_summary(llm_registry, &cone.model_id, previous.as_deref(), &older[from..]).await?;
+
+    if cache {
+        let covers = older.last().map(|m| m.node_id).ok_or("Nothing to summarize")?;
+        storage
+            .summary_store(&cone.id, &Position::new(cone.head.tree_id, covers), &summary, Some(&cone.model_id))
+            .await
+            .map_err(|e| e.message)?;
+    }
+
+    Ok((summary, older.len()))
+}
+
+/// Find the newest cached summary ending at a node of `older`
+///
+/// Summaries always end right before a turn, so only those nodes are
+/// considered; all of them are read with a single path lookup. Returns the
+/// index of the last message the summary covers and its text.
+pub(super) async fn find_cached_summary(
+    storage: &ConeStorage,
+    cone: &ConeConfig,
+    older: &[ContextMessage],
+) -> Result<Option<(usize, String)>, String> {
+    let Some(last) = older.last() else {
+        return Ok(None);
+    };
+    let ends: Vec<usize> = (0..older.len())
+        .filter(|&i| i + 1 == older.len() || older[i + 1].starts_turn)
+        .collect();
+
+    let mut summaries = storage
+        .summaries_get(&cone.id, &Position::new(cone.head.tree_id, last.node_id))
+        .await
+        .map_err(|e| format!("Failed to look up summaries: {}", e.message))?;
+
+    Ok(ends
+        .into_iter()
+        .rev()
+        .find_map(|end| summaries.remove(&older[end].node_id).map(|summary| (end, summary))))
+}
+
+/// Ask the cone's model for a summary of `messages`, continuing `previous`
+async fn complete_summary(
+    llm_registry: &ModelRegistry,
+    model_id: &str,
+    previous: Option<&str>,
+    messages: &[ContextMessage],
+) -> Result<String, String> {
+    let mut transcript = String::new();
+    if let Some(previous) = previous {
+        transcript.push_str(&format!("Summary of the earlier conversation:\n{}\n\n", previous));
+    }
+    for message in messages {
+        transcript.push_str(&format!("[{}] {}\n\n", message.role.as_str(), message.content));
+    }
+
+    let builder = llm_registry
+        .from_id(model_id)
+        .map_err(|e| format!("Failed to create request builder: {}", e))?
+        .system(SUMMARY_PROMPT)
+        .messages(vec![Message::user(&transcript)]);
+
+    let mut stream = builder
+        .stream()
+        .await
+        .map_err(|e| format!("Failed to start LLM stream: {}", e))?;
+
+    let mut summary = String::new();
+    while let Some(event) = stream.next().await {
+        match event {
+            Ok(cllient::streaming::StreamEvent::Content(text)) => summary.push_str(&text),
+            Ok(cllient::streaming::StreamEvent::Error(e)) => return Err(format!("LLM error: {}", e)),
+            Ok(_) => {}
+            Err(e) => return Err(format!("Stream error: {}", e)),
+        }
+    }
+
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// user/assistant pairs, one turn each, `len` bytes per message
+    fn turns(count: usize, len: usize) -> Vec<ContextMessage> {
+        (0..count)
+            .flat_map(|_| {
+                [
+                    ContextMessage::prompt(NodeId::new(), "q".repeat(len)),
+                    ContextMessage::new(NodeId::new(), ContextRole::Assistant, "a".repeat(len)),
+                ]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_last_turns_start() {
+        let mut messages = turns(3, 4);
+        assert_eq!(last_turns_start(&messages, 2), 2);
+        assert_eq!(last_turns_start(&messages, 3), 0);
+
+        // Tool output does not start a turn
+        messages.insert(3, ContextMessage::new(NodeId::new(), ContextRole::User, "tool output"));
+        assert_eq!(last_turns_start(&messages, 1), 5);
+    }
+
+    #[test]
+    fn test_max_tokens_start() {
+        // 3 turns of 2 tokens per message = 4 tokens per turn
+        let messages = turns(3, 8);
+        assert_eq!(max_tokens_start(&messages, 12), 0);
+        assert_eq!(max_tokens_start(&messages, 11), 2);
+        assert_eq!(max_tokens_start(&messages, 8), 2);
+        // The current turn stays even when it is over budget
+        assert_eq!(max_tokens_start(&messages, 1), 4);
+        assert_eq!(max_tokens_start(&[], 1), 0);
+    }
+
+    #[test]
+    fn test_context_mapping() {
+        let node = NodeId::new();
+
+        // resolved_message items keep their role; a user message starts a turn
+        let message = json!({"type": "resolved_message", "role": "assistant", "content": "Done."});
+        let resolved = ContextMapping::default().to_context_message(node, &[message]);
+        assert_eq!(resolved, ContextMessage::new(node, ContextRole::Assistant, "Done."));
+        let prompt = json!({"type": "resolved_message", "role": "user", "content": "Go"});
+        assert!(ContextMapping::default().to_context_message(node, &[prompt]).starts_turn);
+
+        // Line items are joined; items without the field are skipped
+        let output = [json!({"type": "stdout", "line": "a"}), json!({"type": "stdout", "line": "b"}), json!({"type": "exit", "code": 0})];
+        let resolved = ContextMapping::new("line", ContextRole::User).to_context_message(node, &output);
+        assert_eq!(resolved, ContextMessage::new(node, ContextRole::User, "a\nb"));
+
+        // No text at all: the item itself is the content
+        let other = json!({"type": "file", "path": "a.rs"});
+        let resolved = ContextMapping::default().to_context_message(node, &[other.clone()]);
+        assert_eq!(resolved.content, other.to_string());
+
+        let mappings = ContextMappings::default();
+        let plugin = uuid::Uuid::new_v4();
+        mappings.register(plugin, ContextMapping::new("line", ContextRole::System));
+        assert_eq!(mappings.get(&plugin).default_role, ContextRole::System);
+        assert_eq!(mappings.get(&uuid::Uuid::new_v4()), ContextMapping::default());
+    }
+}
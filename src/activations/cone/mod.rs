@@ -1,12 +1,15 @@
 mod activation;
+mod context;
 mod methods;
 mod storage;
+mod tools;
 mod types;
 
 #[cfg(test)]
 mod tests;
 
 pub use activation::{Cone, ConeMethod};
+pub use context::{ContextMapping, ContextRole};
 pub use methods::ConeIdentifier;
 pub use storage::{ConeStorage, ConeStorageConfig};
 pub use types::{
@@ -14,7 +17,7 @@ pub use types::{
     ChatEvent, CreateResult, DeleteResult, GetResult, ListResult,
     RegistryResult, ResolveResult, SetHeadResult,
     // Shared types
-    ChatUsage, ConeConfig, ConeError, ConeId, ConeInfo,
+    ChatUsage, ConeConfig, ConeError, ConeId, ConeInfo, ContextPolicy,
     Message, MessageId, MessageRole, Position,
     // Handle types
     ConeHandle,
@@ -1,7 +1,9 @@
 use super::methods::ConeIdentifier;
-use super::types::{ConeConfig, ConeError, ConeHandle, ConeId, ConeInfo, Message, MessageId, MessageRole, Position};
-use crate::activations::arbor::{ArborStorage, NodeId, TreeId};
+use super::types::{ConeConfig, ConeError, ConeHandle, ConeId, ConeInfo, ContextPolicy, Message, MessageId, MessageRole, Position};
+use crate::activations::arbor::{ArborBackend, NodeId, TreeId};
+use std::collections::HashMap;
 use serde_json::Value;
+use crate::migrations::{self, Migration};
 use sqlx::{sqlite::{SqliteConnectOptions, SqlitePool}, ConnectOptions, Row};
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -23,34 +25,15 @@ impl Default for ConeStorageConfig {
     }
 }
 
-/// Storage layer for cone configurations
-pub struct ConeStorage {
-    pool: SqlitePool,
-    arbor: Arc<ArborStorage>,
-}
-
-impl ConeStorage {
-    /// Create a new cone storage instance with a shared Arbor storage
-    pub async fn new(config: ConeStorageConfig, arbor: Arc<ArborStorage>) -> Result<Self, ConeError> {
-        // Initialize cone database
-        let db_url = format!("sqlite:{}?mode=rwc", config.db_path.display());
-        let mut connect_options: SqliteConnectOptions = db_url.parse()
-            .map_err(|e| format!("Failed to parse database URL: {}", e))?;
-        connect_options.disable_statement_logging();
-        let pool = SqlitePool::connect_with(connect_options.clone())
-            .await
-            .map_err(|e| format!("Failed to connect to cone database: {}", e))?;
-
-        let storage = Self { pool, arbor };
-        storage.run_migrations().await?;
-
-        Ok(storage)
-    }
+/// Arbor node metadata key holding cached context summaries, by cone ID
+const SUMMARY_METADATA_KEY: &str = "cone_summaries";
 
-    /// Run database migrations
-    async fn run_migrations(&self) -> Result<(), ConeError> {
-        sqlx::query(
-            r#"
+/// Cone schema migrations, applied in order by `run_migrations`
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "cones, messages",
+        sql: r#"
             CREATE TABLE IF NOT EXISTS cones (
                 id TEXT PRIMARY KEY,
                 name TEXT NOT NULL UNIQUE,
@@ -78,17 +61,56 @@ impl ConeStorage {
             CREATE INDEX IF NOT EXISTS idx_cones_name ON cones(name);
             CREATE INDEX IF NOT EXISTS idx_cones_tree ON cones(tree_id);
             CREATE INDEX IF NOT EXISTS idx_messages_cone ON messages(cone_id);
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to run cone migrations: {}", e))?;
+        "#,
+    },
+    Migration {
+        version: 2,
+        description: "cone tools",
+        sql: r#"
+            ALTER TABLE cones ADD COLUMN tools TEXT NOT NULL DEFAULT '[]';
+        "#,
+    },
+    Migration {
+        version: 3,
+        description: "cone context policy",
+        sql: r#"
+            ALTER TABLE cones ADD COLUMN context_policy TEXT NOT NULL DEFAULT '{"type":"full"}';
+        "#,
+    },
+];
 
+/// Storage layer for cone configurations
+pub struct ConeStorage {
+    pool: SqlitePool,
+    arbor: Arc<dyn ArborBackend>,
+}
+
+impl ConeStorage {
+    /// Create a new cone storage instance with a shared Arbor storage
+    pub async fn new(config: ConeStorageConfig, arbor: Arc<dyn ArborBackend>) -> Result<Self, ConeError> {
+        // Initialize cone database
+        let db_url = format!("sqlite:{}?mode=rwc", config.db_path.display());
+        let mut connect_options: SqliteConnectOptions = db_url.parse()
+            .map_err(|e| format!("Failed to parse database URL: {}", e))?;
+        connect_options.disable_statement_logging();
+        let pool = SqlitePool::connect_with(connect_options.clone())
+            .await
+            .map_err(|e| format!("Failed to connect to cone database: {}", e))?;
+
+        let storage = Self { pool, arbor };
+        storage.run_migrations().await?;
+
+        Ok(storage)
+    }
+
+    /// Run database migrations
+    async fn run_migrations(&self) -> Result<(), ConeError> {
+        migrations::run_migrations(&self.pool, "cone", MIGRATIONS).await?;
         Ok(())
     }
 
-    /// Get access to the underlying arbor storage
-    pub fn arbor(&self) -> &ArborStorage {
+    /// Get access to the underlying arbor backend
+    pub fn arbor(&self) -> &dyn ArborBackend {
         &self.arbor
     }
 
@@ -105,6 +127,8 @@ impl ConeStorage {
         name: String,
         model_id: String,
         system_prompt: Option<String>,
+        tools: Vec<String>,
+        context_policy: ContextPolicy,
         metadata: Option<Value>,
     ) -> Result<ConeConfig, ConeError> {
         let cone_id = ConeId::new_v4();
@@ -120,11 +144,13 @@ impl ConeStorage {
         let head = Position::new(tree_id, tree.root);
 
         let metadata_json = metadata.as_ref().map(|m| serde_json::to_string(m).unwrap());
+        let tools_json = serde_json::to_string(&tools).unwrap();
+        let policy_json = serde_json::to_string(&context_policy).unwrap();
 
         // Try inserting with the original name first
         let final_name = match sqlx::query(
-            "INSERT INTO cones (id, name, model_id, system_prompt, tree_id, canonical_head, metadata, created_at, updated_at)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO cones (id, name, model_id, system_prompt, tree_id, canonical_head, tools, context_policy, metadata, created_at, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
         )
         .bind(cone_id.to_string())
         .bind(&name)
@@ -132,6 +158,8 @@ impl ConeStorage {
         .bind(&system_prompt)
         .bind(head.tree_id.to_string())
         .bind(head.node_id.to_string())
+        .bind(&tools_json)
+        .bind(&policy_json)
         .bind(metadata_json.clone())
         .bind(now)
         .bind(now)
@@ -143,8 +171,8 @@ impl ConeStorage {
                 let unique_name = format!("{}#{}", name, cone_id);
 
                 sqlx::query(
-                    "INSERT INTO cones (id, name, model_id, system_prompt, tree_id, canonical_head, metadata, created_at, updated_at)
-                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                    "INSERT INTO cones (id, name, model_id, system_prompt, tree_id, canonical_head, tools, context_policy, metadata, created_at, updated_at)
+                     VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
                 )
                 .bind(cone_id.to_string())
                 .bind(&unique_name)
@@ -152,6 +180,8 @@ impl ConeStorage {
                 .bind(&system_prompt)
                 .bind(head.tree_id.to_string())
                 .bind(head.node_id.to_string())
+                .bind(&tools_json)
+                .bind(&policy_json)
                 .bind(metadata_json)
                 .bind(now)
                 .bind(now)
@@ -170,6 +200,8 @@ impl ConeStorage {
             model_id,
             system_prompt,
             head,
+            tools,
+            context_policy,
             metadata,
             created_at: now,
             updated_at: now,
@@ -232,7 +264,7 @@ impl ConeStorage {
     /// Get a cone by ID
     pub async fn cone_get(&self, cone_id: &ConeId) -> Result<ConeConfig, ConeError> {
         let row = sqlx::query(
-            "SELECT id, name, model_id, system_prompt, tree_id, canonical_head, metadata, created_at, updated_at
+            "SELECT id, name, model_id, system_prompt, tree_id, canonical_head, tools, context_policy, metadata, created_at, updated_at
              FROM cones WHERE id = ?",
         )
         .bind(cone_id.to_string())
@@ -241,7 +273,21 @@ impl ConeStorage {
         .map_err(|e| format!("Failed to fetch cone: {}", e))?
         .ok_or_else(|| format!("Cone not found: {}", cone_id))?;
 
-        self.row_to_cone_config(row)
+        let mut cone = self.row_to_cone_config(row)?;
+
+        // Follow copy-on-write updates of the head node so the cone keeps
+        // working after its head is edited in Arbor
+        let current = self
+            .arbor
+            .node_current_version(&cone.head.tree_id, &cone.head.node_id)
+            .await
+            .map_err(|e| format!("Failed to resolve cone head: {}", e))?;
+        if current != cone.head.node_id {
+            self.cone_update_head(cone_id, current).await?;
+            cone.head = cone.head.advance(current);
+        }
+
+        Ok(cone)
     }
 
     /// Get a cone by identifier (name or UUID)
@@ -495,6 +541,66 @@ impl ConeStorage {
         }.to_handle()
     }
 
+    // ========================================================================
+    // Summary Operations
+    // ========================================================================
+
+    /// Cache a summary of the conversation up to and including `at`
+    ///
+    /// The summary is stored in the metadata of the arbor node it ends at,
+    /// under `cone_summaries.{cone_id}`.
+    pub async fn summary_store(
+        &self,
+        cone_id: &ConeId,
+        at: &Position,
+        content: &str,
+        model_id: Option<&str>,
+    ) -> Result<(), ConeError> {
+        let patch = serde_json::json!({
+            SUMMARY_METADATA_KEY: {
+                cone_id.to_string(): {
+                    "content": content,
+                    "model_id": model_id,
+                    "created_at": current_timestamp(),
+                }
+            }
+        });
+        self.arbor
+            .node_update_metadata(&at.tree_id, &at.node_id, patch)
+            .await
+            .map_err(|e| format!("Failed to store summary: {}", e.message))?;
+
+        Ok(())
+    }
+
+    /// Cached summaries on the path from the root to `at`, keyed by the node
+    /// each one ends at
+    pub async fn summaries_get(
+        &self,
+        cone_id: &ConeId,
+        at: &Position,
+    ) -> Result<HashMap<NodeId, String>, ConeError> {
+        let path = self
+            .arbor
+            .context_get_path(&at.tree_id, &at.node_id)
+            .await
+            .map_err(|e| format!("Failed to fetch summaries: {}", e.message))?;
+
+        let cone_key = cone_id.to_string();
+        Ok(path
+            .into_iter()
+            .filter_map(|node| {
+                let metadata = node.metadata?;
+                let content = metadata
+                    .get(SUMMARY_METADATA_KEY)?
+                    .get(&cone_key)?
+                    .get("content")?
+                    .as_str()?;
+                Some((node.id, content.to_string()))
+            })
+            .collect())
+    }
+
     // ========================================================================
     // Helper methods
     // ========================================================================
@@ -521,6 +627,8 @@ impl ConeStorage {
         let tree_id_str: String = row.get("tree_id");
         let head_str: String = row.get("canonical_head");
         let metadata_json: Option<String> = row.get("metadata");
+        let tools_json: String = row.get("tools");
+        let policy_json: String = row.get("context_policy");
 
         let tree_id = TreeId::parse_str(&tree_id_str).map_err(|e| format!("Invalid tree ID: {}", e))?;
         let node_id = NodeId::parse_str(&head_str).map_err(|e| format!("Invalid node ID: {}", e))?;
@@ -531,6 +639,9 @@ impl ConeStorage {
             model_id: row.get("model_id"),
             system_prompt: row.get("system_prompt"),
             head: Position::new(tree_id, node_id),
+            tools: serde_json::from_str(&tools_json).map_err(|e| format!("Invalid tools: {}", e))?,
+            context_policy: serde_json::from_str(&policy_json)
+                .map_err(|e| format!("Invalid context policy: {}", e))?,
             metadata: metadata_json.and_then(|s| serde_json::from_str(&s).ok()),
             created_at: row.get("created_at"),
             updated_at: row.get("updated_at"),
@@ -607,6 +718,8 @@ mod tests {
             (MessageRole::User, "user"),
             (MessageRole::Assistant, "assistant"),
             (MessageRole::System, "system"),
+            (MessageRole::ToolUse, "tool_use"),
+            (MessageRole::ToolResult, "tool_result"),
         ] {
             let message = Message {
                 id: Uuid::new_v4(),
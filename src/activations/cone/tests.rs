@@ -55,11 +55,11 @@ fn test_method_specific_return_types() {
     let list_variants = list_returns.get("oneOf").and_then(|v| v.as_array()).unwrap();
     assert_eq!(list_variants.len(), 2, "ListResult should have 2 variants");
 
-    // chat -> ChatEvent (4 variants: Start, Content, Complete, Error)
+    // chat -> ChatEvent (6 variants: Start, Content, ToolUse, ToolResult, Complete, Error)
     let chat = method_schemas.iter().find(|m| m.name == "chat").unwrap();
     let chat_returns = serde_json::to_value(chat.returns.as_ref().unwrap()).unwrap();
     let chat_variants = chat_returns.get("oneOf").and_then(|v| v.as_array()).unwrap();
-    assert_eq!(chat_variants.len(), 4, "ChatEvent should have 4 variants");
+    assert_eq!(chat_variants.len(), 6, "ChatEvent should have 6 variants");
 
     // registry -> RegistryResult (1 variant: Registry)
     let registry = method_schemas.iter().find(|m| m.name == "registry").unwrap();
@@ -120,6 +120,8 @@ async fn test_cone_message_to_arbor_handle_direct() {
             "test-assistant".to_string(),
             "gpt-4o-mini".to_string(),
             Some("You are a helpful assistant.".to_string()),
+            vec![],
+            ContextPolicy::Full,
             None,
         )
         .await
@@ -246,6 +248,8 @@ async fn test_multi_turn_conversation() {
             "multi-turn-test".to_string(),
             "claude-3-haiku".to_string(),
             None,
+            vec![],
+            ContextPolicy::Full,
             None,
         )
         .await
@@ -338,6 +342,8 @@ async fn test_cone_uses_arbor_directly() {
             "test-cone".to_string(),
             "gpt-4".to_string(),
             None,
+            vec![],
+            ContextPolicy::Full,
             None,
         )
         .await
@@ -407,6 +413,8 @@ async fn test_render_resolved_with_mock_resolver() {
             "resolved-test".to_string(),
             "gpt-4".to_string(),
             None,
+            vec![],
+            ContextPolicy::Full,
             None,
         )
         .await
@@ -481,3 +489,410 @@ async fn test_render_resolved_with_mock_resolver() {
     let lines: Vec<&str> = rendered.lines().collect();
     assert!(lines.len() >= 3, "Should have at least 3 lines (root + 2 messages)");
 }
+
+// ============================================================================
+// Foreign handles in context
+// ============================================================================
+
+/// Hub that resolves a fixed set of handles to canned items
+#[derive(Clone)]
+struct MockHub {
+    items: Arc<std::collections::HashMap<String, Vec<serde_json::Value>>>,
+}
+
+#[async_trait::async_trait]
+impl crate::plexus::HubContext for MockHub {
+    async fn resolve_handle(
+        &self,
+        handle: &crate::types::Handle,
+    ) -> Result<crate::plexus::PlexusStream, crate::plexus::PlexusError> {
+        let items = self
+            .items
+            .get(&handle.to_string())
+            .cloned()
+            .ok_or_else(|| crate::plexus::PlexusError::ExecutionError(format!("No resolver for {}", handle)))?;
+        Ok(crate::plexus::wrap_stream(futures::stream::iter(items), "mock.resolve_handle", vec!["mock".into()]))
+    }
+
+    async fn call(
+        &self,
+        method: &str,
+        _params: serde_json::Value,
+    ) -> Result<crate::plexus::PlexusStream, crate::plexus::PlexusError> {
+        Err(crate::plexus::PlexusError::ExecutionError(format!("No method {}", method)))
+    }
+}
+
+#[tokio::test]
+async fn test_context_resolves_foreign_handles_through_hub() {
+    use super::activation::resolve_context_to_messages;
+    use super::context::{ContextMappings, ContextMessage};
+    use crate::types::Handle;
+    use serde_json::json;
+
+    let (cone_storage, arbor, _dir) = create_test_storage().await;
+    let cone = cone_storage
+        .cone_create("resolver".to_string(), "gpt-4".to_string(), None, vec![], ContextPolicy::Full, None)
+        .await
+        .unwrap();
+    let tree_id = cone.head.tree_id;
+
+    let chat_plugin = uuid::Uuid::new_v4();
+    let shell_plugin = uuid::Uuid::new_v4();
+    let message = Handle::new(chat_plugin, "1.0.0".to_string(), "chat".to_string()).with_meta(vec!["m1".to_string()]);
+    let output = Handle::new(shell_plugin, "1.0.0".to_string(), "execute".to_string()).with_meta(vec!["run1".to_string()]);
+    let failing = Handle::new(chat_plugin, "1.0.0".to_string(), "chat".to_string()).with_meta(vec!["gone".to_string()]);
+    let unknown = Handle::new(shell_plugin, "1.0.0".to_string(), "execute".to_string()).with_meta(vec!["run2".to_string()]);
+
+    let hub = MockHub {
+        items: Arc::new(
+            [
+                (message.to_string(), vec![json!({"type": "resolved_message", "role": "assistant", "content": "From chat"})]),
+                (output.to_string(), vec![json!({"type": "stdout", "line": "a"}), json!({"type": "stdout", "line": "b"})]),
+                (failing.to_string(), vec![json!({"type": "error", "message": "message deleted"})]),
+            ]
+            .into_iter()
+            .collect(),
+        ),
+    };
+
+    // root -> cone prompt -> chat message -> shell output -> failing -> unknown
+    let prompt = append_message(&cone_storage, &arbor, &cone, cone.head.node_id, MessageRole::User, "Run it").await;
+    let mut parent = prompt;
+    let mut foreign = Vec::new();
+    for handle in [&message, &output, &failing, &unknown] {
+        parent = arbor.node_create_external(&tree_id, Some(parent), handle.clone(), None).await.unwrap();
+        foreign.push(parent);
+    }
+    let nodes = arbor.context_get_path(&tree_id, &parent).await.unwrap();
+
+    let mappings = ContextMappings::default();
+    mappings.register(shell_plugin, ContextMapping::new("line", ContextRole::User));
+    let messages = resolve_context_to_messages(&cone_storage, Some(&hub), &mappings, &nodes).await.unwrap();
+
+    assert_eq!(messages.len(), 5, "the empty root is skipped");
+    assert_eq!(messages[0], ContextMessage::prompt(prompt, "Run it"));
+    // Default mapping reads resolved_message items
+    assert_eq!(messages[1], ContextMessage::new(foreign[0], ContextRole::Assistant, "From chat"));
+    // Registered mappings pick their own field; output does not start a turn
+    assert_eq!(messages[2], ContextMessage::new(foreign[1], ContextRole::User, "a\nb"));
+    // Handles that fail to resolve stay visible as references
+    for (message, (node, reason)) in messages[3..].iter().zip([(foreign[2], "message deleted"), (foreign[3], "No resolver")]) {
+        assert_eq!(message.node_id, node);
+        assert!(message.content.starts_with("[External reference:"), "{}", message.content);
+        assert!(message.content.contains(reason), "{}", message.content);
+    }
+}
+
+// ============================================================================
+// Tools
+// ============================================================================
+
+#[tokio::test]
+async fn test_cone_tools_and_tool_messages() {
+    let (cone_storage, arbor, _dir) = create_test_storage().await;
+
+    let tools = vec!["bash.execute".to_string(), "arbor.search".to_string()];
+    let cone = cone_storage
+        .cone_create("agent".to_string(), "gpt-4".to_string(), None, tools.clone(), ContextPolicy::Full, None)
+        .await
+        .unwrap();
+    assert_eq!(cone.tools, tools);
+    assert_eq!(cone_storage.cone_get(&cone.id).await.unwrap().tools, tools);
+
+    // A tool call and its result are separate nodes named after the tool
+    let call = cone_storage
+        .message_create(
+            &cone.id,
+            MessageRole::ToolUse,
+            "<tool_call>{\"name\": \"bash.execute\", \"arguments\": {\"command\": \"ls\"}}</tool_call>".to_string(),
+            Some("gpt-4".to_string()),
+            None,
+            None,
+        )
+        .await
+        .unwrap();
+    let call_node = arbor
+        .node_create_external(
+            &cone.head.tree_id,
+            Some(cone.head.node_id),
+            ConeStorage::message_to_handle(&call, "bash.execute"),
+            None,
+        )
+        .await
+        .unwrap();
+    let result = cone_storage
+        .message_create(&cone.id, MessageRole::ToolResult, "Cargo.toml".to_string(), None, None, None)
+        .await
+        .unwrap();
+    let result_node = arbor
+        .node_create_external(
+            &cone.head.tree_id,
+            Some(call_node),
+            ConeStorage::message_to_handle(&result, "bash.execute"),
+            None,
+        )
+        .await
+        .unwrap();
+
+    let path = arbor.context_get_path(&cone.head.tree_id, &result_node).await.unwrap();
+    assert_eq!(path.len(), 3);
+    let crate::activations::arbor::NodeType::External { handle } = &path[2].data else {
+        panic!("tool result should be an external node");
+    };
+    assert_eq!(handle.meta[1], "tool_result");
+    assert_eq!(handle.meta[2], "bash.execute");
+
+    let resolved = cone_storage.resolve_message_handle(&handle.meta.join(":")).await.unwrap();
+    assert_eq!((resolved.role, resolved.content.as_str()), (MessageRole::ToolResult, "Cargo.toml"));
+}
+
+// ============================================================================
+// Context policies
+// ============================================================================
+
+#[tokio::test]
+async fn test_cone_context_policy_and_cached_summary() {
+    use super::context::{apply_context_policy, find_cached_summary, ContextMessage, ContextRole};
+
+    let (cone_storage, arbor, _dir) = create_test_storage().await;
+
+    let policy = ContextPolicy::Summarize { keep_turns: 2 };
+    let cone = cone_storage
+        .cone_create("summarizer".to_string(), "gpt-4".to_string(), None, vec![], policy.clone(), None)
+        .await
+        .unwrap();
+    assert_eq!(cone_storage.cone_get(&cone.id).await.unwrap().context_policy, policy);
+    assert!(ContextPolicy::LastTurns { turns: 0 }.validate().is_err());
+
+    // Four user/assistant turns
+    let mut messages = Vec::new();
+    let mut parent = cone.head.node_id;
+    for turn in 0..4 {
+        for role in [MessageRole::User, MessageRole::Assistant] {
+            let content = format!("{} {}", role.as_str(), turn);
+            let msg = cone_storage
+                .message_create(&cone.id, role, content.clone(), None, None, None)
+                .await
+                .unwrap();
+            parent = arbor
+                .node_create_external(
+                    &cone.head.tree_id,
+                    Some(parent),
+                    ConeStorage::message_to_handle(&msg, role.as_str()),
+                    None,
+                )
+                .await
+                .unwrap();
+            messages.push(match role {
+                MessageRole::User => ContextMessage::prompt(parent, content),
+                _ => ContextMessage::new(parent, ContextRole::Assistant, content),
+            });
+        }
+    }
+    assert_eq!(find_cached_summary(&cone_storage, &cone, &messages).await.unwrap(), None);
+
+    // A summary cached at the end of the first turn is found again...
+    let first_turn_end = messages[1].node_id;
+    cone_storage
+        .summary_store(&cone.id, &Position::new(cone.head.tree_id, first_turn_end), "The user said hi.", None)
+        .await
+        .unwrap();
+    let cached = find_cached_summary(&cone_storage, &cone, &messages).await.unwrap();
+    assert_eq!(cached, Some((1, "The user said hi.".to_string())));
+
+    // ...from the metadata of the node it ends at, without adding any nodes
+    let metadata = arbor.node_get(&cone.head.tree_id, &first_turn_end).await.unwrap().metadata.unwrap();
+    assert_eq!(metadata["cone_summaries"][cone.id.to_string()]["content"], "The user said hi.");
+    let tree = arbor.tree_get(&cone.head.tree_id).await.unwrap();
+    assert_eq!(tree.nodes.len(), 1 + messages.len());
+    assert_eq!(arbor.context_list_leaves(&cone.head.tree_id).await.unwrap(), vec![parent]);
+
+    // While it lags fewer than keep_turns turns it is reused, and the turn it
+    // misses is sent in full instead of being summarized again
+    let llm_registry = cllient::ModelRegistry::new().unwrap();
+    let (summary, sent) = apply_context_policy(&cone_storage, &llm_registry, &cone, messages.clone(), true)
+        .await
+        .unwrap();
+    assert_eq!(summary.as_deref(), Some("The user said hi."));
+    assert_eq!(sent, messages[2..].to_vec());
+}
+
+// ============================================================================
+// Head lineage
+// ============================================================================
+
+#[tokio::test]
+async fn test_cone_head_follows_node_update() {
+    // Negative windows let cleanup purge the old version right away
+    let dir = tempdir().unwrap();
+    let arbor = Arc::new(
+        ArborStorage::new(ArborConfig {
+            db_path: dir.path().join("test_arbor.db"),
+            auto_cleanup: false,
+            scheduled_deletion_window: -1,
+            archive_window: -1,
+            ..Default::default()
+        })
+        .await
+        .unwrap(),
+    );
+    let cone_storage = ConeStorage::new(
+        ConeStorageConfig { db_path: dir.path().join("test_cones.db") },
+        arbor.clone(),
+    )
+    .await
+    .unwrap();
+
+    let cone = cone_storage
+        .cone_create("edited".to_string(), "gpt-4".to_string(), None, vec![], ContextPolicy::Full, None)
+        .await
+        .unwrap();
+    let message = cone_storage
+        .message_create(&cone.id, MessageRole::User, "Hi".to_string(), None, None, None)
+        .await
+        .unwrap();
+    let head = arbor
+        .node_create_external(
+            &cone.head.tree_id,
+            Some(cone.head.node_id),
+            ConeStorage::message_to_handle(&message, "user"),
+            None,
+        )
+        .await
+        .unwrap();
+    cone_storage.cone_update_head(&cone.id, head).await.unwrap();
+
+    // Tag the head node, which replaces it with a new version
+    let updated = arbor
+        .node_update(&cone.head.tree_id, &head, None, None, Some(serde_json::json!({"starred": true})))
+        .await
+        .unwrap();
+    arbor.cleanup_run().await.unwrap();
+    assert!(arbor.node_get(&cone.head.tree_id, &head).await.is_err(), "old version is purged");
+
+    // The cone moves its head to the new version and its context still resolves
+    let reloaded = cone_storage.cone_get(&cone.id).await.unwrap();
+    assert_eq!(reloaded.head.node_id, updated);
+    let path = arbor.context_get_path(&reloaded.head.tree_id, &reloaded.head.node_id).await.unwrap();
+    assert_eq!(path.len(), 2);
+    assert_eq!(path[1].metadata, Some(serde_json::json!({"starred": true})));
+}
+
+// ============================================================================
+// Branching
+// ============================================================================
+
+/// Store a message and attach it to the cone's tree under `parent`
+async fn append_message(
+    cone_storage: &ConeStorage,
+    arbor: &ArborStorage,
+    cone: &ConeConfig,
+    parent: crate::activations::arbor::NodeId,
+    role: MessageRole,
+    content: &str,
+) -> crate::activations::arbor::NodeId {
+    let msg = cone_storage
+        .message_create(&cone.id, role, content.to_string(), None, None, None)
+        .await
+        .unwrap();
+    arbor
+        .node_create_external(
+            &cone.head.tree_id,
+            Some(parent),
+            ConeStorage::message_to_handle(&msg, role.as_str()),
+            None,
+        )
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_regenerate_answers_nearest_user_message() {
+    use super::activation::{regenerate_target, settle_head};
+
+    let (cone_storage, arbor, _dir) = create_test_storage().await;
+    let cone = cone_storage
+        .cone_create("regen".to_string(), "gpt-4".to_string(), None, vec![], ContextPolicy::Full, None)
+        .await
+        .unwrap();
+    let root = cone.head.node_id;
+
+    // root -> user1 -> answer1 -> user2 -> answer2
+    let user1 = append_message(&cone_storage, &arbor, &cone, root, MessageRole::User, "first").await;
+    let answer1 = append_message(&cone_storage, &arbor, &cone, user1, MessageRole::Assistant, "one").await;
+    let user2 = append_message(&cone_storage, &arbor, &cone, answer1, MessageRole::User, "second").await;
+    let answer2 = append_message(&cone_storage, &arbor, &cone, user2, MessageRole::Assistant, "two").await;
+    cone_storage.cone_update_head(&cone.id, answer2).await.unwrap();
+
+    // From an answer or from the user message itself, the nearest user message is chosen
+    for from in [answer2, user2] {
+        let (target, context) = regenerate_target(&cone_storage, &cone.head.tree_id, &from).await.unwrap();
+        assert_eq!(target, user2);
+        assert_eq!(context.iter().map(|n| n.id).collect::<Vec<_>>(), vec![root, user1, answer1, user2]);
+    }
+    let (target, _) = regenerate_target(&cone_storage, &cone.head.tree_id, &answer1).await.unwrap();
+    assert_eq!(target, user1);
+    assert!(regenerate_target(&cone_storage, &cone.head.tree_id, &root).await.is_err());
+
+    // The new answer hangs off the chosen message, next to the old one
+    let regenerated = append_message(&cone_storage, &arbor, &cone, user2, MessageRole::Assistant, "two again").await;
+    let siblings = arbor.node_get(&cone.head.tree_id, &user2).await.unwrap().children;
+    assert_eq!(siblings, vec![answer2, regenerated]);
+
+    // Without move_head the cone keeps its head
+    let head = cone_storage.cone_get(&cone.id).await.unwrap().head;
+    let response = head.advance(regenerated);
+    assert_eq!(settle_head(&cone_storage, &cone.id, head, response, false).await.unwrap(), head);
+    assert_eq!(cone_storage.cone_get(&cone.id).await.unwrap().head.node_id, answer2);
+
+    assert_eq!(settle_head(&cone_storage, &cone.id, head, response, true).await.unwrap(), response);
+    assert_eq!(cone_storage.cone_get(&cone.id).await.unwrap().head.node_id, regenerated);
+}
+
+#[tokio::test]
+async fn test_edit_branches_beside_the_original_message() {
+    use super::activation::{edit_target, settle_head};
+
+    let (cone_storage, arbor, _dir) = create_test_storage().await;
+    let cone = cone_storage
+        .cone_create("editor".to_string(), "gpt-4".to_string(), None, vec![], ContextPolicy::Full, None)
+        .await
+        .unwrap();
+    let tree_id = cone.head.tree_id;
+    let root = cone.head.node_id;
+
+    // root -> user1 -> answer1 -> user2 -> answer2
+    let user1 = append_message(&cone_storage, &arbor, &cone, root, MessageRole::User, "first").await;
+    let answer1 = append_message(&cone_storage, &arbor, &cone, user1, MessageRole::Assistant, "one").await;
+    let user2 = append_message(&cone_storage, &arbor, &cone, answer1, MessageRole::User, "second").await;
+    let answer2 = append_message(&cone_storage, &arbor, &cone, user2, MessageRole::Assistant, "two").await;
+    cone_storage.cone_update_head(&cone.id, answer2).await.unwrap();
+
+    // Only user messages can be edited; the root is not one
+    assert!(edit_target(&cone_storage, &tree_id, &answer2).await.is_err());
+    assert!(edit_target(&cone_storage, &tree_id, &root).await.is_err());
+
+    // The edit goes under the original's parent, with the context above it
+    let (parent, context) = edit_target(&cone_storage, &tree_id, &user2).await.unwrap();
+    assert_eq!(parent, answer1);
+    assert_eq!(context.iter().map(|n| n.id).collect::<Vec<_>>(), vec![root, user1, answer1]);
+
+    let edited = append_message(&cone_storage, &arbor, &cone, parent, MessageRole::User, "second, edited").await;
+    let reply = append_message(&cone_storage, &arbor, &cone, edited, MessageRole::Assistant, "two, again").await;
+    assert_eq!(arbor.node_get(&tree_id, &answer1).await.unwrap().children, vec![user2, edited]);
+
+    // The original branch is untouched
+    let old_path = arbor.node_get_path(&tree_id, &answer2).await.unwrap();
+    assert_eq!(old_path, vec![root, user1, answer1, user2, answer2]);
+
+    // The head moves to the new branch
+    let head = cone_storage.cone_get(&cone.id).await.unwrap().head;
+    settle_head(&cone_storage, &cone.id, head, head.advance(reply), true).await.unwrap();
+    let head = cone_storage.cone_get(&cone.id).await.unwrap().head;
+    assert_eq!(head.node_id, reply);
+    assert_eq!(
+        arbor.node_get_path(&tree_id, &head.node_id).await.unwrap(),
+        vec![root, user1, answer1, edited, reply]
+    );
+}
@@ -0,0 +1,317 @@
+//! Plexus methods exposed to cone models as tools
+//!
+//! `cllient` streams plain text, so tools use a prompted protocol: the tool
+//! catalog (derived from each plugin's schema) is appended to the system
+//! prompt, the model asks for a call with a `<tool_call>` block, and Cone
+//! runs it through the hub and answers with a `<tool_result>` block.
+
+use crate::plexus::{HubContext, PlexusStreamItem};
+use futures::StreamExt;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Most tool calls a single chat turn may make before the reply is final
+pub const MAX_TOOL_ROUNDS: usize = 8;
+
+/// Tool output beyond this many bytes is cut before it reaches the model
+const MAX_TOOL_OUTPUT_BYTES: usize = 32 * 1024;
+
+const CALL_OPEN: &str = "<tool_call>";
+const CALL_CLOSE: &str = "</tool_call>";
+
+/// A Plexus method offered to the model
+#[derive(Debug, Clone, PartialEq)]
+pub struct ToolSpec {
+    /// Full method path, e.g. `bash.execute`
+    pub name: String,
+    pub description: String,
+    /// JSON schema of the method params (always an object schema)
+    pub parameters: Value,
+}
+
+/// A tool call parsed from a model reply
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct ToolCall {
+    pub name: String,
+    #[serde(default = "empty_object")]
+    pub arguments: Value,
+}
+
+fn empty_object() -> Value {
+    json!({})
+}
+
+/// The parts of a plugin schema needed to describe its methods
+#[derive(Debug, Deserialize)]
+struct SchemaDoc {
+    methods: Vec<MethodDoc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MethodDoc {
+    name: String,
+    #[serde(default)]
+    description: String,
+    params: Option<Value>,
+}
+
+/// Split `bash.execute` into (`bash`, `execute`); nested hubs keep their path
+/// in the namespace (`solar.earth.info` -> (`solar.earth`, `info`))
+pub fn split_tool_name(name: &str) -> Result<(&str, &str), String> {
+    match name.rsplit_once('.') {
+        Some((namespace, method)) if !namespace.is_empty() && !method.is_empty() => {
+            Ok((namespace, method))
+        }
+        _ => Err(format!("Invalid tool '{}': expected namespace.method", name)),
+    }
+}
+
+/// Look up each tool's schema through the hub
+///
+/// Fetches every namespace's schema once; fails if a method does not exist.
+pub async fn load_tool_specs<P: HubContext>(
+    parent: &P,
+    tools: &[String],
+) -> Result<Vec<ToolSpec>, String> {
+    let mut schemas: Vec<(&str, SchemaDoc)> = Vec::new();
+    let mut specs = Vec::with_capacity(tools.len());
+
+    for tool in tools {
+        let (namespace, method) = split_tool_name(tool)?;
+        if !schemas.iter().any(|(ns, _)| *ns == namespace) {
+            schemas.push((namespace, fetch_schema(parent, namespace).await?));
+        }
+        let (_, schema) = schemas.iter().find(|(ns, _)| *ns == namespace).unwrap();
+
+        let method_doc = schema
+            .methods
+            .iter()
+            .find(|m| m.name == method)
+            .ok_or_else(|| format!("Unknown tool '{}': {} has no method '{}'", tool, namespace, method))?;
+        specs.push(tool_spec(namespace, method_doc));
+    }
+
+    Ok(specs)
+}
+
+/// Tool specs already loaded, keyed by a cone's tool list
+///
+/// Plugin schemas are fixed once the hub is built, so each distinct tool
+/// list is looked up once per process instead of once per turn. A cone whose
+/// tools change simply misses the cache under its new list.
+#[derive(Clone, Default)]
+pub struct ToolSpecCache {
+    inner: Arc<RwLock<HashMap<Vec<String>, Vec<ToolSpec>>>>,
+}
+
+impl ToolSpecCache {
+    /// Cached specs for `tools`, loading them through the hub on a miss
+    pub async fn get_or_load<P: HubContext>(&self, parent: &P, tools: &[String]) -> Result<Vec<ToolSpec>, String> {
+        let cached = self.inner.read().unwrap_or_else(|e| e.into_inner()).get(tools).cloned();
+        if let Some(specs) = cached {
+            return Ok(specs);
+        }
+
+        let specs = load_tool_specs(parent, tools).await?;
+        self.inner
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(tools.to_vec(), specs.clone());
+        Ok(specs)
+    }
+}
+
+async fn fetch_schema<P: HubContext>(parent: &P, namespace: &str) -> Result<SchemaDoc, String> {
+    let mut stream = parent
+        .call(&format!("{}.schema", namespace), json!({}))
+        .await
+        .map_err(|e| format!("Failed to fetch {} schema: {}", namespace, e))?;
+
+    while let Some(item) = stream.next().await {
+        match item {
+            PlexusStreamItem::Data { content, .. } => {
+                return serde_json::from_value(content)
+                    .map_err(|e| format!("Invalid {} schema: {}", namespace, e));
+            }
+            PlexusStreamItem::Error { message, .. } => {
+                return Err(format!("Failed to fetch {} schema: {}", namespace, message));
+            }
+            PlexusStreamItem::Done { .. } => break,
+            _ => continue,
+        }
+    }
+
+    Err(format!("Empty schema for {}", namespace))
+}
+
+/// Build a tool from a method schema, ensuring an object params schema
+fn tool_spec(namespace: &str, method: &MethodDoc) -> ToolSpec {
+    let parameters = match method.params.clone() {
+        Some(Value::Object(mut obj)) => {
+            obj.entry("type").or_insert_with(|| json!("object"));
+            Value::Object(obj)
+        }
+        _ => json!({"type": "object"}),
+    };
+
+    ToolSpec {
+        name: format!("{}.{}", namespace, method.name),
+        description: method.description.clone(),
+        parameters,
+    }
+}
+
+/// System prompt section describing the tools and the call protocol
+pub fn tools_prompt(specs: &[ToolSpec]) -> String {
+    let mut prompt = String::from(
+        "You can call the tools below. To call one, reply with a single block\n\
+         <tool_call>{\"name\": \"<tool>\", \"arguments\": {...}}</tool_call>\n\
+         and nothing after it. The result comes back in a <tool_result> block; \
+         call another tool or give your final answer once you have what you need.\n\n\
+         Tools:\n",
+    );
+    for spec in specs {
+        prompt.push_str(&format!("- {}: {}\n  parameters: {}\n", spec.name, spec.description, spec.parameters));
+    }
+    prompt
+}
+
+/// Find a tool call in a model reply
+///
+/// Returns None when the reply contains no `<tool_call>` block, and an error
+/// when the block is not a valid call (the model is told and may retry).
+pub fn parse_tool_call(reply: &str) -> Option<Result<ToolCall, String>> {
+    let start = reply.find(CALL_OPEN)? + CALL_OPEN.len();
+    let body = &reply[start..];
+    let body = body.find(CALL_CLOSE).map_or(body, |end| &body[..end]);
+
+    Some(serde_json::from_str::<ToolCall>(body.trim()).map_err(|e| e.to_string()))
+}
+
+/// Remove a tool call the model made after running out of tool rounds
+///
+/// Returns None when the reply has no `<tool_call>` block, otherwise the text
+/// before it. The caller replaces the call with `tool_limit_note()`.
+pub fn strip_tool_call(reply: &str) -> Option<&str> {
+    let start = reply.find(CALL_OPEN)?;
+    Some(reply[..start].trim_end())
+}
+
+/// Stands in for a tool call that was not run because the turn hit its limit
+pub fn tool_limit_note() -> String {
+    format!("[Tool call not run: the limit of {} tool calls per turn was reached]", MAX_TOOL_ROUNDS)
+}
+
+/// Run a tool call through the hub
+///
+/// Returns the output and whether it is an error. Data items are joined into
+/// one JSON value (an array when there are several).
+pub async fn execute_tool<P: HubContext>(parent: &P, call: &ToolCall) -> (String, bool) {
+    let mut stream = match parent.call(&call.name, call.arguments.clone()).await {
+        Ok(stream) => stream,
+        Err(e) => return (format!("{}", e), true),
+    };
+
+    let mut items = Vec::new();
+    while let Some(item) = stream.next().await {
+        match item {
+            PlexusStreamItem::Data { content, .. } => items.push(content),
+            PlexusStreamItem::Error { message, .. } => return (message, true),
+            PlexusStreamItem::Done { .. } => break,
+            _ => continue,
+        }
+    }
+
+    let output = match items.len() {
+        0 => Value::Null,
+        1 => items.remove(0),
+        _ => Value::Array(items),
+    };
+    let output = match output {
+        Value::String(text) => text,
+        other => other.to_string(),
+    };
+
+    (truncate_output(output), false)
+}
+
+fn truncate_output(mut output: String) -> String {
+    if output.len() <= MAX_TOOL_OUTPUT_BYTES {
+        return output;
+    }
+    let mut end = MAX_TOOL_OUTPUT_BYTES;
+    while !output.is_char_boundary(end) {
+        end -= 1;
+    }
+    let dropped = output.len() - end;
+    output.truncate(end);
+    output.push_str(&format!("\n[truncated {} bytes]", dropped));
+    output
+}
+
+/// Message sent back to the model with a tool's output
+pub fn format_tool_result(name: &str, output: &str) -> String {
+    format!("<tool_result name=\"{}\">\n{}\n</tool_result>", name, output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tool_call() {
+        assert_eq!(parse_tool_call("Just an answer."), None);
+
+        let reply = "Let me check.\n<tool_call>{\"name\": \"bash.execute\", \"arguments\": {\"command\": \"ls\"}}</tool_call>";
+        assert_eq!(
+            parse_tool_call(reply),
+            Some(Ok(ToolCall { name: "bash.execute".into(), arguments: json!({"command": "ls"}) }))
+        );
+
+        // Missing close tag and arguments are tolerated, bad JSON is reported
+        let call = parse_tool_call("<tool_call> {\"name\": \"health.check\"} ").unwrap().unwrap();
+        assert_eq!(call.arguments, json!({}));
+        assert!(parse_tool_call("<tool_call>{name: oops}</tool_call>").unwrap().is_err());
+    }
+
+    #[test]
+    fn test_strip_tool_call() {
+        assert_eq!(strip_tool_call("Just an answer."), None);
+        assert_eq!(
+            strip_tool_call("Almost there.\n<tool_call>{\"name\": \"bash.execute\"}</tool_call>"),
+            Some("Almost there.")
+        );
+        assert_eq!(strip_tool_call("<tool_call>{}</tool_call>"), Some(""));
+    }
+
+    #[test]
+    fn test_tool_spec_from_schema() {
+        let schema: SchemaDoc = serde_json::from_value(json!({
+            "namespace": "bash",
+            "methods": [
+                {"name": "execute", "description": "Run a command", "params": {"properties": {"command": {"type": "string"}}}},
+                {"name": "schema", "description": "Get schema"}
+            ]
+        }))
+        .unwrap();
+
+        let execute = tool_spec("bash", &schema.methods[0]);
+        assert_eq!(execute.name, "bash.execute");
+        assert_eq!(execute.parameters["type"], "object");
+        assert_eq!(execute.parameters["properties"]["command"]["type"], "string");
+        assert_eq!(tool_spec("bash", &schema.methods[1]).parameters, json!({"type": "object"}));
+
+        assert_eq!(split_tool_name("solar.earth.info"), Ok(("solar.earth", "info")));
+        assert!(split_tool_name("execute").is_err());
+        assert!(tools_prompt(&[execute]).contains("- bash.execute: Run a command"));
+    }
+
+    #[test]
+    fn test_truncate_output() {
+        let long = "é".repeat(MAX_TOOL_OUTPUT_BYTES);
+        let cut = truncate_output(long);
+        assert!(cut.ends_with(&format!("[truncated {} bytes]", MAX_TOOL_OUTPUT_BYTES)));
+    }
+}
@@ -32,7 +32,7 @@ pub enum ConeHandle {
     Message {
         /// Message ID with "msg-" prefix (e.g., "msg-550e8400-...")
         message_id: String,
-        /// Role: "user", "assistant", or "system"
+        /// Role: "user", "assistant", "system", "tool_use" or "tool_result"
         role: String,
         /// Display name (cone name or "user")
         name: String,
@@ -49,6 +49,10 @@ pub enum MessageRole {
     User,
     Assistant,
     System,
+    /// Assistant reply that calls a tool
+    ToolUse,
+    /// Output of a tool call
+    ToolResult,
 }
 
 impl MessageRole {
@@ -57,6 +61,8 @@ impl MessageRole {
             MessageRole::User => "user",
             MessageRole::Assistant => "assistant",
             MessageRole::System => "system",
+            MessageRole::ToolUse => "tool_use",
+            MessageRole::ToolResult => "tool_result",
         }
     }
 
@@ -65,6 +71,8 @@ impl MessageRole {
             "user" => Some(MessageRole::User),
             "assistant" => Some(MessageRole::Assistant),
             "system" => Some(MessageRole::System),
+            "tool_use" => Some(MessageRole::ToolUse),
+            "tool_result" => Some(MessageRole::ToolResult),
             _ => None,
         }
     }
@@ -110,6 +118,37 @@ impl Position {
     }
 }
 
+/// How much of a cone's history is sent with each request
+///
+/// A turn starts at each user prompt and runs until the next one.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize, schemars::JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ContextPolicy {
+    /// Send the whole root-to-head path
+    #[default]
+    Full,
+    /// Drop the oldest turns until the messages fit in an estimated token budget
+    /// (the system prompt is not counted; the current turn is always kept)
+    MaxTokens { max_tokens: usize },
+    /// Send only the last `turns` turns
+    LastTurns { turns: usize },
+    /// Keep the last `keep_turns` turns and replace older ones with a summary,
+    /// cached in the metadata of the last arbor node it covers and refreshed
+    /// once it falls `keep_turns` turns behind
+    Summarize { keep_turns: usize },
+}
+
+impl ContextPolicy {
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            ContextPolicy::MaxTokens { max_tokens: 0 } => Err("max_tokens must be at least 1".into()),
+            ContextPolicy::LastTurns { turns: 0 } => Err("turns must be at least 1".into()),
+            ContextPolicy::Summarize { keep_turns: 0 } => Err("keep_turns must be at least 1".into()),
+            _ => Ok(()),
+        }
+    }
+}
+
 /// Cone configuration - defines an cone's identity and behavior
 #[derive(Debug, Clone, Serialize, Deserialize, schemars::JsonSchema)]
 pub struct ConeConfig {
@@ -124,6 +163,12 @@ pub struct ConeConfig {
     /// The canonical head - current position in conversation tree
     /// This couples tree_id and node_id together
     pub head: Position,
+    /// Plexus methods the model may call as tools (e.g., "bash.execute")
+    #[serde(default)]
+    pub tools: Vec<String>,
+    /// What part of the history each request includes
+    #[serde(default)]
+    pub context_policy: ContextPolicy,
     /// Additional configuration metadata
     pub metadata: Option<Value>,
     /// Created timestamp
@@ -232,12 +277,34 @@ pub enum ChatEvent {
         cone_id: ConeId,
         content: String,
     },
+    /// The model called a tool (stored as a tool_use node)
+    #[serde(rename = "chat_tool_use")]
+    ToolUse {
+        cone_id: ConeId,
+        tool_name: String,
+        /// Message ID of the tool_use message
+        tool_use_id: String,
+        input: Value,
+        position: Position,
+    },
+    /// A tool call finished (stored as a tool_result node under the call)
+    #[serde(rename = "chat_tool_result")]
+    ToolResult {
+        cone_id: ConeId,
+        tool_use_id: String,
+        output: String,
+        is_error: bool,
+        position: Position,
+    },
     /// Chat response complete
     #[serde(rename = "chat_complete")]
     Complete {
         cone_id: ConeId,
         /// The new head position (tree + response node)
         new_head: Position,
+        /// Position of the response node (differs from new_head when the
+        /// head was not moved, e.g. ephemeral chats)
+        response: Position,
         /// Total tokens used (if available)
         usage: Option<ChatUsage>,
     },
@@ -1,6 +1,7 @@
 //! Mustache template storage using SQLite
 
 use super::types::{MustacheError, TemplateInfo};
+use crate::migrations::{self, Migration};
 use sqlx::{sqlite::{SqliteConnectOptions, SqlitePool}, ConnectOptions, Row};
 use std::path::PathBuf;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -21,6 +22,29 @@ impl Default for MustacheStorageConfig {
     }
 }
 
+/// Mustache schema migrations, applied in order by `run_migrations`
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "templates",
+        sql: r#"
+            CREATE TABLE IF NOT EXISTS templates (
+                id TEXT PRIMARY KEY,
+                plugin_id TEXT NOT NULL,
+                method TEXT NOT NULL,
+                name TEXT NOT NULL,
+                template TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                updated_at INTEGER NOT NULL,
+                UNIQUE(plugin_id, method, name)
+            );
+
+            CREATE INDEX IF NOT EXISTS idx_templates_plugin ON templates(plugin_id);
+            CREATE INDEX IF NOT EXISTS idx_templates_lookup ON templates(plugin_id, method, name);
+        "#,
+    },
+];
+
 /// Storage layer for mustache templates
 pub struct MustacheStorage {
     pool: SqlitePool,
@@ -45,27 +69,7 @@ impl MustacheStorage {
 
     /// Run database migrations
     async fn run_migrations(&self) -> Result<(), MustacheError> {
-        sqlx::query(
-            r#"
-            CREATE TABLE IF NOT EXISTS templates (
-                id TEXT PRIMARY KEY,
-                plugin_id TEXT NOT NULL,
-                method TEXT NOT NULL,
-                name TEXT NOT NULL,
-                template TEXT NOT NULL,
-                created_at INTEGER NOT NULL,
-                updated_at INTEGER NOT NULL,
-                UNIQUE(plugin_id, method, name)
-            );
-
-            CREATE INDEX IF NOT EXISTS idx_templates_plugin ON templates(plugin_id);
-            CREATE INDEX IF NOT EXISTS idx_templates_lookup ON templates(plugin_id, method, name);
-            "#,
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to run mustache migrations: {}", e))?;
-
+        migrations::run_migrations(&self.pool, "mustache", MIGRATIONS).await?;
         Ok(())
     }
 
@@ -0,0 +1,214 @@
+//! Versioned SQLite schema migrations shared by the activation storages
+//!
+//! Each storage declares an ordered list of [`Migration`]s and calls
+//! [`run_migrations`] when it opens its database. Applied versions are
+//! recorded per component in a `schema_migrations` table, so several
+//! components may share one database file. Pending migrations are applied
+//! in order, each in its own transaction, and a database whose recorded
+//! version is newer than the newest known migration is refused rather than
+//! opened with a schema this build does not understand.
+//!
+//! Version 1 of every component is its original `CREATE ... IF NOT EXISTS`
+//! schema, which makes it safe to apply to databases created before
+//! versioning existed.
+
+use sqlx::{Row, SqlitePool};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A single schema migration
+#[derive(Debug, Clone, Copy)]
+pub struct Migration {
+    /// Schema version this migration brings the database to (1-based, ascending)
+    pub version: i64,
+    /// Short human-readable description, recorded alongside the version
+    pub description: &'static str,
+    /// SQL to execute; may contain multiple statements
+    pub sql: &'static str,
+}
+
+/// Apply all pending migrations for `component`
+///
+/// Returns the schema version the database is at afterwards.
+pub async fn run_migrations(
+    pool: &SqlitePool,
+    component: &str,
+    migrations: &[Migration],
+) -> Result<i64, String> {
+    validate(component, migrations)?;
+
+    sqlx::query(
+        r#"
+        CREATE TABLE IF NOT EXISTS schema_migrations (
+            component TEXT NOT NULL,
+            version INTEGER NOT NULL,
+            description TEXT NOT NULL,
+            applied_at INTEGER NOT NULL,
+            PRIMARY KEY (component, version)
+        )
+        "#,
+    )
+    .execute(pool)
+    .await
+    .map_err(|e| format!("Failed to create schema_migrations table: {}", e))?;
+
+    let current = schema_version(pool, component).await?;
+    let latest = migrations.last().map_or(0, |m| m.version);
+
+    if current > latest {
+        return Err(format!(
+            "Database schema for {} is at version {}, newer than the latest supported version {}; refusing to open",
+            component, current, latest
+        ));
+    }
+
+    for migration in migrations.iter().filter(|m| m.version > current) {
+        let mut tx = pool
+            .begin()
+            .await
+            .map_err(|e| format!("Failed to begin {} migration: {}", component, e))?;
+
+        sqlx::query(migration.sql)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| {
+                format!(
+                    "Failed to run {} migration {} ({}): {}",
+                    component, migration.version, migration.description, e
+                )
+            })?;
+
+        sqlx::query(
+            "INSERT INTO schema_migrations (component, version, description, applied_at)
+             VALUES (?, ?, ?, ?)",
+        )
+        .bind(component)
+        .bind(migration.version)
+        .bind(migration.description)
+        .bind(current_timestamp())
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to record {} migration {}: {}", component, migration.version, e))?;
+
+        tx.commit()
+            .await
+            .map_err(|e| format!("Failed to commit {} migration {}: {}", component, migration.version, e))?;
+    }
+
+    Ok(current.max(latest))
+}
+
+/// Current schema version recorded for `component` (0 if none)
+pub async fn schema_version(pool: &SqlitePool, component: &str) -> Result<i64, String> {
+    let row = sqlx::query(
+        "SELECT COALESCE(MAX(version), 0) AS version FROM schema_migrations WHERE component = ?",
+    )
+    .bind(component)
+    .fetch_one(pool)
+    .await
+    .map_err(|e| format!("Failed to read {} schema version: {}", component, e))?;
+
+    Ok(row.get("version"))
+}
+
+/// Migrations must be numbered 1, 2, 3, ... with no gaps or repeats
+fn validate(component: &str, migrations: &[Migration]) -> Result<(), String> {
+    for (i, migration) in migrations.iter().enumerate() {
+        let expected = i as i64 + 1;
+        if migration.version != expected {
+            return Err(format!(
+                "Invalid {} migration list: expected version {}, found {}",
+                component, expected, migration.version
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn current_timestamp() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlx::sqlite::SqliteConnectOptions;
+    use tempfile::{tempdir, TempDir};
+
+    const V1: Migration = Migration {
+        version: 1,
+        description: "create items",
+        sql: "CREATE TABLE IF NOT EXISTS items (id TEXT PRIMARY KEY);",
+    };
+
+    const V2: Migration = Migration {
+        version: 2,
+        description: "add items.label",
+        sql: "ALTER TABLE items ADD COLUMN label TEXT;",
+    };
+
+    async fn create_test_pool() -> (SqlitePool, TempDir) {
+        let dir = tempdir().unwrap();
+        let options = SqliteConnectOptions::new()
+            .filename(dir.path().join("test_migrations.db"))
+            .create_if_missing(true);
+        let pool = SqlitePool::connect_with(options).await.unwrap();
+        (pool, dir)
+    }
+
+    #[tokio::test]
+    async fn test_applies_pending_migrations_once() {
+        let (pool, _dir) = create_test_pool().await;
+
+        assert_eq!(run_migrations(&pool, "test", &[V1]).await.unwrap(), 1);
+        // Re-running is a no-op; adding V2 applies only V2
+        assert_eq!(run_migrations(&pool, "test", &[V1]).await.unwrap(), 1);
+        assert_eq!(run_migrations(&pool, "test", &[V1, V2]).await.unwrap(), 2);
+        assert_eq!(run_migrations(&pool, "test", &[V1, V2]).await.unwrap(), 2);
+
+        sqlx::query("INSERT INTO items (id, label) VALUES ('a', 'x')")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        // Versions are tracked per component
+        assert_eq!(schema_version(&pool, "other").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_migration_rolls_back() {
+        let (pool, _dir) = create_test_pool().await;
+        let broken = Migration {
+            version: 2,
+            description: "broken",
+            sql: "CREATE TABLE extra (id TEXT); SELECT * FROM missing_table;",
+        };
+
+        run_migrations(&pool, "test", &[V1]).await.unwrap();
+        assert!(run_migrations(&pool, "test", &[V1, broken]).await.is_err());
+        assert_eq!(schema_version(&pool, "test").await.unwrap(), 1);
+
+        let extra = sqlx::query("SELECT name FROM sqlite_master WHERE name = 'extra'")
+            .fetch_optional(&pool)
+            .await
+            .unwrap();
+        assert!(extra.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_refuses_newer_database() {
+        let (pool, _dir) = create_test_pool().await;
+
+        run_migrations(&pool, "test", &[V1, V2]).await.unwrap();
+        let err = run_migrations(&pool, "test", &[V1]).await.unwrap_err();
+        assert!(err.contains("newer"), "unexpected error: {}", err);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_misnumbered_migrations() {
+        let (pool, _dir) = create_test_pool().await;
+        assert!(run_migrations(&pool, "test", &[V2]).await.is_err());
+    }
+}